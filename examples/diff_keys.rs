@@ -0,0 +1,28 @@
+//! Compare the keys of two example configuration files.
+//!
+//! ```bash
+//! cargo run --example diff_keys -- old/config.toml new/config.toml
+//! ```
+
+use anyhow::Context;
+
+pub fn main() -> anyhow::Result<()> {
+    let mut args = std::env::args().skip(1);
+    let (old_path, new_path) = match (args.next(), args.next()) {
+        (Some(old_path), Some(new_path)) => (old_path, new_path),
+        _ => anyhow::bail!("Usage: diff_keys <OLD_CONFIG> <NEW_CONFIG>"),
+    };
+
+    let read = |path: &str| -> anyhow::Result<toml::Value> {
+        let value =
+            std::fs::read_to_string(path).with_context(|| format!("Error reading {path}"))?;
+        toml::from_str(&value).with_context(|| format!("Error parsing {path}"))
+    };
+
+    let diff = toml_env::diff_keys(&read(&old_path)?, &read(&new_path)?);
+    print!("{diff}");
+    if !diff.is_empty() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
@@ -1,8 +1,8 @@
-use std::{path::Path, str::FromStr};
+use std::path::Path;
 
 use anyhow::Context;
 use serde::{Deserialize, Serialize};
-use toml_env::{self, Args, AutoMapEnvArgs, TomlKeyPath};
+use toml_env::{self, map_env, Args, AutoMapEnvArgs};
 
 #[derive(Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
@@ -30,13 +30,10 @@ pub fn main() -> anyhow::Result<()> {
     } = toml_env::initialize(Args {
         logging: toml_env::Logging::StdOut,
         config_path: Some("config.toml".as_ref()),
-        map_env: [
-            ("ENV_VAR", "child.value_1"),
-            ("MY_APP__CHILD__VALUE_7", "child.value_3"),
-        ]
-        .into_iter()
-        .map(|(name, key)| (name, TomlKeyPath::from_str(key).unwrap()))
-        .collect(),
+        map_env: map_env! {
+            "ENV_VAR" => "child.value_1",
+            "MY_APP__CHILD__VALUE_7" => "child.value_3",
+        },
         auto_map_env: Some(AutoMapEnvArgs {
             prefix: Some("MY_APP"),
             ..AutoMapEnvArgs::default()
@@ -1,4 +1,13 @@
-#![doc = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/README.md"))]
+// The examples in the README use the default features, so they are only collected as doctests
+// with them enabled. The README is the crate documentation in every feature configuration.
+#![cfg_attr(
+    any(not(doctest), all(feature = "dotenv", feature = "auto-map-env")),
+    doc = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/README.md"))
+)]
+#![cfg_attr(
+    all(doctest, not(all(feature = "dotenv", feature = "auto-map-env"))),
+    doc = "A simple configuration library using `toml`."
+)]
 #![deny(missing_docs)]
 
 // NOTE: This crate intentionally uses a single module in order to put pressure on keeping the
@@ -6,12 +15,16 @@
 
 use std::{
     borrow::Cow,
-    collections::{BTreeMap, HashMap},
+    collections::{BTreeMap, BTreeSet, HashMap},
+    ffi::{OsStr, OsString},
     path::{Path, PathBuf},
     str::FromStr,
+    sync::Arc,
+    time::{Duration, SystemTime},
 };
 
 use serde::{de::DeserializeOwned, Serialize};
+use smallvec::SmallVec;
 use thiserror::Error;
 use toml::Value;
 
@@ -21,6 +34,10 @@ pub type Result<T> = std::result::Result<T, Error>;
 /// Default name for attempting to load the configuration (and environment variables) from a file.
 pub const DEFAULT_DOTENV_PATH: &str = ".env.toml";
 
+/// Conventional name for a `.env.toml` format file with per-developer overrides (which is not
+/// committed to version control), see [`Args::dotenv_local_path`].
+pub const DEFAULT_DOTENV_LOCAL_PATH: &str = ".env.local.toml";
+
 /// Default environment variable name to use for loading configuration from. Also the same name
 /// used for the table of the configuration within the `.env.toml`.
 pub const DEFAULT_CONFIG_VARIABLE_NAME: &str = "CONFIG";
@@ -29,6 +46,18 @@ pub const DEFAULT_CONFIG_VARIABLE_NAME: &str = "CONFIG";
 /// This will be replaced with a `.` for the [`TomlKeyPath`].
 pub const DEFAULT_MAP_ENV_DIVIDER: &str = "__";
 
+/// The conventional suffix for environment variables which refer to a file containing the value
+/// of the variable without the suffix, see [`Args::file_suffix`].
+pub const DEFAULT_FILE_SUFFIX: &str = "_FILE";
+
+/// The conventional value for environment variables which remove the key they are mapped to, see
+/// [`Args::unset_sentinel`].
+pub const DEFAULT_UNSET_SENTINEL: &str = "__UNSET__";
+
+/// Prefix for the values of environment variables which are base64 encoded, see
+/// [`Args::decode_base64`].
+pub const BASE64_PREFIX: &str = "base64:";
+
 /// A source of configuration.
 #[derive(Debug, Clone)]
 pub enum ConfigSource {
@@ -43,11 +72,39 @@ pub enum ConfigSource {
     DotEnv(PathBuf),
     /// From a configuration file.
     File(PathBuf),
+    /// From the embedded defaults in [`Args::defaults`].
+    Defaults,
     /// From environment variables.
     Environment {
-        /// The names of the environment variables.
+        /// The names of the environment variables, sorted by name.
+        variable_names: Vec<String>,
+    },
+    /// From configuration supplied as a string in [`Args::config_inline`] or
+    /// [`Args::dotenv_inline`].
+    Inline {
+        /// The label of the configuration, see [`InlineSource::label`].
+        label: String,
+    },
+    /// From environment variables which were already loaded from a `.env` file (e.g. by
+    /// `dotenvy`), see [`Args::preloaded_dotenv_path`].
+    PreloadedDotEnv {
+        /// Path to the `.env` file.
+        path: PathBuf,
+        /// The names of the environment variables, sorted by name.
         variable_names: Vec<String>,
     },
+    /// From a custom [`Source`] in [`Args::sources`].
+    Custom {
+        /// The description of the source, see [`Source::description`].
+        description: String,
+        /// The kind of the source, see [`Source::kind`].
+        kind: SourceKind,
+    },
+    /// From a [`ConfigLocation::Url`].
+    Remote {
+        /// The URL the configuration was fetched from.
+        url: String,
+    },
 }
 
 impl std::fmt::Display for ConfigSource {
@@ -56,1001 +113,12151 @@ impl std::fmt::Display for ConfigSource {
             ConfigSource::Merged { from, into } => write!(f, "({from}) merged into ({into})"),
             ConfigSource::DotEnv(path) => write!(f, "dotenv TOML file {path:?}"),
             ConfigSource::File(path) => write!(f, "config TOML file {path:?}"),
+            ConfigSource::Defaults => f.write_str("embedded defaults"),
             ConfigSource::Environment { variable_names } => {
                 let variable_names = variable_names.join(", ");
                 write!(f, "environment variables {variable_names}")
             }
+            ConfigSource::Inline { label } => write!(f, "inline configuration {label:?}"),
+            ConfigSource::PreloadedDotEnv {
+                path,
+                variable_names,
+            } => {
+                let variable_names = variable_names.join(", ");
+                write!(
+                    f,
+                    "environment variables {variable_names} preloaded from {path:?}"
+                )
+            }
+            ConfigSource::Custom { description, kind } => write!(f, "{kind} {description}"),
+            ConfigSource::Remote { url } => write!(f, "remote configuration {url:?}"),
         }
     }
 }
 
-/// An error that occurs while initializing configuration.
-#[derive(Debug, Error)]
-#[error(transparent)]
-pub struct Error(#[from] InnerError);
+impl ConfigSource {
+    /// The kind of this source, or `None` for [`ConfigSource::Merged`].
+    pub fn kind(&self) -> Option<SourceKind> {
+        match self {
+            ConfigSource::Merged { .. } => None,
+            ConfigSource::DotEnv(_) => Some(SourceKind::DotEnv),
+            ConfigSource::File(_) => Some(SourceKind::File),
+            ConfigSource::Defaults => Some(SourceKind::Defaults),
+            ConfigSource::Environment { .. } => Some(SourceKind::Environment),
+            ConfigSource::Inline { .. } => Some(SourceKind::Inline),
+            ConfigSource::PreloadedDotEnv { .. } => Some(SourceKind::PreloadedDotEnv),
+            ConfigSource::Custom { kind, .. } => Some(*kind),
+            ConfigSource::Remote { .. } => Some(SourceKind::Remote),
+        }
+    }
+}
 
-/// An error that occurs while initializing configuration.
-#[derive(Debug, Error)]
+/// A kind of [`ConfigSource`], used in [`KeyPolicy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[non_exhaustive]
-enum InnerError {
-    /// Error reading environment variable.
-    #[error("Error reading {name} environment variable")]
-    ErrorReadingEnvironmentVariable {
-        /// Name of the environment variable.
-        name: String,
-        /// Source of the error.
-        #[source]
-        error: std::env::VarError,
-    },
-    /// Error reading TOML file.
-    #[error("Error reading TOML file {path:?}")]
-    ErrorReadingFile {
-        /// Path to the file.
-        path: PathBuf,
-        /// Source of the error.
-        #[source]
-        error: std::io::Error,
-    },
-    /// Error parsing TOML file.
-    #[error("Error parsing TOML file {path:?}")]
-    ErrorParsingTomlFile {
-        /// Path to the file.
-        path: PathBuf,
-        /// Source of the error.
-        #[source]
-        error: Box<toml::de::Error>,
-    },
-    /// Cannot parse a table in the `.toml.env` file.
-    #[error("Cannot parse {key} as environment variable in {path:?}. Advice: {advice}")]
-    CannotParseTomlDotEnvFile {
-        /// Key in the TOML file.
-        key: String,
-        /// Path to the file.
-        path: PathBuf,
-        /// Advice
-        advice: String,
-    },
-    /// Error parsing envirnment variable
-    #[error("Error parsing config key ({name}) in TOML config file {path:?}")]
-    ErrorParsingTomlDotEnvFileKey {
-        /// Name of the key variable.
-        name: String,
-        /// Path to the file.
-        path: PathBuf,
-        /// Source of the error.
-        #[source]
-        error: Box<toml::de::Error>,
-    },
-    /// Either there was an error parsing the environment variable as the config, or if the value
-    /// is a filename, it does not exist.
-    #[error(
-        "Error parsing config environment variable ({name}={value:?}) as the config or if it is a filename, the file does not exist."
-    )]
-    ErrorParsingEnvironmentVariableAsConfigOrFile {
-        /// Name of the environment variable.
-        name: String,
-        /// Value of the environment variable.
-        value: String,
-        /// Source of the error.
-        #[source]
-        error: Box<toml::de::Error>,
-    },
-    /// Error parsing file as `.env.toml` format.
-    #[error(
-        "Error parsing the {path:?} as `.env.toml` format file:\n{value:#?}\nTop level should be a table."
-    )]
-    UnexpectedTomlDotEnvFileFormat {
-        /// Path to file.
-        path: PathBuf,
-        /// Value that was unable to be parsed as `.env.toml` format
-        value: Value,
-    },
-    /// Error parsing merged configuration.
-    #[error("Error parsing merged configuration from {source}")]
-    ErrorParsingMergedToml {
-        /// Source(s) of the configuration.
-        source: ConfigSource,
-        /// Source of the error.
-        #[source]
-        error: Box<toml::de::Error>,
-    },
-    /// Error merging configurations.
-    #[error("Error merging configuration {from} into {into}: {error}")]
-    ErrorMerging {
-        /// Error merging from this source.
-        from: ConfigSource,
-        /// Error merging into this source.
-        into: ConfigSource,
-        /// Source of the error.
-        error: serde_toml_merge::Error,
-    },
-    #[error("Error inserting toml value")]
-    InsertTomlValueError(#[from] InsertTomlValueError),
+pub enum SourceKind {
+    /// See [`ConfigSource::DotEnv`].
+    DotEnv,
+    /// See [`ConfigSource::File`].
+    File,
+    /// See [`ConfigSource::Defaults`].
+    Defaults,
+    /// See [`ConfigSource::Environment`]. This includes the config stored in the
+    /// [`Args::config_variable_name`] environment variable (a file which it refers to is a
+    /// [`SourceKind::File`]).
+    Environment,
+    /// See [`ConfigSource::Inline`].
+    Inline,
+    /// See [`ConfigSource::PreloadedDotEnv`].
+    PreloadedDotEnv,
+    /// See [`ConfigSource::Custom`].
+    Custom,
+    /// A secret backend (e.g. Vault, or a [`KeyPerFileSource`] for a mounted Kubernetes Secret),
+    /// see [`ConfigSource::Custom`].
+    Secrets,
+    /// See [`ConfigSource::Remote`].
+    Remote,
 }
 
-/// What method of logging for this library to use.
-#[derive(Default, Clone, Copy)]
-pub enum Logging {
-    /// Don't perform any logging
-    #[default]
-    None,
-    /// Use STDOUT for logging. This may be attractive if you are relying on the output of this
-    /// library to configure your logging system and still want to see what's going on here before
-    /// the system is configured.
-    StdOut,
-    /// Use the [`log`] crate for logging.
-    #[cfg(feature = "log")]
-    Log,
+impl std::fmt::Display for SourceKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SourceKind::DotEnv => f.write_str("dotenv TOML file"),
+            SourceKind::File => f.write_str("config TOML file"),
+            SourceKind::Defaults => f.write_str("embedded defaults"),
+            SourceKind::Environment => f.write_str("environment variables"),
+            SourceKind::Inline => f.write_str("inline configuration"),
+            SourceKind::PreloadedDotEnv => f.write_str("preloaded dotenv file"),
+            SourceKind::Custom => f.write_str("custom source"),
+            SourceKind::Secrets => f.write_str("secrets"),
+            SourceKind::Remote => f.write_str("remote configuration"),
+        }
+    }
 }
 
-type InnerResult<T> = std::result::Result<T, InnerError>;
-
-/// A path to a key into a [`toml::Value`]. In the format of `key.0.key` (`0` for indexing into an
-/// array) when parsed using [`FromStr`].
+/// A custom provider of configuration (e.g. an internal configuration store), which is merged with
+/// the other sources, see [`Args::sources`].
 ///
-/// See [`TomlKeyPath::resolve()`] for an example.
-#[derive(Debug, Clone, Default)]
-pub struct TomlKeyPath(Vec<PathElement>);
+/// # Example
+///
+/// ```rust
+/// use toml_env::{initialize, Args, Source};
+///
+/// struct Store;
+///
+/// impl Source for Store {
+///     fn load(
+///         &self,
+///     ) -> Result<Option<toml::Value>, Box<dyn std::error::Error + Send + Sync>> {
+///         Ok(Some(toml::from_str("name = \"from store\"")?))
+///     }
+///
+///     fn description(&self) -> String {
+///         "store".to_owned()
+///     }
+/// }
+///
+/// let config: toml::Value = initialize(Args {
+///     sources: vec![Box::new(Store)],
+///     ..Args::default()
+/// })
+/// .unwrap()
+/// .unwrap();
+/// assert_eq!(config["name"].as_str(), Some("from store"));
+/// ```
+pub trait Source {
+    /// Load the configuration, `None` if there is none.
+    fn load(&self) -> std::result::Result<Option<Value>, Box<dyn std::error::Error + Send + Sync>>;
+    /// A description of the source, used in [`ConfigSource::Custom`] and error messages.
+    fn description(&self) -> String;
+    /// Where the source is merged relative to the other sources.
+    /// [`SourcePrecedence::AfterFile`] by default.
+    fn precedence(&self) -> SourcePrecedence {
+        SourcePrecedence::AfterFile
+    }
+    /// The resources which [`Source::load`] may access, see [`accesses()`]. Empty by default, so
+    /// sources which access files, the network or run commands should declare them.
+    fn accesses(&self) -> Vec<Access> {
+        Vec::new()
+    }
+    /// Whether the source is optional, in which case a failure to load it is skipped with a
+    /// warning (see [`InitializationReport::warnings`]) instead of failing initialization.
+    /// `false` by default, unless the [`Source::kind`] is in the [`Args::optional_sources`].
+    fn optional(&self) -> bool {
+        false
+    }
+    /// The kind of the source, used in [`ConfigSource::Custom`], [`Args::key_policies`] and
+    /// [`Args::optional_sources`]. [`SourceKind::Custom`] by default, the secret backends provided
+    /// by this crate use [`SourceKind::Secrets`] (except [`KeyPerFileSource`], which may be a
+    /// ConfigMap, see [`KeyPerFileSource::kind`]).
+    fn kind(&self) -> SourceKind {
+        SourceKind::Custom
+    }
+}
 
-#[derive(Debug, Clone, Hash, PartialEq, PartialOrd, Ord, Eq)]
-enum PathElement {
-    TableProperty(String),
-    ArrayIndex(usize),
+/// A resource which [`initialize()`] may access, see [`accesses()`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Access {
+    /// A file or directory, which may not exist.
+    Path(PathBuf),
+    /// A network endpoint, as a URL (e.g. `https://vault.internal:8200`).
+    Endpoint(String),
+    /// A command which is run, looked up using the `PATH` if it is not a path.
+    Command(OsString),
 }
 
-impl std::fmt::Display for PathElement {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            PathElement::TableProperty(p) => p.fmt(f),
-            PathElement::ArrayIndex(i) => i.fmt(f),
-        }
-    }
+/// Where a custom [`Source`] is merged relative to the other sources. Sources with the same
+/// precedence are merged in the order of [`Args::sources`], later sources taking precedence.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SourcePrecedence {
+    /// Takes precedence over the [`Args::defaults`], but not the config file.
+    AfterDefaults,
+    /// Takes precedence over the config file (and inline configuration), but not environment
+    /// variables.
+    #[default]
+    AfterFile,
+    /// Takes precedence over all other sources.
+    Highest,
 }
 
-impl TomlKeyPath {
-    /// Resolve a value contained within a [`toml::Value`] using this [`TomlKeyPath`].
-    ///
-    /// # Example
-    ///
-    /// ```rust
-    /// use toml_env::TomlKeyPath;
-    /// use toml;
-    ///
-    /// let toml_value = toml::from_str(r#"
-    /// key="value1"
-    /// array=["hello", "world"]
-    /// [child]
-    /// key="value2"
-    /// "#).unwrap();
-    ///
-    /// let key1: TomlKeyPath = "key".parse().unwrap();
-    /// let key1_value = key1.resolve(&toml_value)
-    ///     .expect("Expected to resolve")
-    ///     .as_str()
-    ///     .expect("Expected to be a string");
-    /// assert_eq!(key1_value, "value1");
-    ///
-    /// let key2: TomlKeyPath = "child.key".parse().unwrap();
-    /// let key2_value = key2.resolve(&toml_value)
-    ///     .expect("Expected to resolve")
-    ///     .as_str()
-    ///     .expect("Expected to be a string");
-    /// assert_eq!(key2_value, "value2");
-    ///
-    /// let hello: TomlKeyPath = "array.0".parse().unwrap();
-    /// let hello_value = hello.resolve(&toml_value)
-    ///     .expect("Expected to resolve")
-    ///     .as_str()
-    ///     .expect("Expected to be a string");
-    /// assert_eq!(hello_value, "hello");
-    /// ```
-    pub fn resolve<'a>(&self, value: &'a toml::Value) -> Option<&'a toml::Value> {
-        Self::resolve_impl(&mut self.clone(), value)
-    }
+/// A [`Source`] which runs a command (e.g. a secret manager's CLI such as `op inject` or
+/// `pass show`) and parses its standard output as TOML. The command's standard error is
+/// inherited, and it fails to load if the command exits unsuccessfully. Loads `None` if the
+/// command prints nothing.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use toml_env::{initialize, Args, CommandSource, SourcePrecedence};
+///
+/// let config: Option<toml::Value> = initialize(Args {
+///     sources: vec![Box::new(
+///         CommandSource::new("op")
+///             .args(["inject", "--in-file", "secrets.toml.tpl"])
+///             .precedence(SourcePrecedence::Highest),
+///     )],
+///     ..Args::default()
+/// })
+/// .unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct CommandSource {
+    program: OsString,
+    args: Vec<OsString>,
+    precedence: SourcePrecedence,
+}
 
-    fn resolve_impl<'a>(key: &mut Self, value: &'a toml::Value) -> Option<&'a toml::Value> {
-        if key.0.is_empty() {
-            return Some(value);
+impl CommandSource {
+    /// Create a source which runs `program` (looked up using the `PATH` if it is not a path)
+    /// with no arguments, at [`SourcePrecedence::AfterFile`].
+    pub fn new(program: impl Into<OsString>) -> Self {
+        Self {
+            program: program.into(),
+            args: Vec::new(),
+            precedence: SourcePrecedence::default(),
         }
+    }
 
-        let current_key = key.0.remove(0);
+    /// Add an argument to pass to the command.
+    pub fn arg(mut self, arg: impl Into<OsString>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
 
-        match value {
-            Value::Table(table) => match current_key {
-                PathElement::TableProperty(p) => {
-                    let value = table.get(&p)?;
-                    Self::resolve_impl(key, value)
-                }
-                PathElement::ArrayIndex(_) => None,
-            },
-            Value::Array(array) => match current_key {
-                PathElement::ArrayIndex(i) => {
-                    let value = array.get(i)?;
-                    Self::resolve_impl(key, value)
-                }
-                PathElement::TableProperty(_) => None,
-            },
-            _ => None,
-        }
+    /// Add arguments to pass to the command.
+    pub fn args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<OsString>,
+    {
+        self.args.extend(args.into_iter().map(Into::into));
+        self
     }
-}
 
-impl std::fmt::Display for TomlKeyPath {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str(
-            &self
-                .0
-                .iter()
-                .map(|e| e.to_string())
-                .collect::<Vec<_>>()
-                .join("."),
-        )
+    /// Set where the output of the command is merged relative to the other sources.
+    pub fn precedence(mut self, precedence: SourcePrecedence) -> Self {
+        self.precedence = precedence;
+        self
     }
 }
 
-impl FromStr for TomlKeyPath {
-    type Err = ();
-
-    /// Parse a string into a [`TomlKeyPath`].
-    ///
-    /// # Example
-    ///
-    /// ```rust
-    /// use toml_env::TomlKeyPath;
-    ///
-    /// "key".parse::<TomlKeyPath>().unwrap();
-    /// "key.0".parse::<TomlKeyPath>().unwrap();
-    /// "key.key".parse::<TomlKeyPath>().unwrap();
-    /// "key.0.key".parse::<TomlKeyPath>().unwrap();
-    /// "key.key.key".parse::<TomlKeyPath>().unwrap();
-    /// "".parse::<TomlKeyPath>().unwrap();
-    /// ".".parse::<TomlKeyPath>().unwrap();
-    /// ".key".parse::<TomlKeyPath>().unwrap();
-    /// "key.".parse::<TomlKeyPath>().unwrap();
-    /// ```
+impl Source for CommandSource {
+    fn load(&self) -> std::result::Result<Option<Value>, Box<dyn std::error::Error + Send + Sync>> {
+        let output = std::process::Command::new(&self.program)
+            .args(&self.args)
+            .stdin(std::process::Stdio::null())
+            .stderr(std::process::Stdio::inherit())
+            .output()?;
+        if !output.status.success() {
+            return Err(Box::new(Error::from(InnerError::CommandFailed {
+                status: output.status,
+            })));
+        }
+        let stdout = String::from_utf8(output.stdout)?;
+        if stdout.trim().is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(toml::from_str(&stdout)?))
+    }
 
-    fn from_str(s: &str) -> std::result::Result<Self, ()> {
-        if s.is_empty() {
-            return Ok(Self::default());
+    fn description(&self) -> String {
+        let mut description = format!("command `{}", self.program.to_string_lossy());
+        for arg in &self.args {
+            description.push(' ');
+            description.push_str(&arg.to_string_lossy());
         }
+        description.push('`');
+        description
+    }
 
-        let v: Vec<PathElement> = s
-            .split('.')
-            .filter_map(|k| {
-                if k.is_empty() {
-                    None
-                } else {
-                    if let Ok(i) = usize::from_str(k) {
-                        Some(PathElement::ArrayIndex(i))
-                    } else {
-                        Some(PathElement::TableProperty(k.to_owned()))
-                    }
-                }
-            })
-            .collect();
+    fn accesses(&self) -> Vec<Access> {
+        vec![Access::Command(self.program.clone())]
+    }
 
-        Ok(Self(v))
+    fn precedence(&self) -> SourcePrecedence {
+        self.precedence
     }
 }
 
-/// Automatically map environment variables into config.
-pub struct AutoMapEnvArgs<'a> {
-    /// The divider that separates different levels of the parent.child relationship for the
-    /// mapping. This will get replaced with `.` when converting the name of a variable to a [`TomlKeyPath`]. The default value is [`DEFAULT_DOTENV_PATH`].
-    pub divider: &'a str,
-    /// Prefix for environment variables to be mapped. By default this will be [`DEFAULT_CONFIG_VARIABLE_NAME`].
-    pub prefix: Option<&'a str>,
-    /// A transform operation to perform on the environment variable before parsing it. By default
-    /// this transforms it to lowercase.
-    pub transform: Box<dyn Fn(&str) -> String>,
+/// A [`Source`] which fetches secrets from AWS Secrets Manager, inserting each of them into the
+/// configuration at a key path. Secrets which are JSON objects (such as the credentials of a
+/// database managed by AWS) are converted into tables, otherwise they are parsed as TOML. The
+/// credentials and region are loaded from the environment using `aws-config`, unless the region
+/// is set using [`AwsSecretsManagerSource::region`].
+///
+/// The secrets are fetched using a single threaded `tokio` runtime, which is run on a dedicated
+/// thread when loaded from within an asynchronous context.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use toml_env::{initialize, Args, AwsSecretsManagerSource};
+///
+/// let config: Option<toml::Value> = initialize(Args {
+///     sources: vec![Box::new(
+///         AwsSecretsManagerSource::new()
+///             .secret("prod/my-app", "".parse().unwrap())
+///             .secret("prod/my-app/database", "database".parse().unwrap()),
+///     )],
+///     ..Args::default()
+/// })
+/// .unwrap();
+/// ```
+#[cfg(feature = "aws-secrets-manager")]
+#[derive(Debug, Clone, Default)]
+pub struct AwsSecretsManagerSource {
+    secrets: Vec<(String, TomlKeyPath)>,
+    region: Option<String>,
+    precedence: SourcePrecedence,
 }
 
-impl Default for AutoMapEnvArgs<'_> {
-    fn default() -> Self {
-        Self {
-            divider: DEFAULT_MAP_ENV_DIVIDER,
-            prefix: None,
-            transform: Box::new(|name| name.to_lowercase()),
-        }
+#[cfg(feature = "aws-secrets-manager")]
+impl AwsSecretsManagerSource {
+    /// Create a source with no secrets, at [`SourcePrecedence::AfterFile`].
+    pub fn new() -> Self {
+        Self::default()
     }
-}
 
-/// Args as input to [`initialize()`].
-pub struct Args<'a> {
-    /// Path to `.env.toml` format file. The value is [`DEFAULT_DOTENV_PATH`] by default.
-    pub dotenv_path: &'a Path,
-    /// Path to a config file to load.
-    pub config_path: Option<&'a Path>,
-    /// Name of the environment variable to use that stores the config. The value is [`DEFAULT_CONFIG_VARIABLE_NAME`] by default.
-    pub config_variable_name: &'a str,
-    /// What method of logging to use (if any). [`Logging::None`] by default.
-    pub logging: Logging,
-    /// Map the specified environment variables into config keys.
-    pub map_env: HashMap<&'a str, TomlKeyPath>,
-    /// See [`AutoMapEnvArgs`].
-    pub auto_map_env: Option<AutoMapEnvArgs<'a>>,
-}
+    /// Fetch the secret with this name or ARN, inserting it at `key` (an empty path to merge it
+    /// into the root of the configuration). Later secrets take precedence over earlier ones.
+    pub fn secret(mut self, secret_id: impl Into<String>, key: TomlKeyPath) -> Self {
+        self.secrets.push((secret_id.into(), key));
+        self
+    }
 
-impl Default for Args<'static> {
-    fn default() -> Self {
-        Self {
-            dotenv_path: Path::new(DEFAULT_DOTENV_PATH),
-            config_path: None,
-            config_variable_name: DEFAULT_CONFIG_VARIABLE_NAME,
-            logging: Logging::default(),
-            map_env: HashMap::default(),
-            auto_map_env: None,
-        }
+    /// Fetch the secrets from this region, instead of the region configured in the environment.
+    pub fn region(mut self, region: impl Into<String>) -> Self {
+        self.region = Some(region.into());
+        self
     }
-}
 
-fn log_info(logging: Logging, args: std::fmt::Arguments<'_>) {
-    match logging {
-        Logging::None => {}
-        Logging::StdOut => println!("INFO {}: {}", module_path!(), std::fmt::format(args)),
-        #[cfg(feature = "log")]
-        Logging::Log => log::info!("{}", std::fmt::format(args)),
+    /// Set where the secrets are merged relative to the other sources.
+    pub fn precedence(mut self, precedence: SourcePrecedence) -> Self {
+        self.precedence = precedence;
+        self
     }
 }
 
-/// Reads and parses the .env.toml file (or whatever is specified in `dotenv_path`). Returns
-/// `Some(C)` if the file contains a table with the name matching `config_variable_name`.
-fn initialize_dotenv_toml<'a, C: DeserializeOwned + Serialize>(
-    dotenv_path: &'a Path,
-    config_variable_name: &'a str,
-    logging: Logging,
-) -> InnerResult<Option<C>> {
-    let path = Path::new(dotenv_path);
-    if !path.exists() {
-        return Ok(None);
+#[cfg(feature = "aws-secrets-manager")]
+impl Source for AwsSecretsManagerSource {
+    fn load(&self) -> std::result::Result<Option<Value>, Box<dyn std::error::Error + Send + Sync>> {
+        if self.secrets.is_empty() {
+            return Ok(None);
+        }
+        run_outside_runtime(|| self.fetch())
     }
 
-    log_info(
-        logging,
-        format_args!("Loading config and environment variables from dotenv {path:?}"),
-    );
+    fn description(&self) -> String {
+        let secret_ids: Vec<&str> = self.secrets.iter().map(|(id, _)| id.as_str()).collect();
+        format!("AWS Secrets Manager secrets {}", secret_ids.join(", "))
+    }
 
-    let env_str = std::fs::read_to_string(path).map_err(|error| InnerError::ErrorReadingFile {
-        path: path.to_owned(),
-        error,
-    })?;
-    let env: Value =
-        toml::from_str(&env_str).map_err(|error| InnerError::ErrorParsingTomlFile {
-            path: path.to_owned(),
-            error: error.into(),
-        })?;
-    let table: toml::value::Table = match env {
-        Value::Table(table) => table,
-        unexpected => {
-            return Err(InnerError::UnexpectedTomlDotEnvFileFormat {
-                path: path.to_owned(),
-                value: unexpected,
-            });
-        }
-    };
+    fn accesses(&self) -> Vec<Access> {
+        aws_accesses("secretsmanager", self.region.as_deref())
+    }
 
-    if table.is_empty() {
-        return Ok(None);
+    fn precedence(&self) -> SourcePrecedence {
+        self.precedence
     }
 
-    let mut config: Option<C> = None;
-    let mut set_keys: String = String::new();
-    for (key, value) in table {
-        let value_string = match value {
-            Value::Table(_) => {
-                if key.as_str() != config_variable_name {
-                    return Err(InnerError::CannotParseTomlDotEnvFile {
-                        key,
-                        path: path.to_owned(),
-                        advice: format!("Only a table with {config_variable_name} is allowed in a .toml.env format file."),
-                    });
-                }
-                match C::deserialize(value.clone()) {
-                    Ok(c) => config = Some(c),
-                    Err(error) => {
-                        return Err(InnerError::ErrorParsingTomlDotEnvFileKey {
-                            name: key,
-                            path: path.to_owned(),
-                            error: error.into(),
+    fn kind(&self) -> SourceKind {
+        SourceKind::Secrets
+    }
+}
+
+#[cfg(feature = "aws-secrets-manager")]
+impl AwsSecretsManagerSource {
+    /// Fetch the secrets, see [`Source::load`].
+    fn fetch(
+        &self,
+    ) -> std::result::Result<Option<Value>, Box<dyn std::error::Error + Send + Sync>> {
+        let (runtime, config) = aws_runtime(self.region.as_deref())?;
+        let client = aws_sdk_secretsmanager::Client::new(&config);
+        runtime.block_on(async {
+            let mut value = Value::Table(toml::Table::new());
+            for (secret_id, key) in &self.secrets {
+                let output = client
+                    .get_secret_value()
+                    .secret_id(secret_id)
+                    .send()
+                    .await?;
+                let secret = match output.secret_string() {
+                    Some(secret) => parse_secret(secret)?,
+                    None => {
+                        return Err(Error::from(InnerError::SecretNotString {
+                            secret_id: secret_id.clone(),
                         })
+                        .into())
                     }
+                };
+                match (key.is_empty(), &mut value, secret) {
+                    (true, Value::Table(table), Value::Table(secret)) => table.extend(secret),
+                    (_, value, secret) => insert_toml_value(value, key.clone(), secret)?,
                 }
-                None
-            }
-            Value::String(value) => Some(value),
-            Value::Integer(value) => Some(value.to_string()),
-            Value::Float(value) => Some(value.to_string()),
-            Value::Boolean(value) => Some(value.to_string()),
-            Value::Datetime(value) => Some(value.to_string()),
-            Value::Array(value) => {
-                return Err(InnerError::CannotParseTomlDotEnvFile {
-                    key,
-                    path: path.to_owned(),
-                    advice: format!("Array values are not supported: {value:?}"),
-                })
             }
-        };
-
-        if let Some(value_string) = value_string {
-            set_keys.push('\n');
-            set_keys.push_str(key.as_str());
-            std::env::set_var(key.as_str(), value_string)
-        }
+            Ok(Some(value))
+        })
     }
-
-    log_info(
-        logging,
-        format_args!(
-            "Set environment variables specified in {dotenv_path:?}:\x1b[34m{set_keys}\x1b[0m"
-        ),
-    );
-    Ok(config)
 }
 
-#[derive(Debug, thiserror::Error)]
-enum InsertTomlValueError {
-    #[error("Table property {property:?} can only be used to index into a table. Cannot index into {value:?}")]
-    TablePropertyCannotIndex {
-        property: String,
-        value: toml::Value,
-    },
-    #[error(
-        "Array index {index} can only be used to index into an array. Cannot index into {value:?}"
-    )]
-    ArrayIndexCannotIndex { index: usize, value: toml::Value },
-    #[error("Array index {index} cannot be greater than the length of {array:?}")]
-    ArrayOutOfBounds {
-        index: usize,
-        array: Vec<toml::Value>,
-    },
+/// A [`Source`] which loads the parameters under a path from AWS Systems Manager Parameter
+/// Store, decrypting `SecureString` parameters, and converting the hierarchy of parameters
+/// (divided by `/`) into nested tables. The path is removed from the names of the parameters,
+/// and the values are inserted as strings (unless [`AwsSsmSource::infer_types`] is used). For
+/// example, with the path `/my-app/prod`, the parameter `/my-app/prod/database/port` with the
+/// value `5432` becomes `database.port = "5432"`.
+/// Loads `None` if there are no parameters under the path. The credentials and region are loaded
+/// from the environment using `aws-config`, unless the region is set using
+/// [`AwsSsmSource::region`].
+///
+/// The parameters are fetched using a single threaded `tokio` runtime, which is run on a dedicated
+/// thread when loaded from within an asynchronous context.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use toml_env::{initialize, Args, AwsSsmSource};
+///
+/// let config: Option<toml::Value> = initialize(Args {
+///     sources: vec![Box::new(AwsSsmSource::new("/my-app/prod"))],
+///     ..Args::default()
+/// })
+/// .unwrap();
+/// ```
+#[cfg(feature = "aws-ssm")]
+#[derive(Debug, Clone)]
+pub struct AwsSsmSource {
+    path: String,
+    region: Option<String>,
+    infer_types: bool,
+    precedence: SourcePrecedence,
 }
 
-/// Insert a `new_value` into a `value` at the location specified by `path`, creating any required
-/// tables or arrays if they are missing. If the `path` is empty, it will replace the value
-/// entirely.
-fn insert_toml_value(
-    value: &mut toml::Value,
-    mut path: TomlKeyPath,
-    new_value: Value,
-) -> std::result::Result<(), InsertTomlValueError> {
-    if path.0.is_empty() {
-        *value = new_value;
-        return Ok(());
+#[cfg(feature = "aws-ssm")]
+impl AwsSsmSource {
+    /// Create a source which loads the parameters under `path` (e.g. `/my-app/prod`), at
+    /// [`SourcePrecedence::AfterFile`].
+    pub fn new(path: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            region: None,
+            infer_types: false,
+            precedence: SourcePrecedence::default(),
+        }
     }
 
-    let current_key = path.0.remove(0);
-    let next_key = path.0.get(0);
+    /// Load the parameters from this region, instead of the region configured in the environment.
+    pub fn region(mut self, region: impl Into<String>) -> Self {
+        self.region = Some(region.into());
+        self
+    }
 
-    match (current_key, value) {
-        (PathElement::TableProperty(property), Value::Table(table)) => {
-            let next_value = table.get_mut(&property);
-            match (next_value, next_key) {
-                (None, None) => {
-                    table.insert(property, new_value);
-                    return Ok(());
-                }
-                (None, Some(PathElement::ArrayIndex(_))) => {
-                    table.insert(property.clone(), toml::Value::Array(Vec::with_capacity(1)));
-                    return insert_toml_value(
-                        table
-                            .get_mut(&property)
-                            .expect("Expect inserted property to be present"),
-                        path,
-                        new_value,
-                    );
-                }
-                (None, Some(PathElement::TableProperty(_))) => {
-                    table.insert(
-                        property.clone(),
-                        toml::Value::Table(toml::Table::with_capacity(1)),
-                    );
-                    return insert_toml_value(
-                        table
-                            .get_mut(&property)
-                            .expect("Expect inserted property to be present"),
-                        path,
-                        new_value,
-                    );
-                }
-                (Some(next_value), None) => {
-                    *next_value = new_value;
-                    return Ok(());
-                }
-                (Some(next_value), Some(_)) => {
-                    return insert_toml_value(next_value, path, new_value)
-                }
-            }
-        }
-        (PathElement::TableProperty(property), value) => {
-            return Err(InsertTomlValueError::TablePropertyCannotIndex {
-                property,
-                value: value.clone(),
-            })
-        }
-        (PathElement::ArrayIndex(index), Value::Array(array)) => {
-            if index > array.len() {
-                return Err(InsertTomlValueError::ArrayOutOfBounds {
-                    index,
-                    array: array.clone(),
-                });
-            }
-            let next_value = array.get_mut(index);
-            match (next_value, next_key) {
-                (None, None) => {
-                    array.insert(index, new_value);
-                    return Ok(());
-                }
-                (None, Some(PathElement::ArrayIndex(_))) => {
-                    array.insert(index, toml::Value::Array(Vec::with_capacity(1)));
-                    return insert_toml_value(
-                        array
-                            .get_mut(index)
-                            .expect("Expect inserted element to be present"),
-                        path,
-                        new_value,
-                    );
-                }
-                (None, Some(PathElement::TableProperty(_))) => {
-                    array.insert(index, toml::Value::Table(toml::Table::with_capacity(1)));
-                    return insert_toml_value(
-                        array
-                            .get_mut(index)
-                            .expect("Expect inserted element to be present"),
-                        path,
-                        new_value,
-                    );
-                }
-                (Some(next_value), None) => {
-                    *next_value = new_value;
-                    return Ok(());
-                }
-                (Some(next_value), Some(_)) => {
-                    return insert_toml_value(next_value, path, new_value)
-                }
-            }
-        }
-        (PathElement::ArrayIndex(index), value) => {
-            Err(InsertTomlValueError::ArrayIndexCannotIndex {
-                index,
-                value: value.clone(),
-            })
-        }
+    /// Infer the types of the values (e.g. `5432` as an integer) instead of inserting them as
+    /// strings, for paths which only contain non-secret `String` parameters.
+    pub fn infer_types(mut self, infer_types: bool) -> Self {
+        self.infer_types = infer_types;
+        self
+    }
+
+    /// Set where the parameters are merged relative to the other sources.
+    pub fn precedence(mut self, precedence: SourcePrecedence) -> Self {
+        self.precedence = precedence;
+        self
     }
 }
 
-/// Initialize from environment variables.
-fn initialize_env(
-    logging: Logging,
-    map_env: HashMap<&'_ str, TomlKeyPath>,
-    auto_args: Option<AutoMapEnvArgs<'_>>,
-    config_variable_name: &'_ str,
-) -> InnerResult<Option<Value>> {
-    fn parse_toml_value(value: String) -> Value {
-        if let Ok(value) = bool::from_str(&value) {
-            return Value::Boolean(value);
-        }
-        if let Ok(value) = f64::from_str(&value) {
-            return Value::Float(value);
-        }
-        if let Ok(value) = i64::from_str(&value) {
-            return Value::Integer(value);
-        }
-        if let Ok(value) = toml::value::Datetime::from_str(&value) {
-            return Value::Datetime(value);
-        }
+#[cfg(feature = "aws-ssm")]
+impl Source for AwsSsmSource {
+    fn load(&self) -> std::result::Result<Option<Value>, Box<dyn std::error::Error + Send + Sync>> {
+        let parameters = run_outside_runtime(|| self.fetch())?;
+        Ok(ssm_parameters_value(
+            &self.path,
+            parameters,
+            self.infer_types,
+        )?)
+    }
 
-        Value::String(value)
+    fn description(&self) -> String {
+        format!("AWS SSM parameters {:?}", self.path)
     }
 
-    // Using a BTreeMap to ensure values are sorted by environment variable, so that array indices
-    // are in the correct order of insertion to avoid an out of bounds.
-    let mut map_env: BTreeMap<Cow<'_, str>, TomlKeyPath> = map_env
-        .into_iter()
-        .map(|(key, value)| (Cow::Borrowed(key), value))
-        .collect();
+    fn accesses(&self) -> Vec<Access> {
+        aws_accesses("ssm", self.region.as_deref())
+    }
 
-    if let Some(auto_args) = auto_args {
-        let mut prefix = auto_args.prefix.unwrap_or(config_variable_name).to_owned();
-        prefix.push_str(auto_args.divider);
-        for (key, _) in std::env::vars_os() {
-            let key = if let Some(key) = key.to_str() {
-                key.to_owned()
-            } else {
-                continue;
-            };
+    fn precedence(&self) -> SourcePrecedence {
+        self.precedence
+    }
 
-            let key_without_prefix: &str = if let Some(0) = key.find(&prefix) {
-                key.split_at(prefix.len()).1
-            } else {
-                continue;
-            };
+    fn kind(&self) -> SourceKind {
+        SourceKind::Secrets
+    }
+}
 
-            let key_transformed = (auto_args.transform)(key_without_prefix);
-            let toml_key: TomlKeyPath =
-                if let Ok(key) = key_transformed.replace(auto_args.divider, ".").parse() {
-                    key
-                } else {
-                    continue;
+#[cfg(feature = "aws-ssm")]
+impl AwsSsmSource {
+    /// Fetch the names and values of the parameters under the path.
+    fn fetch(
+        &self,
+    ) -> std::result::Result<Vec<(String, String)>, Box<dyn std::error::Error + Send + Sync>> {
+        let (runtime, config) = aws_runtime(self.region.as_deref())?;
+        let client = aws_sdk_ssm::Client::new(&config);
+        runtime.block_on(async {
+            let mut entries = Vec::new();
+            let mut next_token = None;
+            loop {
+                let output = client
+                    .get_parameters_by_path()
+                    .path(&self.path)
+                    .recursive(true)
+                    .with_decryption(true)
+                    .set_next_token(next_token)
+                    .send()
+                    .await?;
+                for parameter in output.parameters() {
+                    if let (Some(name), Some(value)) = (parameter.name(), parameter.value()) {
+                        entries.push((name.to_owned(), value.to_owned()));
+                    }
+                }
+                next_token = match output.next_token() {
+                    Some(next_token) => Some(next_token.to_owned()),
+                    None => break,
                 };
-
-            map_env.entry(key.into()).or_insert(toml_key);
-        }
+            }
+            Ok(entries)
+        })
     }
+}
 
-    if map_env.is_empty() {
+/// Remove the `path` from the names of the `parameters` fetched from AWS SSM Parameter Store, and
+/// nest them into tables, inferring the types of the values if `infer_types`, see
+/// [`AwsSsmSource`]. Returns `None` if there are no parameters.
+#[cfg(feature = "aws-ssm")]
+fn ssm_parameters_value(
+    path: &str,
+    parameters: Vec<(String, String)>,
+    infer_types: bool,
+) -> std::result::Result<Option<Value>, InsertTomlValueError> {
+    if parameters.is_empty() {
         return Ok(None);
     }
+    let entries = parameters.into_iter().map(|(name, value)| {
+        let name = match name.strip_prefix(path) {
+            Some(name) => name.to_owned(),
+            None => name,
+        };
+        (name, value)
+    });
+    Ok(Some(nest_key_values(entries, "/", infer_types)?))
+}
 
-    if !matches!(logging, Logging::None) {
-        let mut buffer = String::new();
-        buffer.push_str("\x1b[34m");
-        for (k, v) in &map_env {
-            if std::env::var(k.as_ref()).is_ok() {
-                buffer.push_str(&format!("\n{k} => {v}"));
-            }
+/// The resources accessed by the AWS SDK to make requests to the `service`: the regional endpoint
+/// of the service (if the region is known), the shared config and credentials files, and the
+/// instance metadata endpoint. Other credential providers (e.g. SSO or the ECS container
+/// endpoint) may also be accessed if they are configured.
+#[cfg(any(feature = "aws-secrets-manager", feature = "aws-ssm"))]
+fn aws_accesses(service: &str, region: Option<&str>) -> Vec<Access> {
+    let mut accesses = Vec::new();
+    let region = region
+        .map(str::to_owned)
+        .or_else(|| std::env::var("AWS_REGION").ok())
+        .or_else(|| std::env::var("AWS_DEFAULT_REGION").ok());
+    if let Some(region) = region {
+        accesses.push(Access::Endpoint(format!(
+            "https://{service}.{region}.amazonaws.com"
+        )));
+    }
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    for (variable, name) in [
+        ("AWS_CONFIG_FILE", "config"),
+        ("AWS_SHARED_CREDENTIALS_FILE", "credentials"),
+    ] {
+        match (std::env::var_os(variable), &home) {
+            (Some(path), _) => accesses.push(Access::Path(PathBuf::from(path))),
+            (None, Some(home)) => accesses.push(Access::Path(home.join(".aws").join(name))),
+            (None, None) => {}
         }
-        buffer.push_str("\x1b[0m");
-        log_info(
-            logging,
-            format_args!("Loading config from current environment variables: {buffer}"),
+    }
+    accesses.push(Access::Endpoint("http://169.254.169.254".to_owned()));
+    accesses
+}
+
+/// Run `fetch` (which starts a runtime using [`aws_runtime()`]) on a dedicated thread if this is
+/// called from within a `tokio` runtime (e.g. from `#[tokio::main]`), where another runtime
+/// cannot be started.
+#[cfg(any(feature = "aws-secrets-manager", feature = "aws-ssm"))]
+fn run_outside_runtime<T: Send>(fetch: impl FnOnce() -> T + Send) -> T {
+    if tokio::runtime::Handle::try_current().is_err() {
+        return fetch();
+    }
+    std::thread::scope(|scope| match scope.spawn(fetch).join() {
+        Ok(result) => result,
+        Err(panic) => std::panic::resume_unwind(panic),
+    })
+}
+
+/// Create a single threaded `tokio` runtime to make requests using the AWS SDK, and load the
+/// SDK's configuration from the environment, using the `region` if it is set.
+#[cfg(any(feature = "aws-secrets-manager", feature = "aws-ssm"))]
+fn aws_runtime(
+    region: Option<&str>,
+) -> std::io::Result<(tokio::runtime::Runtime, aws_config::SdkConfig)> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    let mut loader = aws_config::defaults(aws_config::BehaviorVersion::latest());
+    if let Some(region) = region {
+        loader = loader.region(aws_config::Region::new(region.to_owned()));
+    }
+    let config = runtime.block_on(loader.load());
+    Ok((runtime, config))
+}
+
+/// The path of the service account token which is mounted into Kubernetes pods, used to log in
+/// with [`VaultAuth::Kubernetes`].
+#[cfg(feature = "vault")]
+const KUBERNETES_SERVICE_ACCOUNT_TOKEN_PATH: &str =
+    "/var/run/secrets/kubernetes.io/serviceaccount/token";
+
+/// How a [`VaultSource`] authenticates with Vault.
+#[cfg(feature = "vault")]
+#[derive(Clone)]
+pub enum VaultAuth {
+    /// Use this token (e.g. the value of the `VAULT_TOKEN` environment variable).
+    Token(String),
+    /// Log in using the AppRole auth method mounted at `approle`.
+    AppRole {
+        /// The ID of the role.
+        role_id: String,
+        /// The secret ID issued for the role.
+        secret_id: String,
+    },
+    /// Log in using the Kubernetes auth method mounted at `kubernetes`, with the service account
+    /// token of the pod.
+    Kubernetes {
+        /// The name of the role.
+        role: String,
+    },
+}
+
+#[cfg(feature = "vault")]
+impl std::fmt::Debug for VaultAuth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VaultAuth::Token(_) => f.debug_tuple("Token").field(&REDACTED).finish(),
+            VaultAuth::AppRole { role_id, .. } => f
+                .debug_struct("AppRole")
+                .field("role_id", role_id)
+                .field("secret_id", &REDACTED)
+                .finish(),
+            VaultAuth::Kubernetes { role } => {
+                f.debug_struct("Kubernetes").field("role", role).finish()
+            }
+        }
+    }
+}
+
+/// A [`Source`] which reads secrets from HashiCorp Vault's KV version 2 secrets engine, inserting
+/// the data of each of them into the configuration at a key path. Requests are made using the
+/// [`RemoteArgs`] set with [`VaultSource::remote`] (e.g. to trust the certificate authority of
+/// the Vault server).
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use toml_env::{initialize, Args, SourcePrecedence, VaultAuth, VaultSource};
+///
+/// let token = std::env::var("VAULT_TOKEN").unwrap();
+/// let config: Option<toml::Value> = initialize(Args {
+///     sources: vec![Box::new(
+///         VaultSource::new("https://vault.internal:8200", VaultAuth::Token(token))
+///             .secret("secret", "my-app/database", "database".parse().unwrap())
+///             .precedence(SourcePrecedence::Highest),
+///     )],
+///     ..Args::default()
+/// })
+/// .unwrap();
+/// ```
+#[cfg(feature = "vault")]
+#[derive(Debug, Clone)]
+pub struct VaultSource {
+    address: String,
+    auth: VaultAuth,
+    secrets: Vec<(String, String, TomlKeyPath)>,
+    remote: RemoteArgs,
+    precedence: SourcePrecedence,
+}
+
+#[cfg(feature = "vault")]
+impl VaultSource {
+    /// Create a source with no secrets for the Vault server at `address` (e.g.
+    /// `https://vault.internal:8200`), at [`SourcePrecedence::AfterFile`].
+    pub fn new(address: impl Into<String>, auth: VaultAuth) -> Self {
+        Self {
+            address: address.into(),
+            auth,
+            secrets: Vec::new(),
+            remote: RemoteArgs::default(),
+            precedence: SourcePrecedence::default(),
+        }
+    }
+
+    /// Read the secret at `path` in the KV version 2 secrets engine mounted at `mount` (e.g.
+    /// `secret`), inserting its data at `key` (an empty path to merge it into the root of the
+    /// configuration). Later secrets take precedence over earlier ones.
+    pub fn secret(
+        mut self,
+        mount: impl Into<String>,
+        path: impl Into<String>,
+        key: TomlKeyPath,
+    ) -> Self {
+        self.secrets.push((mount.into(), path.into(), key));
+        self
+    }
+
+    /// Set the timeout, headers (e.g. `X-Vault-Namespace`) and agent used for requests.
+    pub fn remote(mut self, remote: RemoteArgs) -> Self {
+        self.remote = remote;
+        self
+    }
+
+    /// Set where the secrets are merged relative to the other sources.
+    pub fn precedence(mut self, precedence: SourcePrecedence) -> Self {
+        self.precedence = precedence;
+        self
+    }
+
+    /// Log in using the auth method if necessary, returning the token.
+    fn token(&self) -> std::result::Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        let (method, body) = match &self.auth {
+            VaultAuth::Token(token) => return Ok(token.clone()),
+            VaultAuth::AppRole { role_id, secret_id } => (
+                "approle",
+                serde_json::json!({ "role_id": role_id, "secret_id": secret_id }),
+            ),
+            VaultAuth::Kubernetes { role } => {
+                let jwt = std::fs::read_to_string(KUBERNETES_SERVICE_ACCOUNT_TOKEN_PATH)?;
+                (
+                    "kubernetes",
+                    serde_json::json!({ "role": role, "jwt": jwt.trim() }),
+                )
+            }
+        };
+        let url = format!(
+            "{}/v1/auth/{method}/login",
+            self.address.trim_end_matches('/')
         );
+        let response: serde_json::Value = serde_json::from_str(
+            &self
+                .remote
+                .request("POST", &url)
+                .send_string(&serde_json::to_string(&body)?)?
+                .into_string()?,
+        )?;
+        match response
+            .get("auth")
+            .and_then(|auth| auth.get("client_token"))
+            .and_then(serde_json::Value::as_str)
+        {
+            Some(token) => Ok(token.to_owned()),
+            None => Err(Error::from(InnerError::UnexpectedVaultResponse { url }).into()),
+        }
     }
+}
 
-    log_info(logging, format_args!("Loading config from environment"));
+#[cfg(feature = "vault")]
+impl Source for VaultSource {
+    fn load(&self) -> std::result::Result<Option<Value>, Box<dyn std::error::Error + Send + Sync>> {
+        if self.secrets.is_empty() {
+            return Ok(None);
+        }
+        let token = self.token()?;
+        let address = self.address.trim_end_matches('/');
 
-    let mut config = toml::Value::Table(toml::Table::new());
-    for (variable_name, toml_key) in map_env {
-        let value = match std::env::var(variable_name.as_ref()) {
-            Ok(value) => value,
-            Err(std::env::VarError::NotPresent) => continue,
-            Err(error) => {
-                return Err(InnerError::ErrorReadingEnvironmentVariable {
-                    name: (*variable_name.into_owned()).to_owned(),
-                    error,
-                })
+        let mut value = Value::Table(toml::Table::new());
+        for (mount, path, key) in &self.secrets {
+            let url = format!("{address}/v1/{mount}/data/{path}");
+            let response: serde_json::Value = serde_json::from_str(
+                &self
+                    .remote
+                    .request("GET", &url)
+                    .set("X-Vault-Token", &token)
+                    .call()?
+                    .into_string()?,
+            )?;
+            let data = match response.get("data").and_then(|data| data.get("data")) {
+                Some(data) => Value::try_from(data.clone())?,
+                None => return Err(Error::from(InnerError::UnexpectedVaultResponse { url }).into()),
+            };
+            match (key.is_empty(), &mut value, data) {
+                (true, Value::Table(table), Value::Table(data)) => table.extend(data),
+                (_, value, data) => insert_toml_value(value, key.clone(), data)?,
+            }
+        }
+        Ok(Some(value))
+    }
+
+    fn description(&self) -> String {
+        let paths: Vec<String> = self
+            .secrets
+            .iter()
+            .map(|(mount, path, _)| format!("{mount}/{path}"))
+            .collect();
+        format!("Vault secrets {} from {}", paths.join(", "), self.address)
+    }
+
+    fn accesses(&self) -> Vec<Access> {
+        let mut accesses = vec![Access::Endpoint(self.address.clone())];
+        if let VaultAuth::Kubernetes { .. } = self.auth {
+            accesses.push(Access::Path(PathBuf::from(
+                KUBERNETES_SERVICE_ACCOUNT_TOKEN_PATH,
+            )));
+        }
+        accesses
+    }
+
+    fn precedence(&self) -> SourcePrecedence {
+        self.precedence
+    }
+
+    fn kind(&self) -> SourceKind {
+        SourceKind::Secrets
+    }
+}
+
+/// The environment variable which systemd sets to the directory containing the credentials of
+/// a service, see [`SystemdCredentialsSource`].
+pub const CREDENTIALS_DIRECTORY_VARIABLE: &str = "CREDENTIALS_DIRECTORY";
+
+/// A [`Source`] which reads credentials passed to a systemd service (using `LoadCredential=`,
+/// `SetCredential=` or `ImportCredential=`) from the directory in the
+/// [`CREDENTIALS_DIRECTORY_VARIABLE`], inserting each of them into the configuration at a key
+/// path. Loads `None` if the variable is not set (e.g. when not running under systemd).
+///
+/// Credentials are mapped using [`SystemdCredentialsSource::credential`], and with
+/// [`SystemdCredentialsSource::auto_map`] the other credentials are mapped by splitting their
+/// names on a divider, similar to [`AutoMapEnvArgs`]. The values are inserted as strings (so that
+/// a numeric password can be deserialized into a `String`), with a trailing newline removed.
+///
+/// # Example
+///
+/// With `LoadCredential=database.password:/etc/my-app/db-password` in the unit file:
+///
+/// ```rust,no_run
+/// use toml_env::{initialize, Args, SourcePrecedence, SystemdCredentialsSource};
+///
+/// let config: Option<toml::Value> = initialize(Args {
+///     sources: vec![Box::new(
+///         SystemdCredentialsSource::new()
+///             .credential("api-token", "api.token".parse().unwrap())
+///             .auto_map(".")
+///             .precedence(SourcePrecedence::Highest),
+///     )],
+///     ..Args::default()
+/// })
+/// .unwrap();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct SystemdCredentialsSource {
+    directory: Option<PathBuf>,
+    credentials: Vec<(String, TomlKeyPath)>,
+    auto_map_divider: Option<String>,
+    precedence: SourcePrecedence,
+}
+
+impl SystemdCredentialsSource {
+    /// Create a source with no mapped credentials, at [`SourcePrecedence::AfterFile`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read the credentials from this directory instead of the directory in the
+    /// [`CREDENTIALS_DIRECTORY_VARIABLE`].
+    pub fn directory(mut self, directory: impl Into<PathBuf>) -> Self {
+        self.directory = Some(directory.into());
+        self
+    }
+
+    /// Insert the credential with this name at `key`. Ignored if the credential doesn't exist.
+    pub fn credential(mut self, name: impl Into<String>, key: TomlKeyPath) -> Self {
+        self.credentials.push((name.into(), key));
+        self
+    }
+
+    /// Map the credentials which are not mapped using [`SystemdCredentialsSource::credential`],
+    /// by splitting their names on the `divider` (e.g. `database__password` with the divider
+    /// `__` is inserted at `database.password`).
+    pub fn auto_map(mut self, divider: impl Into<String>) -> Self {
+        self.auto_map_divider = Some(divider.into());
+        self
+    }
+
+    /// Set where the credentials are merged relative to the other sources.
+    pub fn precedence(mut self, precedence: SourcePrecedence) -> Self {
+        self.precedence = precedence;
+        self
+    }
+
+    /// The directory containing the credentials, `None` if not running under systemd.
+    fn credentials_directory(&self) -> Option<PathBuf> {
+        self.directory
+            .clone()
+            .or_else(|| std::env::var_os(CREDENTIALS_DIRECTORY_VARIABLE).map(PathBuf::from))
+    }
+}
+
+impl Source for SystemdCredentialsSource {
+    fn load(&self) -> std::result::Result<Option<Value>, Box<dyn std::error::Error + Send + Sync>> {
+        let directory = match self.credentials_directory() {
+            Some(directory) => directory,
+            None => return Ok(None),
+        };
+        let read_credential = |name: &str| read_value_file(&directory.join(name));
+
+        let mut value = match &self.auto_map_divider {
+            Some(divider) => {
+                let mut names = Vec::new();
+                for entry in std::fs::read_dir(&directory)? {
+                    let name = entry?.file_name().to_string_lossy().into_owned();
+                    if !self.credentials.iter().any(|(mapped, _)| *mapped == name) {
+                        names.push(name);
+                    }
+                }
+                names.sort();
+                let mut entries = Vec::new();
+                for name in names {
+                    if let Some(content) = read_credential(&name)? {
+                        entries.push((name, content));
+                    }
+                }
+                nest_key_values(entries, divider, false)?
             }
+            None => Value::Table(toml::Table::new()),
         };
-        let value = parse_toml_value(value);
-        insert_toml_value(&mut config, toml_key.clone(), value)?;
+        for (name, key) in &self.credentials {
+            if let Some(content) = read_credential(name)? {
+                insert_toml_value(&mut value, key.clone(), Value::String(content))?;
+            }
+        }
+        Ok(Some(value))
+    }
+
+    fn description(&self) -> String {
+        match &self.directory {
+            Some(directory) => format!("systemd credentials in {directory:?}"),
+            None => format!("systemd credentials in ${CREDENTIALS_DIRECTORY_VARIABLE}"),
+        }
+    }
+
+    fn accesses(&self) -> Vec<Access> {
+        self.credentials_directory()
+            .map(Access::Path)
+            .into_iter()
+            .collect()
+    }
+
+    fn precedence(&self) -> SourcePrecedence {
+        self.precedence
     }
 
-    Ok(Some(config.into()))
+    fn kind(&self) -> SourceKind {
+        SourceKind::Secrets
+    }
 }
 
-/// Initialize configuration from available sources specified in [`Args`].
+/// A [`Source`] which reads a directory where each file contains the value of a key, such as a
+/// Kubernetes ConfigMap or Secret mounted as a volume. The names of the files are the keys, which
+/// are either top level keys, or split on a divider (set using [`KeyPerFileSource::divider`])
+/// into nested tables (e.g. `database__password` with the divider `__` is inserted at
+/// `database.password`). The values are inserted as strings (unless
+/// [`KeyPerFileSource::infer_types`] is used), with a trailing newline removed.
+/// Files whose names start with `..` (which Kubernetes uses to update the volume atomically) are
+/// ignored. Loads `None` if the directory doesn't exist.
 ///
-/// If no configuration was found, will return `None`.
+/// # Example
 ///
-/// See [`toml-env`](crate).
-pub fn initialize<C>(args: Args<'_>) -> Result<Option<C>>
-where
-    C: DeserializeOwned + Serialize,
-{
-    let config_variable_name = args.config_variable_name;
-    let logging = args.logging;
-    let dotenv_path = args.dotenv_path;
+/// ```rust,no_run
+/// use toml_env::{initialize, Args, KeyPerFileSource, SourceKind};
+///
+/// let config: Option<toml::Value> = initialize(Args {
+///     sources: vec![
+///         Box::new(KeyPerFileSource::new("/etc/my-app/config").divider("__")),
+///         Box::new(
+///             KeyPerFileSource::new("/etc/my-app/secrets")
+///                 .divider("__")
+///                 .kind(SourceKind::Secrets),
+///         ),
+///     ],
+///     ..Args::default()
+/// })
+/// .unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct KeyPerFileSource {
+    directory: PathBuf,
+    divider: Option<String>,
+    infer_types: bool,
+    precedence: SourcePrecedence,
+    kind: SourceKind,
+}
 
-    let config_env_config: Option<(Value, ConfigSource)> = match std::env::var(config_variable_name) {
-        Ok(variable_value) => match toml::from_str(&variable_value) {
-            Ok(config) => {
-                log_info(
-                    logging,
-                    format_args!(
-                        "Options loaded from `{config_variable_name}` environment variable"
-                    ),
-                );
-                Ok(Some(config))
+impl KeyPerFileSource {
+    /// Create a source which reads the files in `directory` as top level keys, at
+    /// [`SourcePrecedence::AfterFile`].
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+            divider: None,
+            infer_types: false,
+            precedence: SourcePrecedence::default(),
+            kind: SourceKind::Custom,
+        }
+    }
+
+    /// Split the names of the files on the `divider` into nested tables.
+    pub fn divider(mut self, divider: impl Into<String>) -> Self {
+        self.divider = Some(divider.into());
+        self
+    }
+
+    /// Infer the types of the values (e.g. `5432` as an integer) instead of inserting them as
+    /// strings, for ConfigMaps which contain non-secret values.
+    pub fn infer_types(mut self, infer_types: bool) -> Self {
+        self.infer_types = infer_types;
+        self
+    }
+
+    /// Set where the keys are merged relative to the other sources.
+    pub fn precedence(mut self, precedence: SourcePrecedence) -> Self {
+        self.precedence = precedence;
+        self
+    }
+
+    /// Set the kind of the source (see [`Source::kind`]), e.g. [`SourceKind::Secrets`] for a
+    /// mounted Kubernetes Secret, so that it satisfies [`Args::key_policies`] which only allow
+    /// secrets. [`SourceKind::Custom`] by default, because the directory may be a ConfigMap.
+    pub fn kind(mut self, kind: SourceKind) -> Self {
+        self.kind = kind;
+        self
+    }
+}
+
+impl Source for KeyPerFileSource {
+    fn load(&self) -> std::result::Result<Option<Value>, Box<dyn std::error::Error + Send + Sync>> {
+        let entries = match std::fs::read_dir(&self.directory) {
+            Ok(entries) => entries,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(error.into()),
+        };
+        let mut paths = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            let visible = path
+                .file_name()
+                .is_some_and(|name| !name.to_string_lossy().starts_with(".."));
+            // Follows the symbolic links which Kubernetes creates for each key.
+            if visible && path.is_file() {
+                paths.push(path);
             }
-            Err(error) => {
-                let path = Path::new(&variable_value);
-                if path.is_file() {
-                    log_info(
-                        args.logging,
-                        format_args!("Loading environment variables from {path:?}"),
-                    );
+        }
+        paths.sort();
 
-                    let config_str =
-                        std::fs::read_to_string(path).map_err(|error| InnerError::ErrorReadingFile {
-                            path: path.to_owned(),
-                            error,
-                        })?;
-                    let config: Value = toml::from_str(&config_str).map_err(|error| {
-                        InnerError::ErrorParsingTomlFile {
-                            path: path.to_owned(),
-                            error: error.into(),
-                        }
-                    })?;
-                    log_info(logging, format_args!("Options loaded from file specified in `{config_variable_name}` environment variable: {path:?}"));
-                    Ok(Some(config))
-                } else {
-                    Err(InnerError::ErrorParsingEnvironmentVariableAsConfigOrFile {
-                        name: config_variable_name.to_owned(),
-                        value: variable_value,
-                        error: error.into(),
-                    })
-                }
+        let mut entries = Vec::new();
+        for path in paths {
+            if let (Some(name), Some(content)) = (path.file_name(), read_value_file(&path)?) {
+                entries.push((name.to_string_lossy().into_owned(), content));
             }
-        },
-        Err(std::env::VarError::NotPresent) => {
-            log_info(
-                logging,
-                format_args!(
-                    "No environment variable with the name {config_variable_name} found, using default options."
-                ),
-            );
-            Ok(None)
         }
-        Err(error) => Err(InnerError::ErrorReadingEnvironmentVariable {
-            name: config_variable_name.to_owned(),
-            error,
-        }),
-    }?.map(|config| {
-        let source = ConfigSource::DotEnv(args.dotenv_path.to_owned());
-        (config, source)
-    });
+        let value = match &self.divider {
+            Some(divider) => nest_key_values(entries, divider, self.infer_types)?,
+            None => Value::Table(
+                entries
+                    .into_iter()
+                    .map(|(key, content)| {
+                        let content = if self.infer_types {
+                            parse_toml_value(content, NumberInference::PreferInteger, false)
+                        } else {
+                            Value::String(content)
+                        };
+                        (key, content)
+                    })
+                    .collect(),
+            ),
+        };
+        Ok(Some(value))
+    }
 
-    let dotenv_config =
-        initialize_dotenv_toml(dotenv_path, config_variable_name, logging)?.map(|config| {
-            (
-                config,
-                ConfigSource::Environment {
-                    variable_names: vec![args.config_variable_name.to_owned()],
-                },
-            )
-        });
+    fn description(&self) -> String {
+        format!("files in {:?}", self.directory)
+    }
 
-    let config: Option<(Value, ConfigSource)> = match (dotenv_config, config_env_config) {
-        (None, None) => None,
-        (None, Some(config)) => Some(config),
-        (Some(config), None) => Some(config),
-        (Some(from), Some(into)) => {
-            let config = serde_toml_merge::merge(into.0, from.0).map_err(|error| {
-                InnerError::ErrorMerging {
-                    from: from.1.clone(),
-                    into: into.1.clone(),
-                    error,
-                }
-            })?;
+    fn accesses(&self) -> Vec<Access> {
+        vec![Access::Path(self.directory.clone())]
+    }
 
-            let source = ConfigSource::Merged {
-                from: from.1.into(),
-                into: into.1.into(),
+    fn precedence(&self) -> SourcePrecedence {
+        self.precedence
+    }
+
+    fn kind(&self) -> SourceKind {
+        self.kind
+    }
+}
+
+/// A [`Source`] which reads secrets (e.g. API tokens) from the OS credential store (the macOS
+/// Keychain, the Windows Credential Manager, or the Secret Service on Linux) using the `keyring`
+/// crate, inserting each of them into the configuration at a key path as a string. Secrets which
+/// don't exist in the credential store are ignored. The credential store is accessed using the
+/// platform's IPC (e.g. D-Bus), which is not listed by [`Source::accesses`].
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use toml_env::{initialize, Args, KeyringSource};
+///
+/// let config: Option<toml::Value> = initialize(Args {
+///     sources: vec![Box::new(
+///         KeyringSource::new().secret("my-app", "api", "api.token".parse().unwrap()),
+///     )],
+///     ..Args::default()
+/// })
+/// .unwrap();
+/// ```
+#[cfg(feature = "keyring")]
+#[derive(Debug, Clone, Default)]
+pub struct KeyringSource {
+    secrets: Vec<(String, String, TomlKeyPath)>,
+    precedence: SourcePrecedence,
+}
+
+#[cfg(feature = "keyring")]
+impl KeyringSource {
+    /// Create a source with no secrets, at [`SourcePrecedence::AfterFile`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read the password of the credential with this `service` and `account`, inserting it at
+    /// `key`.
+    pub fn secret(
+        mut self,
+        service: impl Into<String>,
+        account: impl Into<String>,
+        key: TomlKeyPath,
+    ) -> Self {
+        self.secrets.push((service.into(), account.into(), key));
+        self
+    }
+
+    /// Set where the secrets are merged relative to the other sources.
+    pub fn precedence(mut self, precedence: SourcePrecedence) -> Self {
+        self.precedence = precedence;
+        self
+    }
+}
+
+#[cfg(feature = "keyring")]
+impl Source for KeyringSource {
+    fn load(&self) -> std::result::Result<Option<Value>, Box<dyn std::error::Error + Send + Sync>> {
+        let mut value = None;
+        for (service, account, key) in &self.secrets {
+            let password = match keyring::Entry::new(service, account)?.get_password() {
+                Ok(password) => password,
+                Err(keyring::Error::NoEntry) => continue,
+                Err(error) => return Err(error.into()),
             };
+            let value = value.get_or_insert_with(|| Value::Table(toml::Table::new()));
+            insert_toml_value(value, key.clone(), Value::String(password))?;
+        }
+        Ok(value)
+    }
+
+    fn description(&self) -> String {
+        let credentials: Vec<String> = self
+            .secrets
+            .iter()
+            .map(|(service, account, _)| format!("{service}/{account}"))
+            .collect();
+        format!("keyring credentials {}", credentials.join(", "))
+    }
+
+    fn precedence(&self) -> SourcePrecedence {
+        self.precedence
+    }
+
+    fn kind(&self) -> SourceKind {
+        SourceKind::Secrets
+    }
+}
 
-            Some((config, source))
+/// Read a file containing a single value (see [`SystemdCredentialsSource`] and
+/// [`KeyPerFileSource`]) with a trailing newline removed, `None` if it doesn't exist.
+fn read_value_file(path: &Path) -> std::io::Result<Option<String>> {
+    match std::fs::read_to_string(path) {
+        Ok(mut content) => {
+            if content.ends_with('\n') {
+                content.pop();
+                if content.ends_with('\r') {
+                    content.pop();
+                }
+            }
+            Ok(Some(content))
         }
-    };
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(error),
+    }
+}
 
-    let env_config = initialize_env(
-        args.logging,
-        args.map_env.clone(),
-        args.auto_map_env,
-        config_variable_name,
-    )?
-    .map(|value| {
-        (
-            value,
-            ConfigSource::Environment {
-                variable_names: args.map_env.keys().map(|key| (*key).to_owned()).collect(),
-            },
-        )
-    });
+/// A key-value store which a [`KvTreeSource`] reads from.
+#[cfg(feature = "kv-tree")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KvBackend {
+    /// Consul's KV store, using the `/v1/kv` HTTP API. An ACL token can be sent using the
+    /// `X-Consul-Token` header in [`RemoteArgs::headers`].
+    Consul,
+    /// etcd version 3, using the `/v3/kv/range` endpoint of its JSON gateway. An auth token can
+    /// be sent using the `Authorization` header in [`RemoteArgs::headers`].
+    Etcd,
+}
+
+/// A [`Source`] which reads the keys under a prefix from Consul or etcd, converting the hierarchy
+/// of keys (divided by `/`) into nested tables. The prefix is removed from the keys, and the
+/// values are inserted as strings (unless [`KvTreeSource::infer_types`] is used). For example,
+/// with the prefix `config/my-app/`, the key `config/my-app/database/port` with the value `5432`
+/// becomes `database.port = "5432"`. The prefix is a directory, so `config/my-app` also reads the
+/// keys under `config/my-app/` (and not `config/my-app-staging/`). Loads `None` if there are no
+/// keys under the prefix.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use toml_env::{initialize, Args, KvBackend, KvTreeSource, RemoteArgs};
+///
+/// let token = std::env::var("CONSUL_HTTP_TOKEN").unwrap();
+/// let config: Option<toml::Value> = initialize(Args {
+///     sources: vec![Box::new(
+///         KvTreeSource::new(KvBackend::Consul, "http://consul.internal:8500", "config/my-app/")
+///             .remote(RemoteArgs {
+///                 headers: vec![("X-Consul-Token".to_owned(), token)],
+///                 ..RemoteArgs::default()
+///             }),
+///     )],
+///     ..Args::default()
+/// })
+/// .unwrap();
+/// ```
+#[cfg(feature = "kv-tree")]
+#[derive(Debug, Clone)]
+pub struct KvTreeSource {
+    backend: KvBackend,
+    address: String,
+    prefix: String,
+    remote: RemoteArgs,
+    infer_types: bool,
+    precedence: SourcePrecedence,
+}
+
+#[cfg(feature = "kv-tree")]
+impl KvTreeSource {
+    /// Create a source which reads the keys under `prefix` from the `backend` at `address` (e.g.
+    /// `http://consul.internal:8500`), at [`SourcePrecedence::AfterFile`]. A `/` is appended to
+    /// the `prefix` if it doesn't end with one, unless it is empty.
+    pub fn new(backend: KvBackend, address: impl Into<String>, prefix: impl Into<String>) -> Self {
+        let mut prefix = prefix.into();
+        if !prefix.is_empty() && !prefix.ends_with('/') {
+            prefix.push('/');
+        }
+        Self {
+            backend,
+            address: address.into(),
+            prefix,
+            remote: RemoteArgs::default(),
+            infer_types: false,
+            precedence: SourcePrecedence::default(),
+        }
+    }
+
+    /// Infer the types of the values (e.g. `5432` as an integer) instead of inserting them as
+    /// strings.
+    pub fn infer_types(mut self, infer_types: bool) -> Self {
+        self.infer_types = infer_types;
+        self
+    }
+
+    /// Set the timeout, headers (e.g. auth tokens) and agent used for requests.
+    pub fn remote(mut self, remote: RemoteArgs) -> Self {
+        self.remote = remote;
+        self
+    }
+
+    /// Set where the keys are merged relative to the other sources.
+    pub fn precedence(mut self, precedence: SourcePrecedence) -> Self {
+        self.precedence = precedence;
+        self
+    }
+
+    /// Read the keys and values under the prefix from Consul.
+    fn consul_entries(
+        &self,
+        address: &str,
+    ) -> std::result::Result<Vec<(String, String)>, Box<dyn std::error::Error + Send + Sync>> {
+        let url = format!(
+            "{address}/v1/kv/{}?recurse=true",
+            percent_encode_path(&self.prefix)
+        );
+        let response = match self.remote.request("GET", &url).call() {
+            Ok(response) => response,
+            Err(ureq::Error::Status(404, _)) => return Ok(Vec::new()),
+            Err(error) => return Err(error.into()),
+        };
+        let response: serde_json::Value = serde_json::from_str(&response.into_string()?)?;
+        let entries = match response.as_array() {
+            Some(entries) => entries,
+            None => return Err(Error::from(InnerError::UnexpectedKvResponse { url }).into()),
+        };
+        entries
+            .iter()
+            // Folders have a `null` value.
+            .filter(|entry| {
+                entry
+                    .get("Value")
+                    .and_then(serde_json::Value::as_str)
+                    .is_some()
+            })
+            .map(|entry| {
+                let key = entry.get("Key").and_then(serde_json::Value::as_str);
+                match (key, decode_base64_field(entry, "Value")) {
+                    (Some(key), Some(value)) => Ok((key.to_owned(), value)),
+                    _ => Err(
+                        Error::from(InnerError::UnexpectedKvResponse { url: url.clone() }).into(),
+                    ),
+                }
+            })
+            .collect()
+    }
+
+    /// Read the keys and values under the prefix from etcd.
+    fn etcd_entries(
+        &self,
+        address: &str,
+    ) -> std::result::Result<Vec<(String, String)>, Box<dyn std::error::Error + Send + Sync>> {
+        let url = format!("{address}/v3/kv/range");
+        // The range of keys which start with the prefix ends at the prefix with its last byte
+        // incremented, or at `\0` (meaning all keys) for an empty prefix.
+        let mut range_end = self.prefix.as_bytes().to_vec();
+        while let Some(last) = range_end.pop() {
+            if last < u8::MAX {
+                range_end.push(last + 1);
+                break;
+            }
+        }
+        if range_end.is_empty() {
+            range_end.push(0);
+        }
+        let body = serde_json::json!({
+            "key": encode_base64(self.prefix.as_bytes()),
+            "range_end": encode_base64(&range_end),
+        });
+        let response: serde_json::Value = serde_json::from_str(
+            &self
+                .remote
+                .request("POST", &url)
+                .send_string(&serde_json::to_string(&body)?)?
+                .into_string()?,
+        )?;
+        // `kvs` is omitted when there are no keys.
+        match response.get("kvs").map(serde_json::Value::as_array) {
+            Some(Some(entries)) => {
+                entries
+                    .iter()
+                    .map(|entry| {
+                        // Empty values are omitted.
+                        let value = match entry.get("value") {
+                            Some(_) => decode_base64_field(entry, "value"),
+                            None => Some(String::new()),
+                        };
+                        match (decode_base64_field(entry, "key"), value) {
+                            (Some(key), Some(value)) => Ok((key, value)),
+                            _ => Err(Error::from(InnerError::UnexpectedKvResponse {
+                                url: url.clone(),
+                            })
+                            .into()),
+                        }
+                    })
+                    .collect()
+            }
+            Some(None) => Err(Error::from(InnerError::UnexpectedKvResponse { url }).into()),
+            None => Ok(Vec::new()),
+        }
+    }
+}
+
+#[cfg(feature = "kv-tree")]
+impl Source for KvTreeSource {
+    fn load(&self) -> std::result::Result<Option<Value>, Box<dyn std::error::Error + Send + Sync>> {
+        let address = self.address.trim_end_matches('/');
+        let entries = match self.backend {
+            KvBackend::Consul => self.consul_entries(address)?,
+            KvBackend::Etcd => self.etcd_entries(address)?,
+        };
+        // Keys which aren't under the prefix are ignored, although the store shouldn't return them.
+        let entries: Vec<(String, String)> = entries
+            .into_iter()
+            .filter_map(|(key, value)| Some((key.strip_prefix(&self.prefix)?.to_owned(), value)))
+            .collect();
+        if entries.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(nest_key_values(entries, "/", self.infer_types)?))
+    }
+
+    fn description(&self) -> String {
+        let backend = match self.backend {
+            KvBackend::Consul => "Consul",
+            KvBackend::Etcd => "etcd",
+        };
+        format!("{backend} keys {:?} from {}", self.prefix, self.address)
+    }
+
+    fn accesses(&self) -> Vec<Access> {
+        vec![Access::Endpoint(self.address.clone())]
+    }
+
+    fn precedence(&self) -> SourcePrecedence {
+        self.precedence
+    }
+}
+
+/// Decode the base64 encoded string `field` of an `entry` in the response from a key-value
+/// store.
+#[cfg(feature = "kv-tree")]
+fn decode_base64_field(entry: &serde_json::Value, field: &str) -> Option<String> {
+    let decoded = decode_base64(entry.get(field)?.as_str()?)?;
+    String::from_utf8(decoded).ok()
+}
+
+/// Nest the `entries` into tables by splitting their keys on the `divider`, with the types of the
+/// values inferred if `infer_types` is `true`, otherwise inserted as strings. Empty segments (e.g.
+/// from a trailing divider) are ignored.
+fn nest_key_values(
+    entries: impl IntoIterator<Item = (String, String)>,
+    divider: &str,
+    infer_types: bool,
+) -> std::result::Result<Value, InsertTomlValueError> {
+    let mut value = Value::Table(toml::Table::new());
+    for (key, entry) in entries {
+        let mut path = TomlKeyPath::default();
+        for segment in key.split(divider).filter(|segment| !segment.is_empty()) {
+            path.push(segment);
+        }
+        if path.is_empty() {
+            continue;
+        }
+        let entry = if infer_types {
+            parse_toml_value(entry, NumberInference::PreferInteger, false)
+        } else {
+            Value::String(entry)
+        };
+        insert_toml_value(&mut value, path, entry)?;
+    }
+    Ok(value)
+}
+
+/// Parse a secret fetched from a secret manager as JSON if it is a JSON object, otherwise as
+/// TOML.
+#[cfg(feature = "aws-secrets-manager")]
+fn parse_secret(
+    secret: &str,
+) -> std::result::Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+    if secret.trim_start().starts_with('{') {
+        let json: serde_json::Value = serde_json::from_str(secret)?;
+        return Ok(Value::try_from(json)?);
+    }
+    Ok(toml::from_str(secret)?)
+}
+
+/// A policy restricting which kinds of source may set a key (or any of its children), see
+/// [`Args::key_policies`].
+#[derive(Debug, Clone)]
+pub enum KeyPolicy {
+    /// The key may only be set by the specified kinds of source.
+    Allow {
+        /// The key that the policy applies to.
+        key: TomlKeyPath,
+        /// The kinds of source which are allowed to set the key.
+        sources: Vec<SourceKind>,
+    },
+    /// The key may not be set by the specified kinds of source.
+    Deny {
+        /// The key that the policy applies to.
+        key: TomlKeyPath,
+        /// The kinds of source which are not allowed to set the key.
+        sources: Vec<SourceKind>,
+    },
+}
+
+impl KeyPolicy {
+    /// Whether a source of the specified `kind` is allowed to set the key.
+    fn allows(&self, kind: SourceKind) -> bool {
+        match self {
+            KeyPolicy::Allow { sources, .. } => sources.contains(&kind),
+            KeyPolicy::Deny { sources, .. } => !sources.contains(&kind),
+        }
+    }
+
+    fn key(&self) -> &TomlKeyPath {
+        match self {
+            KeyPolicy::Allow { key, .. } | KeyPolicy::Deny { key, .. } => key,
+        }
+    }
+}
+
+/// An error from a source which was skipped, see [`Args::optional_sources`] and
+/// [`Args::missing_key_path`].
+#[derive(Debug, Clone, Error)]
+#[error("Skipped {kind}: {error}")]
+pub struct SourceWarning {
+    /// The kind of source which was skipped.
+    pub kind: SourceKind,
+    /// The error which caused the source to be skipped.
+    #[source]
+    pub error: Arc<Error>,
+}
+
+/// An error that occurs while initializing configuration.
+#[derive(Debug, Error)]
+#[error(transparent)]
+pub struct Error(#[from] InnerError);
+
+/// An error that occurs while initializing configuration.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+enum InnerError {
+    /// Error reading environment variable.
+    #[error("Error reading {name} environment variable")]
+    ErrorReadingEnvironmentVariable {
+        /// Name of the environment variable.
+        name: String,
+        /// Source of the error.
+        #[source]
+        error: std::env::VarError,
+    },
+    /// Error reading TOML file.
+    #[error("Error reading TOML file {path:?}")]
+    ErrorReadingFile {
+        /// Path to the file.
+        path: PathBuf,
+        /// Source of the error.
+        #[source]
+        error: std::io::Error,
+    },
+    /// Error parsing TOML file.
+    #[error("Error parsing TOML file {path:?}")]
+    ErrorParsingTomlFile {
+        /// Path to the file.
+        path: PathBuf,
+        /// Source of the error.
+        #[source]
+        error: Box<toml::de::Error>,
+    },
+    /// The [`Args::config_variable_key_path`] was not found in the config stored in the
+    /// environment variable.
+    #[error("No config found at `{key}` in `{name}` environment variable")]
+    ConfigKeyPathNotFound {
+        /// Name of the environment variable.
+        name: String,
+        /// The key path which was not found.
+        key: Box<TomlKeyPath>,
+    },
+    /// The key path passed to [`TomlValueExt::get_as`] or [`MultiConfig::get`] is invalid.
+    #[error(transparent)]
+    InvalidKeyPath {
+        /// Source of the error.
+        error: TomlKeyPathParseError,
+    },
+    /// No value was found at the key path passed to [`TomlValueExt::get_as`].
+    #[error("No value found at `{key}`")]
+    KeyPathNotFound {
+        /// The key path which was not found.
+        key: Box<TomlKeyPath>,
+    },
+    /// Error deserializing the value at the key path passed to [`TomlValueExt::get_as`] or
+    /// [`MultiConfig::get`].
+    #[error("Error deserializing the value at `{key}`")]
+    ErrorDeserializingValue {
+        /// The key path of the value.
+        key: Box<TomlKeyPath>,
+        /// Source of the error.
+        #[source]
+        error: Box<toml::de::Error>,
+    },
+    /// The scheme of a [`ConfigLocation`] is not supported.
+    #[error(
+        "Unsupported config location {location:?}, expected a path, file://, env:// or stdin://"
+    )]
+    UnsupportedConfigLocation {
+        /// The location which could not be parsed.
+        location: String,
+    },
+    /// Error reading the configuration from standard input.
+    #[error("Error reading config from stdin")]
+    ErrorReadingStdin {
+        /// Source of the error.
+        #[source]
+        error: std::io::Error,
+    },
+    /// Error loading a custom [`Source`].
+    #[error("Error loading config from custom source {description}")]
+    ErrorLoadingSource {
+        /// The description of the source.
+        description: String,
+        /// Source of the error.
+        #[source]
+        error: Box<dyn std::error::Error + Send + Sync>,
+    },
+    /// A [`ConfigLocation::Url`] was used without the `remote` feature.
+    #[cfg(not(feature = "remote"))]
+    #[error("Cannot fetch config from {url:?}, the `remote` feature is not enabled")]
+    RemoteFeatureDisabled {
+        /// The URL of the configuration.
+        url: String,
+    },
+    /// Error fetching the configuration from a [`ConfigLocation::Url`].
+    #[cfg(feature = "remote")]
+    #[error("Error fetching config from {url:?}")]
+    ErrorFetchingRemoteConfig {
+        /// The URL of the configuration.
+        url: String,
+        /// Source of the error.
+        #[source]
+        error: Box<dyn std::error::Error + Send + Sync>,
+    },
+    /// The command run by a [`CommandSource`] exited unsuccessfully.
+    #[error("Command exited unsuccessfully ({status})")]
+    CommandFailed {
+        /// The exit status of the command.
+        status: std::process::ExitStatus,
+    },
+    /// A secret fetched by an [`AwsSecretsManagerSource`] is binary rather than a string.
+    #[cfg(feature = "aws-secrets-manager")]
+    #[error("AWS Secrets Manager secret {secret_id:?} is not a string")]
+    SecretNotString {
+        /// The name or ARN of the secret.
+        secret_id: String,
+    },
+    /// The response from Vault to a request made by a [`VaultSource`] did not contain the
+    /// expected data.
+    #[cfg(feature = "vault")]
+    #[error("Unexpected response from Vault for {url:?}")]
+    UnexpectedVaultResponse {
+        /// The URL of the request.
+        url: String,
+    },
+    /// The response from a key-value store to a request made by a [`KvTreeSource`] did not
+    /// contain the expected data.
+    #[cfg(feature = "kv-tree")]
+    #[error("Unexpected response from key-value store for {url:?}")]
+    UnexpectedKvResponse {
+        /// The URL of the request.
+        url: String,
+    },
+    /// Error reading the configuration from the reader passed to [`initialize_reader()`].
+    #[error("Error reading config from {label:?}")]
+    ErrorReadingConfig {
+        /// Label of the reader.
+        label: String,
+        /// Source of the error.
+        #[source]
+        error: std::io::Error,
+    },
+    /// Error serializing the default configuration to use as a schema.
+    #[error("Error serializing the default configuration")]
+    ErrorSerializingDefaults {
+        /// Source of the error.
+        #[source]
+        error: Box<toml::ser::Error>,
+    },
+    /// Error parsing a JSON5 config file.
+    #[cfg(feature = "json5")]
+    #[error("Error parsing JSON5 file {path:?}")]
+    ErrorParsingJson5File {
+        /// Path to the file.
+        path: PathBuf,
+        /// Source of the error.
+        #[source]
+        error: Box<json5::Error>,
+    },
+    /// Error parsing an INI config file.
+    #[cfg(feature = "ini")]
+    #[error("Error parsing INI file {path:?}")]
+    ErrorParsingIniFile {
+        /// Path to the file.
+        path: PathBuf,
+        /// Source of the error.
+        #[source]
+        error: Box<ini::ParseError>,
+    },
+    /// A top level property in an INI config file has the same name as a section.
+    #[cfg(feature = "ini")]
+    #[error("Property {name:?} in INI file {path:?} has the same name as a section")]
+    IniPropertyConflictsWithSection {
+        /// Path to the file.
+        path: PathBuf,
+        /// Name of the property and section.
+        name: String,
+    },
+    /// Error parsing the inline configuration.
+    #[error("Error parsing inline configuration {label:?}")]
+    ErrorParsingInlineConfig {
+        /// Label of the inline configuration.
+        label: String,
+        /// Source of the error.
+        #[source]
+        error: Box<toml::de::Error>,
+    },
+    /// Error parsing the embedded defaults.
+    #[error("Error parsing embedded defaults")]
+    ErrorParsingDefaults {
+        /// Source of the error.
+        #[source]
+        error: Box<toml::de::Error>,
+    },
+    /// Cannot parse a table in the `.toml.env` file.
+    #[cfg(feature = "dotenv")]
+    #[error("Cannot parse {key} as environment variable in {path:?}. Advice: {advice}")]
+    CannotParseTomlDotEnvFile {
+        /// Key in the TOML file.
+        key: String,
+        /// Path to the file.
+        path: PathBuf,
+        /// Advice
+        advice: String,
+    },
+    /// A non-finite float in the `.toml.env` file, which are not allowed by
+    /// [`Args::dotenv_non_finite_floats`].
+    #[cfg(feature = "dotenv")]
+    #[error(
+        "Cannot set environment variable {key} to non-finite float {value} from {path:?}. Advice: \
+        use a string instead, or set dotenv_non_finite_floats to NonFiniteFloats::Allow"
+    )]
+    NonFiniteDotEnvFloat {
+        /// Key in the TOML file.
+        key: String,
+        /// Path to the file.
+        path: PathBuf,
+        /// The non-finite value.
+        value: f64,
+    },
+    /// A variable in the `.toml.env` file is already set in the environment, which is not allowed
+    /// by [`Args::dotenv_overlap`].
+    #[cfg(feature = "dotenv")]
+    #[error(
+        "Cannot set environment variable {key} from {path:?} because it is already set (e.g. by \
+        another dotenv loader). Advice: remove it from one of the sources"
+    )]
+    DotEnvVariableAlreadySet {
+        /// Key in the TOML file.
+        key: String,
+        /// Path to the file.
+        path: PathBuf,
+    },
+    /// A line in the `.env` file (see [`Args::env_file_path`]) could not be parsed.
+    #[cfg(feature = "dotenv")]
+    #[error("Error parsing line {line} of .env file {path:?}, expected KEY=value")]
+    ErrorParsingEnvFile {
+        /// Path to the file.
+        path: PathBuf,
+        /// The line number (starting at 1).
+        line: usize,
+    },
+    /// Error parsing envirnment variable
+    #[cfg(feature = "dotenv")]
+    #[error("Error parsing config key ({name}) in TOML config file {path:?}")]
+    ErrorParsingTomlDotEnvFileKey {
+        /// Name of the key variable.
+        name: String,
+        /// Path to the file.
+        path: PathBuf,
+        /// Source of the error.
+        #[source]
+        error: Box<toml::de::Error>,
+    },
+    /// Either there was an error parsing the environment variable as the config, or if the value
+    /// is a filename, it does not exist.
+    #[error(
+        "Error parsing config environment variable ({name}={value:?}) as the config or if it is a filename, the file does not exist."
+    )]
+    ErrorParsingEnvironmentVariableAsConfigOrFile {
+        /// Name of the environment variable.
+        name: String,
+        /// Value of the environment variable.
+        value: String,
+        /// Source of the error.
+        #[source]
+        error: Box<toml::de::Error>,
+    },
+    /// Error parsing file as `.env.toml` format.
+    #[cfg(feature = "dotenv")]
+    #[error(
+        "Error parsing the {path:?} as `.env.toml` format file:\n{value:#?}\nTop level should be a table."
+    )]
+    UnexpectedTomlDotEnvFileFormat {
+        /// Path to file.
+        path: PathBuf,
+        /// Value that was unable to be parsed as `.env.toml` format
+        value: Value,
+    },
+    /// Error parsing merged configuration.
+    #[error("Error parsing merged configuration from {source}")]
+    ErrorParsingMergedToml {
+        /// Source(s) of the configuration.
+        source: ConfigSource,
+        /// Source of the error.
+        #[source]
+        error: Box<toml::de::Error>,
+    },
+    /// Error merging configurations.
+    #[error("Error merging configuration {from} into {into}: {error}")]
+    ErrorMerging {
+        /// Error merging from this source.
+        from: Box<ConfigSource>,
+        /// Error merging into this source.
+        into: Box<ConfigSource>,
+        /// Source of the error.
+        error: MergeError,
+    },
+    /// Error parsing the value of an environment variable as the type specified in the schema or
+    /// by a [`ValueHint`].
+    #[error("Error parsing environment variable {name}={value:?} (mapped to {key}) as {expected}")]
+    ErrorCoercingEnvironmentVariable {
+        /// Name of the environment variable.
+        name: String,
+        /// Value of the environment variable.
+        value: String,
+        /// Key that the environment variable is mapped to.
+        key: Box<TomlKeyPath>,
+        /// Expected type of the value.
+        expected: &'static str,
+    },
+    /// An element of an array of tables from an environment variable does not have the shape
+    /// specified in the schema, see [`Args::schema`].
+    #[error(
+        "Error parsing environment variable {name} (mapped to {key}): expected {expected} at \
+        `{path}`"
+    )]
+    UnexpectedEnvironmentVariableShape {
+        /// Name of the environment variable.
+        name: String,
+        /// Key that the environment variable is mapped to.
+        key: Box<TomlKeyPath>,
+        /// Path to the value which has the wrong type.
+        path: Box<TomlKeyPath>,
+        /// Expected type of the value.
+        expected: &'static str,
+    },
+    /// [`Args::base_dir`] is a relative path.
+    #[error("Args::base_dir {path:?} must be an absolute path")]
+    RelativeBaseDir {
+        /// The base directory.
+        path: PathBuf,
+    },
+    /// [`AutoMapEnvArgs::list_delimiter`] was set without an [`Args::schema`], which is needed to
+    /// know which variables are mapped to arrays.
+    #[cfg(feature = "auto-map-env")]
+    #[error("AutoMapEnvArgs::list_delimiter requires Args::schema to be set")]
+    ListDelimiterWithoutSchema,
+    #[error(transparent)]
+    MergeConflict(Box<MergeConflict>),
+    /// Invalid value for a key used to configure the logger in [`init_logger()`].
+    #[cfg(feature = "log-init")]
+    #[error("Invalid value {value} for `{key}` (expected {expected})")]
+    InvalidLoggerConfig {
+        /// The key with the invalid value.
+        key: &'static str,
+        /// The invalid value.
+        value: Value,
+        /// Description of the expected value.
+        expected: &'static str,
+    },
+    /// Error initializing the logger in [`init_logger()`].
+    #[cfg(feature = "log-init")]
+    #[error("Error initializing logger")]
+    ErrorInitializingLogger {
+        /// Source of the error.
+        #[source]
+        error: log::SetLoggerError,
+    },
+    /// A key was set by a kind of source which is not allowed to set it by a [`KeyPolicy`].
+    #[error("Key `{key}` is not allowed to be set by {kind} (set by {config_source})")]
+    KeyPolicyViolation {
+        /// The key which violated the policy.
+        key: Box<TomlKeyPath>,
+        /// The kind of source which set the key.
+        kind: SourceKind,
+        /// The source which set the key.
+        config_source: ConfigSource,
+    },
+    #[error("Error inserting toml value")]
+    InsertTomlValueError(#[from] InsertTomlValueError),
+}
+
+impl Error {
+    /// If this error was caused by two sources of configuration containing incompatible types
+    /// for the same key, returns the details of the conflict.
+    pub fn merge_conflict(&self) -> Option<&MergeConflict> {
+        match &self.0 {
+            InnerError::MergeConflict(conflict) => Some(conflict.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+/// Two sources of configuration contain values with incompatible types for the same key (e.g. a
+/// `float` in one and an `integer` in the other), so they cannot be merged.
+#[derive(Debug, Clone, Error)]
+#[error(
+    "Cannot merge key `{key}`: {from_type} value from {from} is incompatible with {into_type} value from {into}"
+)]
+pub struct MergeConflict {
+    /// Path to the conflicting key.
+    pub key: TomlKeyPath,
+    /// Type of the value (see [`toml::Value::type_str()`]) in the source with higher precedence.
+    pub from_type: &'static str,
+    /// The source with higher precedence, which was being merged into `into`.
+    pub from: ConfigSource,
+    /// Type of the value (see [`toml::Value::type_str()`]) in the source with lower precedence.
+    pub into_type: &'static str,
+    /// The source with lower precedence which contains the conflicting key.
+    pub into: ConfigSource,
+}
+
+/// What method of logging for this library to use.
+#[derive(Debug, Default, Clone, Copy)]
+pub enum Logging {
+    /// Don't perform any logging
+    #[default]
+    None,
+    /// Use STDOUT for logging. This may be attractive if you are relying on the output of this
+    /// library to configure your logging system and still want to see what's going on here before
+    /// the system is configured.
+    StdOut,
+    /// Use the [`log`] crate for logging.
+    #[cfg(feature = "log")]
+    Log,
+}
+
+/// How much of the parsed configuration is logged, see [`Args::config_log`].
+#[cfg(feature = "pretty-logging")]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigLog {
+    /// Log the entire configuration.
+    #[default]
+    Full,
+    /// Log at most `max_keys` values (arrays count as a single value) and at most `max_bytes` of
+    /// rendered TOML, followed by a `... N more keys omitted` line if anything was left out.
+    Truncated {
+        /// Maximum number of values to log, or `None` for no limit.
+        max_keys: Option<usize>,
+        /// Maximum length of the rendered TOML in bytes, or `None` for no limit.
+        max_bytes: Option<usize>,
+    },
+    /// Only log the top level keys, with the number of values within each of them.
+    Summary,
+}
+
+type InnerResult<T> = std::result::Result<T, InnerError>;
+
+/// How line endings are handled in values set as environment variables from the `.env.toml` file.
+#[cfg(feature = "dotenv")]
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEndings {
+    /// Convert `\r\n` line endings to `\n`, so that values in a file edited on Windows don't
+    /// contain a trailing `\r` on each line.
+    #[default]
+    Lf,
+    /// Keep line endings as they are in the file.
+    Preserve,
+}
+
+#[cfg(feature = "dotenv")]
+impl LineEndings {
+    fn apply(self, value: String) -> String {
+        match self {
+            LineEndings::Lf if value.contains('\r') => value.replace("\r\n", "\n"),
+            LineEndings::Lf | LineEndings::Preserve => value,
+        }
+    }
+}
+
+/// How variables in the `.env.toml` file which are already set in the environment (e.g. by
+/// `dotenvy` or another loader) are handled, see [`Args::dotenv_overlap`].
+#[cfg(feature = "dotenv")]
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DotEnvOverlap {
+    /// Set the environment variable to the value from the `.env.toml` file.
+    #[default]
+    Overwrite,
+    /// Keep the value which is already set.
+    Keep,
+    /// Return an error.
+    Error,
+}
+
+/// How non-finite floats (`inf`, `-inf` and `nan`) in the `.env.toml` file are handled when they
+/// are set as environment variables.
+#[cfg(feature = "dotenv")]
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NonFiniteFloats {
+    /// Set the environment variable to `inf`, `-inf` or `nan`, which are parsed back as floats.
+    #[default]
+    Allow,
+    /// Return an error, for applications which don't expect non-finite values.
+    Error,
+}
+
+/// Format a float so that it parses back as the same float (the shortest representation which
+/// round-trips, with `-0.0` preserved), and as a float rather than an integer (e.g. `1.0` rather
+/// than `1`). Non-finite floats are formatted using their TOML representation (`inf`, `-inf` and
+/// `nan`).
+fn format_float(value: f64) -> String {
+    if value.is_nan() {
+        "nan".to_owned()
+    } else if value.is_infinite() {
+        if value.is_sign_positive() {
+            "inf"
+        } else {
+            "-inf"
+        }
+        .to_owned()
+    } else {
+        format!("{value:?}")
+    }
+}
+
+/// A path to a key into a [`toml::Value`]. In the format of `key.0.key` (`0` for indexing into an
+/// array) when parsed using [`FromStr`]. Keys containing dots can be quoted as in TOML (e.g.
+/// `servers."my.host.com".port`), and quoting a number makes it a table key rather than an array
+/// index (e.g. `ports."8080".name` for a table keyed by port numbers).
+///
+/// Paths are compared and ordered element by element, with table properties ordered before array
+/// indices, so they can be used as keys in maps and sets.
+///
+/// See [`TomlKeyPath::resolve()`] for an example.
+#[derive(Debug, Clone, Default, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TomlKeyPath(PathElements);
+
+/// The elements of a [`TomlKeyPath`], stored inline for paths of up to four elements so that
+/// typical key paths don't allocate for their elements.
+type PathElements = SmallVec<[PathElement; 4]>;
+
+/// An element of a [`TomlKeyPath`].
+#[derive(Debug, Clone, Hash, PartialEq, PartialOrd, Ord, Eq)]
+#[non_exhaustive]
+pub enum PathElement {
+    /// A key indexing into a table.
+    TableProperty(Box<str>),
+    /// An index into an array.
+    ArrayIndex(usize),
+    /// A `*` element, which matches every key in a table and every element in an array (see
+    /// [`TomlKeyPath::resolve_all()`]). Parsed from an unquoted `*`, whereas a quoted `"*"` is a
+    /// [`PathElement::TableProperty`].
+    Wildcard,
+    /// A `+` element, which appends to an array when inserting a value (see
+    /// [`insert_toml_value()`]). Parsed from an unquoted `+`, whereas a quoted `"+"` is a
+    /// [`PathElement::TableProperty`].
+    Append,
+}
+
+/// Always a [`PathElement::TableProperty`], even if the key looks like an array index.
+impl From<&str> for PathElement {
+    fn from(property: &str) -> Self {
+        PathElement::TableProperty(property.into())
+    }
+}
+
+/// Always a [`PathElement::TableProperty`], even if the key looks like an array index.
+impl From<String> for PathElement {
+    fn from(property: String) -> Self {
+        PathElement::TableProperty(property.into_boxed_str())
+    }
+}
+
+impl From<usize> for PathElement {
+    fn from(index: usize) -> Self {
+        PathElement::ArrayIndex(index)
+    }
+}
+
+impl std::fmt::Display for PathElement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PathElement::TableProperty(p) => p.fmt(f),
+            PathElement::ArrayIndex(i) => i.fmt(f),
+            PathElement::Wildcard => f.write_str("*"),
+            PathElement::Append => f.write_str("+"),
+        }
+    }
+}
+
+impl TomlKeyPath {
+    /// Resolve a value contained within a [`toml::Value`] using this [`TomlKeyPath`].
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use toml_env::TomlKeyPath;
+    /// use toml;
+    ///
+    /// let toml_value = toml::from_str(r#"
+    /// key="value1"
+    /// array=["hello", "world"]
+    /// [child]
+    /// key="value2"
+    /// "#).unwrap();
+    ///
+    /// let key1: TomlKeyPath = "key".parse().unwrap();
+    /// let key1_value = key1.resolve(&toml_value)
+    ///     .expect("Expected to resolve")
+    ///     .as_str()
+    ///     .expect("Expected to be a string");
+    /// assert_eq!(key1_value, "value1");
+    ///
+    /// let key2: TomlKeyPath = "child.key".parse().unwrap();
+    /// let key2_value = key2.resolve(&toml_value)
+    ///     .expect("Expected to resolve")
+    ///     .as_str()
+    ///     .expect("Expected to be a string");
+    /// assert_eq!(key2_value, "value2");
+    ///
+    /// let hello: TomlKeyPath = "array.0".parse().unwrap();
+    /// let hello_value = hello.resolve(&toml_value)
+    ///     .expect("Expected to resolve")
+    ///     .as_str()
+    ///     .expect("Expected to be a string");
+    /// assert_eq!(hello_value, "hello");
+    /// ```
+    pub fn resolve<'a>(&self, value: &'a toml::Value) -> Option<&'a toml::Value> {
+        self.0
+            .iter()
+            .try_fold(value, |value, element| match (element, value) {
+                (PathElement::TableProperty(p), Value::Table(table)) => table.get(&**p),
+                (PathElement::ArrayIndex(i), Value::Array(array)) => array.get(*i),
+                _ => None,
+            })
+    }
+
+    /// Resolve a mutable reference to a value contained within a [`toml::Value`] using this
+    /// [`TomlKeyPath`], which can be used to edit the value in place. See
+    /// [`insert_toml_value()`] for inserting values at keys which may not exist yet.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use toml_env::TomlKeyPath;
+    ///
+    /// let mut toml_value: toml::Value = toml::from_str(r#"
+    /// [paths]
+    /// data = "./data/"
+    /// "#).unwrap();
+    ///
+    /// let key: TomlKeyPath = "paths.data".parse().unwrap();
+    /// if let Some(toml::Value::String(data)) = key.resolve_mut(&mut toml_value) {
+    ///     *data = data.trim_end_matches('/').to_owned();
+    /// }
+    /// assert_eq!(toml_value["paths"]["data"].as_str(), Some("./data"));
+    ///
+    /// let missing: TomlKeyPath = "paths.cache".parse().unwrap();
+    /// assert!(missing.resolve_mut(&mut toml_value).is_none());
+    /// ```
+    pub fn resolve_mut<'a>(&self, value: &'a mut toml::Value) -> Option<&'a mut toml::Value> {
+        resolve_elements_mut(&self.0, value)
+    }
+
+    /// Resolve all of the values contained within a [`toml::Value`] which match this
+    /// [`TomlKeyPath`], along with their concrete paths. A `*` element matches every key in a
+    /// table and every element in an array (the same as in [`Args::redact`]). Without any `*`
+    /// elements this matches at most one value, like [`TomlKeyPath::resolve()`].
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use toml_env::TomlKeyPath;
+    ///
+    /// let toml_value: toml::Value = toml::from_str(r#"
+    /// [servers.alpha]
+    /// port = 8080
+    /// [servers.beta]
+    /// port = 8081
+    /// [servers.gamma]
+    /// host = "gamma"
+    /// "#).unwrap();
+    ///
+    /// let ports: TomlKeyPath = "servers.*.port".parse().unwrap();
+    /// let resolved: Vec<(String, i64)> = ports
+    ///     .resolve_all(&toml_value)
+    ///     .map(|(path, value)| (path.to_string(), value.as_integer().unwrap()))
+    ///     .collect();
+    /// assert_eq!(
+    ///     resolved,
+    ///     [
+    ///         ("servers.alpha.port".to_owned(), 8080),
+    ///         ("servers.beta.port".to_owned(), 8081),
+    ///     ]
+    /// );
+    /// ```
+    pub fn resolve_all<'a>(
+        &self,
+        value: &'a toml::Value,
+    ) -> impl Iterator<Item = (TomlKeyPath, &'a toml::Value)> {
+        fn resolve_all_impl<'a>(
+            path: &mut PathElements,
+            pattern: &[PathElement],
+            value: &'a Value,
+            resolved: &mut Vec<(TomlKeyPath, &'a Value)>,
+        ) {
+            let (element, rest) = match pattern.split_first() {
+                Some(split) => split,
+                None => {
+                    resolved.push((TomlKeyPath(path.clone()), value));
+                    return;
+                }
+            };
+            let mut descend = |element: PathElement, value: &'a Value| {
+                path.push(element);
+                resolve_all_impl(path, rest, value, resolved);
+                path.pop();
+            };
+            match (element, value) {
+                (PathElement::Wildcard, Value::Table(table)) => {
+                    for (key, value) in table {
+                        descend(PathElement::TableProperty(key.as_str().into()), value);
+                    }
+                }
+                (PathElement::Wildcard, Value::Array(array)) => {
+                    for (index, value) in array.iter().enumerate() {
+                        descend(PathElement::ArrayIndex(index), value);
+                    }
+                }
+                (PathElement::TableProperty(key), Value::Table(table)) => {
+                    if let Some(value) = table.get(&**key) {
+                        descend(element.clone(), value);
+                    }
+                }
+                (PathElement::ArrayIndex(index), Value::Array(array)) => {
+                    if let Some(value) = array.get(*index) {
+                        descend(element.clone(), value);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let mut resolved = Vec::new();
+        resolve_all_impl(&mut PathElements::new(), &self.0, value, &mut resolved);
+        resolved.into_iter()
+    }
+
+    /// Remove the value at this [`TomlKeyPath`] from a [`toml::Value`], returning it if it was
+    /// present. Removing an array element shifts the following elements down. An empty path
+    /// cannot be removed, and returns `None`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use toml_env::TomlKeyPath;
+    ///
+    /// let mut toml_value: toml::Value = toml::from_str(r#"
+    /// hosts = ["a", "b", "c"]
+    /// [db]
+    /// url = "postgres://db"
+    /// "#).unwrap();
+    ///
+    /// let url: TomlKeyPath = "db.url".parse().unwrap();
+    /// assert_eq!(url.remove(&mut toml_value).unwrap().as_str(), Some("postgres://db"));
+    /// assert!(url.resolve(&toml_value).is_none());
+    ///
+    /// let host: TomlKeyPath = "hosts.1".parse().unwrap();
+    /// assert_eq!(host.remove(&mut toml_value).unwrap().as_str(), Some("b"));
+    /// assert_eq!(host.resolve(&toml_value).unwrap().as_str(), Some("c"));
+    ///
+    /// assert!(url.remove(&mut toml_value).is_none());
+    /// ```
+    pub fn remove(&self, value: &mut toml::Value) -> Option<toml::Value> {
+        let (last, parent_path) = self.0.split_last()?;
+        let parent = resolve_elements_mut(parent_path, value)?;
+        match (last, parent) {
+            (PathElement::TableProperty(property), Value::Table(table)) => {
+                table.remove(&**property)
+            }
+            (PathElement::ArrayIndex(index), Value::Array(array)) if *index < array.len() => {
+                Some(array.remove(*index))
+            }
+            _ => None,
+        }
+    }
+
+    /// Append an `element` to the end of this path.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use toml_env::{PathElement, TomlKeyPath};
+    ///
+    /// let mut path = TomlKeyPath::default();
+    /// path.push("servers");
+    /// path.push(0);
+    /// path.push("host");
+    /// assert_eq!(path.to_string(), "servers.0.host");
+    /// assert_eq!(path.len(), 3);
+    ///
+    /// let parent = path.parent().unwrap();
+    /// assert_eq!(parent.to_string(), "servers.0");
+    /// assert_eq!(parent.join(&"port".parse().unwrap()).to_string(), "servers.0.port");
+    ///
+    /// assert_eq!(path.pop(), Some(PathElement::TableProperty("host".into())));
+    /// assert_eq!(path.iter().last(), Some(&PathElement::ArrayIndex(0)));
+    /// ```
+    pub fn push(&mut self, element: impl Into<PathElement>) {
+        self.0.push(element.into());
+    }
+
+    /// Remove the last element of this path and return it, or `None` if the path is empty.
+    pub fn pop(&mut self) -> Option<PathElement> {
+        self.0.pop()
+    }
+
+    /// The path without its last element, or `None` if the path is empty.
+    pub fn parent(&self) -> Option<TomlKeyPath> {
+        self.0
+            .split_last()
+            .map(|(_, parent)| TomlKeyPath(parent.iter().cloned().collect()))
+    }
+
+    /// A new path with the elements of `other` appended to the elements of this path.
+    pub fn join(&self, other: &TomlKeyPath) -> TomlKeyPath {
+        TomlKeyPath(self.0.iter().chain(&other.0).cloned().collect())
+    }
+
+    /// Iterate over the elements of this path.
+    pub fn iter(&self) -> std::slice::Iter<'_, PathElement> {
+        self.0.iter()
+    }
+
+    /// The number of elements in this path.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Whether this path has no elements (referring to the root of a value).
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// See [`TomlKeyPath::resolve_mut()`].
+fn resolve_elements_mut<'a>(
+    elements: &[PathElement],
+    value: &'a mut toml::Value,
+) -> Option<&'a mut toml::Value> {
+    elements
+        .iter()
+        .try_fold(value, |value, element| match (element, value) {
+            (PathElement::TableProperty(p), Value::Table(table)) => table.get_mut(&**p),
+            (PathElement::ArrayIndex(i), Value::Array(array)) => array.get_mut(*i),
+            _ => None,
+        })
+}
+
+impl std::fmt::Display for TomlKeyPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(
+            &self
+                .0
+                .iter()
+                .map(|e| match e {
+                    // Quote properties which would otherwise be parsed differently.
+                    PathElement::TableProperty(p)
+                        if p.is_empty()
+                            || &**p == "*"
+                            || &**p == "+"
+                            || p.contains(['.', '"', '\''])
+                            || usize::from_str(p).is_ok() =>
+                    {
+                        format!("\"{}\"", p.replace('\\', "\\\\").replace('"', "\\\""))
+                    }
+                    e => e.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join("."),
+        )
+    }
+}
+
+/// The reason that a [`TomlKeyPath`] could not be parsed, see [`TomlKeyPathParseError`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TomlKeyPathParseErrorKind {
+    /// A segment is empty (e.g. `child..value`), only returned by [`TomlKeyPath::parse_strict()`].
+    EmptySegment,
+    /// A quoted segment is missing its closing quote.
+    UnterminatedQuote,
+    /// A quoted segment is followed by a character other than `.`.
+    UnexpectedCharacter(char),
+}
+
+impl std::fmt::Display for TomlKeyPathParseErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TomlKeyPathParseErrorKind::EmptySegment => f.write_str("empty segment"),
+            TomlKeyPathParseErrorKind::UnterminatedQuote => f.write_str("unterminated quote"),
+            TomlKeyPathParseErrorKind::UnexpectedCharacter(c) => {
+                write!(f, "unexpected character {c:?} after quoted segment")
+            }
+        }
+    }
+}
+
+/// An error parsing a [`TomlKeyPath`].
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("Invalid key path {path:?}: {kind} at position {position}")]
+pub struct TomlKeyPathParseError {
+    /// The path which could not be parsed.
+    pub path: String,
+    /// The byte offset in the `path` at which the error occurred.
+    pub position: usize,
+    /// The reason for the error.
+    pub kind: TomlKeyPathParseErrorKind,
+}
+
+impl TomlKeyPath {
+    /// Parse a string into a [`TomlKeyPath`] like [`FromStr`], but also reject empty segments
+    /// (e.g. `child..value` or `key.`), which are usually typos. An empty string is the path to
+    /// the root of a value.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use toml_env::{TomlKeyPath, TomlKeyPathParseErrorKind};
+    ///
+    /// assert_eq!(TomlKeyPath::parse_strict("child.value").unwrap().len(), 2);
+    ///
+    /// let error = TomlKeyPath::parse_strict("child..value").unwrap_err();
+    /// assert_eq!(error.kind, TomlKeyPathParseErrorKind::EmptySegment);
+    /// assert_eq!(error.position, 6);
+    ///
+    /// // The same path is accepted (ignoring the empty segment) when using `FromStr`.
+    /// assert_eq!("child..value".parse::<TomlKeyPath>().unwrap().len(), 2);
+    /// ```
+    pub fn parse_strict(s: &str) -> std::result::Result<Self, TomlKeyPathParseError> {
+        Self::parse(s, true)
+    }
+
+    fn parse(s: &str, strict: bool) -> std::result::Result<Self, TomlKeyPathParseError> {
+        let error = |position: usize, kind: TomlKeyPathParseErrorKind| TomlKeyPathParseError {
+            path: s.to_owned(),
+            position,
+            kind,
+        };
+
+        let mut v = PathElements::new();
+        if s.is_empty() {
+            return Ok(Self(v));
+        }
+        let mut chars = s.char_indices().peekable();
+        loop {
+            match chars.peek() {
+                Some(&(start, quote @ ('"' | '\''))) => {
+                    chars.next();
+                    let mut property = String::new();
+                    loop {
+                        match chars.next() {
+                            Some((_, c)) if c == quote => break,
+                            // Basic strings (but not literal strings) support escapes.
+                            Some((_, '\\')) if quote == '"' => match chars.next() {
+                                Some((_, c)) => property.push(c),
+                                None => {
+                                    return Err(error(
+                                        start,
+                                        TomlKeyPathParseErrorKind::UnterminatedQuote,
+                                    ))
+                                }
+                            },
+                            Some((_, c)) => property.push(c),
+                            None => {
+                                return Err(error(
+                                    start,
+                                    TomlKeyPathParseErrorKind::UnterminatedQuote,
+                                ))
+                            }
+                        }
+                    }
+                    v.push(PathElement::TableProperty(property.into_boxed_str()));
+                }
+                next => {
+                    let start = next.map_or(s.len(), |(i, _)| *i);
+                    let mut k = String::new();
+                    while let Some((_, c)) = chars.next_if(|(_, c)| *c != '.') {
+                        k.push(c);
+                    }
+                    if k.is_empty() {
+                        if strict {
+                            return Err(error(start, TomlKeyPathParseErrorKind::EmptySegment));
+                        }
+                    } else if k == "*" {
+                        v.push(PathElement::Wildcard);
+                    } else if k == "+" {
+                        v.push(PathElement::Append);
+                    } else if let Ok(i) = usize::from_str(&k) {
+                        v.push(PathElement::ArrayIndex(i));
+                    } else {
+                        v.push(PathElement::TableProperty(k.into_boxed_str()));
+                    }
+                }
+            }
+
+            match chars.next() {
+                Some((_, '.')) => {}
+                None => break,
+                // Characters following a closing quote.
+                Some((i, c)) => {
+                    return Err(error(i, TomlKeyPathParseErrorKind::UnexpectedCharacter(c)))
+                }
+            }
+        }
+
+        Ok(Self(v))
+    }
+}
+
+impl FromStr for TomlKeyPath {
+    type Err = TomlKeyPathParseError;
+
+    /// Parse a string into a [`TomlKeyPath`]. Empty segments are ignored, see
+    /// [`TomlKeyPath::parse_strict()`] to reject them instead.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use toml_env::TomlKeyPath;
+    ///
+    /// "key".parse::<TomlKeyPath>().unwrap();
+    /// "key.0".parse::<TomlKeyPath>().unwrap();
+    /// "key.key".parse::<TomlKeyPath>().unwrap();
+    /// "key.0.key".parse::<TomlKeyPath>().unwrap();
+    /// "key.key.key".parse::<TomlKeyPath>().unwrap();
+    /// "".parse::<TomlKeyPath>().unwrap();
+    /// ".".parse::<TomlKeyPath>().unwrap();
+    /// ".key".parse::<TomlKeyPath>().unwrap();
+    /// "key.".parse::<TomlKeyPath>().unwrap();
+    ///
+    /// // Quoted keys (which are never array indices) can contain dots.
+    /// let path: TomlKeyPath = r#"servers."my.host.com".port"#.parse().unwrap();
+    /// assert_eq!(path.len(), 3);
+    /// "key.'0'".parse::<TomlKeyPath>().unwrap();
+    /// r#"key."escaped \" quote""#.parse::<TomlKeyPath>().unwrap();
+    /// "key.\"unterminated".parse::<TomlKeyPath>().unwrap_err();
+    /// ```
+    fn from_str(s: &str) -> std::result::Result<Self, TomlKeyPathParseError> {
+        Self::parse(s, false)
+    }
+}
+
+/// Serialized in the same format as [`Display`](std::fmt::Display).
+impl Serialize for TomlKeyPath {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+/// Deserialized from a string using [`FromStr`].
+impl<'de> serde::Deserialize<'de> for TomlKeyPath {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct Visitor;
+
+        impl serde::de::Visitor<'_> for Visitor {
+            type Value = TomlKeyPath;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                formatter.write_str("a key path string like `key.0.key`")
+            }
+
+            fn visit_str<E: serde::de::Error>(
+                self,
+                value: &str,
+            ) -> std::result::Result<Self::Value, E> {
+                TomlKeyPath::from_str(value).map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_str(Visitor)
+    }
+}
+
+/// Extension trait for typed access to values in a [`Value`], such as a configuration loaded
+/// without a fixed type (see [`InitializationReport::value`]).
+///
+/// # Example
+///
+/// ```rust
+/// use toml_env::TomlValueExt;
+///
+/// let value: toml::Value = toml::from_str("[server]\nport = 8080").unwrap();
+/// let port: u16 = value.get_as("server.port").unwrap();
+/// assert_eq!(port, 8080);
+///
+/// let error = value.get_as::<u16>("server.host").unwrap_err();
+/// assert_eq!(error.to_string(), "No value found at `server.host`");
+/// ```
+pub trait TomlValueExt {
+    /// Resolve the value at the key `path` (see [`TomlKeyPath`]) and deserialize it into `T`.
+    /// Returns an error (which reports the `path`) if the `path` is invalid, if there is no value
+    /// at the `path`, or if the value cannot be deserialized.
+    fn get_as<T: DeserializeOwned>(&self, path: &str) -> Result<T>;
+}
+
+impl TomlValueExt for Value {
+    fn get_as<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+        get_key_as(self, parse_get_as_path(path)?)
+    }
+}
+
+/// Parse the `path` passed to [`TomlValueExt::get_as`].
+fn parse_get_as_path(path: &str) -> Result<TomlKeyPath> {
+    Ok(path
+        .parse()
+        .map_err(|error| InnerError::InvalidKeyPath { error })?)
+}
+
+/// Deserialize the value at the `key` in `value`, see [`TomlValueExt::get_as`].
+fn get_key_as<T: DeserializeOwned>(value: &Value, key: TomlKeyPath) -> Result<T> {
+    let value = match key.resolve(value) {
+        Some(value) => value.clone(),
+        None => return Err(InnerError::KeyPathNotFound { key: Box::new(key) }.into()),
+    };
+    value.try_into().map_err(|error| {
+        InnerError::ErrorDeserializingValue {
+            key: Box::new(key),
+            error: Box::new(error),
+        }
+        .into()
+    })
+}
+
+/// A configuration value along with its key and a description of its source (see
+/// [`InitializationReport::provenance`]), for reporting where a misconfigured value came from in
+/// an application's own errors. Serialized as the `value` alone.
+///
+/// The source is only filled in when the configuration is deserialized using
+/// [`InitializationReport::deserialize_with_sources()`]. Otherwise (e.g. using [`initialize()`])
+/// the [`WithSource::key`] is empty and the [`WithSource::source`] is `None`.
+///
+/// # Example
+///
+/// ```rust
+/// use serde::{Deserialize, Serialize};
+/// use toml_env::{initialize_with_report, map_env, Args, NumberInference, WithSource};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Config {
+///     port: WithSource<u16>,
+/// }
+///
+/// std::env::set_var("WITH_SOURCE_PORT", "80");
+///
+/// let (_config, report) = initialize_with_report::<toml::Value>(Args {
+///     map_env: map_env! { "WITH_SOURCE_PORT" => "port" },
+///     number_inference: NumberInference::PreferInteger,
+///     ..Args::default()
+/// })
+/// .unwrap();
+///
+/// let config: Config = report.deserialize_with_sources().unwrap().unwrap();
+/// if *config.port < 1024 {
+///     let message = format!(
+///         "`{}` from {} must not be a privileged port",
+///         config.port.key,
+///         config.port.source.as_deref().unwrap_or("unknown source"),
+///     );
+///     assert_eq!(
+///         message,
+///         "`port` from environment variables WITH_SOURCE_PORT must not be a privileged port"
+///     );
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct WithSource<T> {
+    /// The value.
+    pub value: T,
+    /// The key of the value in the configuration.
+    pub key: TomlKeyPath,
+    /// A description of the source of the value (see [`ConfigSource`]), `None` if it is unknown
+    /// (e.g. for a table containing values from several sources).
+    pub source: Option<String>,
+}
+
+/// The name used by [`WithSource`] to request its key and source from a [`SourceDeserializer`].
+const WITH_SOURCE: &str = "$__toml_env_private_WithSource";
+
+impl<T> std::ops::Deref for WithSource<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T: Serialize> Serialize for WithSource<T> {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        self.value.serialize(serializer)
+    }
+}
+
+/// A [`SourceDeserializer`] visits a sequence of the value, its key, and its source if known.
+/// Other deserializers visit the value as a newtype struct.
+impl<'de, T: serde::Deserialize<'de>> serde::Deserialize<'de> for WithSource<T> {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct Visitor<T>(std::marker::PhantomData<T>);
+
+        impl<'de, T: serde::Deserialize<'de>> serde::de::Visitor<'de> for Visitor<T> {
+            type Value = WithSource<T>;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("a configuration value")
+            }
+
+            fn visit_newtype_struct<D>(
+                self,
+                deserializer: D,
+            ) -> std::result::Result<Self::Value, D::Error>
+            where
+                D: serde::Deserializer<'de>,
+            {
+                Ok(WithSource {
+                    value: T::deserialize(deserializer)?,
+                    key: TomlKeyPath::default(),
+                    source: None,
+                })
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> std::result::Result<Self::Value, A::Error>
+            where
+                A: serde::de::SeqAccess<'de>,
+            {
+                use serde::de::Error;
+                let value = seq
+                    .next_element()?
+                    .ok_or_else(|| A::Error::invalid_length(0, &self))?;
+                let key: String = seq
+                    .next_element()?
+                    .ok_or_else(|| A::Error::invalid_length(1, &self))?;
+                Ok(WithSource {
+                    value,
+                    key: key.parse().map_err(A::Error::custom)?,
+                    source: seq.next_element()?,
+                })
+            }
+        }
+
+        deserializer.deserialize_newtype_struct(WITH_SOURCE, Visitor(std::marker::PhantomData))
+    }
+}
+
+/// A merged configuration which logs each access to a secret (a key in [`Args::redact`], or a
+/// table containing one) made using [`TomlValueExt::get_as`], including accesses which fail, so
+/// that reads of credentials can be audited. Created using [`InitializationReport::audited()`].
+///
+/// # Example
+///
+/// ```rust
+/// use toml_env::{initialize_with_report, map_env, Args, Logging, TomlValueExt};
+///
+/// std::env::set_var("AUDITED_DB_PASSWORD", "hunter2");
+///
+/// let (_config, report) = initialize_with_report::<toml::Value>(Args {
+///     map_env: map_env! { "AUDITED_DB_PASSWORD" => "db.password" },
+///     redact: vec!["db.password".parse().unwrap()],
+///     ..Args::default()
+/// })
+/// .unwrap();
+///
+/// // Logs "Accessed secret `db.password` at <seconds since the UNIX epoch>".
+/// let config = report.audited(Logging::StdOut);
+/// let password: String = config.get_as("db.password").unwrap();
+/// assert_eq!(password, "hunter2");
+/// ```
+#[derive(Clone)]
+pub struct AuditedConfig {
+    /// The merged configuration.
+    value: Value,
+    /// See [`Args::redact`].
+    redact: Vec<TomlKeyPath>,
+    /// Where accesses to secrets are logged.
+    logging: Logging,
+}
+
+impl AuditedConfig {
+    /// The merged configuration. Accesses made directly to this value are not logged.
+    pub fn value(&self) -> &Value {
+        &self.value
+    }
+}
+
+impl TomlValueExt for AuditedConfig {
+    fn get_as<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+        let key = parse_get_as_path(path)?;
+        // Logged before deserializing, so that reads which fail are also audited.
+        if contains_redacted(&key, &self.redact) {
+            log_info(
+                self.logging,
+                format_args!(
+                    "Accessed secret `{key}` at {}",
+                    unix_seconds(SystemTime::now())
+                ),
+            );
+        }
+        get_key_as(&self.value, key)
+    }
+}
+
+/// Create a mapping from environment variable names to [`TomlKeyPath`]s for use with
+/// [`Args::map_env`]. The key paths are validated at compile time (they cannot contain empty
+/// segments like `child..value`).
+///
+/// # Example
+///
+/// ```rust
+/// use toml_env::{map_env, Args};
+///
+/// let args = Args {
+///     map_env: map_env! {
+///         "VALUE_1" => "value_1",
+///         "MY_APP__CHILD__VALUE_7" => "child.value_3",
+///     },
+///     ..Args::default()
+/// };
+/// assert_eq!(args.map_env["MY_APP__CHILD__VALUE_7"].to_string(), "child.value_3");
+///
+/// // Keys containing dots can be quoted.
+/// let map_env = map_env! { "HOST_PORT" => r#"servers."my.host.com".port"# };
+/// ```
+///
+/// Invalid key paths are a compile error:
+///
+/// ```rust,compile_fail
+/// let map_env = toml_env::map_env! { "VALUE" => "child..value" };
+/// ```
+#[macro_export]
+macro_rules! map_env {
+    ($($name:expr => $key:literal),* $(,)?) => {{
+        $(
+            const _: () = ::std::assert!(
+                $crate::__is_valid_key_path($key),
+                ::std::concat!("Invalid key path ", ::std::stringify!($key))
+            );
+        )*
+        #[allow(unused_mut)]
+        let mut map = ::std::collections::HashMap::<&str, $crate::TomlKeyPath>::new();
+        $(
+            map.insert(
+                $name,
+                <$crate::TomlKeyPath as ::std::str::FromStr>::from_str($key)
+                    .expect("Expected key path to be validated at compile time"),
+            );
+        )*
+        map
+    }};
+}
+
+/// Used by [`map_env!`] to validate key paths at compile time.
+#[doc(hidden)]
+pub const fn __is_valid_key_path(path: &str) -> bool {
+    let bytes = path.as_bytes();
+    if bytes.is_empty() {
+        return true;
+    }
+
+    let mut i = 0;
+    let mut segment_len = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'.' {
+            if segment_len == 0 {
+                return false;
+            }
+            segment_len = 0;
+        } else if segment_len == 0 && (bytes[i] == b'"' || bytes[i] == b'\'') {
+            let quote = bytes[i];
+            i += 1;
+            while i < bytes.len() && bytes[i] != quote {
+                if quote == b'"' && bytes[i] == b'\\' {
+                    i += 1;
+                }
+                i += 1;
+            }
+            if i >= bytes.len() || (i + 1 < bytes.len() && bytes[i + 1] != b'.') {
+                return false;
+            }
+            // A quoted segment is never empty, even if the quotes are.
+            segment_len = 1;
+        } else {
+            segment_len += 1;
+        }
+        i += 1;
+    }
+    segment_len != 0
+}
+
+/// The naming conventions used to find and map configuration, which can be customized once and
+/// used to create consistent [`Args`] (using [`Conventions::args()`]) and [`AutoMapEnvArgs`]
+/// (using [`Conventions::auto_map_env_args()`]).
+///
+/// # Example
+///
+/// ```rust
+/// use toml_env::{Args, Conventions};
+///
+/// let conventions = Conventions {
+///     config_variable_name: "MY_APP",
+///     ..Conventions::default()
+/// };
+/// let args = Args {
+///     # #[cfg(feature = "auto-map-env")]
+///     auto_map_env: Some(conventions.auto_map_env_args()),
+///     ..conventions.args()
+/// };
+/// assert_eq!(args.config_variable_name, "MY_APP");
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Conventions<'a> {
+    /// Path to the `.env.toml` format file. The value is [`DEFAULT_DOTENV_PATH`] by default.
+    #[cfg(feature = "dotenv")]
+    pub dotenv_path: &'a Path,
+    /// Name of the environment variable that stores the config, which is also the prefix for
+    /// automatically mapped environment variables. The value is [`DEFAULT_CONFIG_VARIABLE_NAME`]
+    /// by default.
+    pub config_variable_name: &'a str,
+    /// The divider between different levels of parent.child in environment variable names. The
+    /// value is [`DEFAULT_MAP_ENV_DIVIDER`] by default.
+    pub divider: &'a str,
+    /// Transform the name of an environment variable (without the prefix) into a key. By default
+    /// this transforms it to lowercase.
+    pub transform: fn(&str) -> String,
+}
+
+impl Default for Conventions<'static> {
+    fn default() -> Self {
+        Self {
+            #[cfg(feature = "dotenv")]
+            dotenv_path: Path::new(DEFAULT_DOTENV_PATH),
+            config_variable_name: DEFAULT_CONFIG_VARIABLE_NAME,
+            divider: DEFAULT_MAP_ENV_DIVIDER,
+            transform: |name| name.to_lowercase(),
+        }
+    }
+}
+
+impl<'a> Conventions<'a> {
+    /// The name of the automatically mapped environment variable for a `key`, assuming that
+    /// [`Conventions::transform`] converts names to lowercase (e.g. `MY_APP__DB__HOST` for
+    /// `db.host`).
+    pub fn variable_name(&self, key: &TomlKeyPath) -> String {
+        uppercase_variable_name(self.config_variable_name, self.divider, key)
+    }
+
+    /// Create [`Args`] using these conventions, with other options set to their defaults.
+    pub fn args(&self) -> Args<'a> {
+        Args {
+            #[cfg(feature = "dotenv")]
+            dotenv_path: self.dotenv_path,
+            config_variable_name: self.config_variable_name,
+            ..Args::default()
+        }
+    }
+
+    /// Create [`AutoMapEnvArgs`] using these conventions, with other options set to their
+    /// defaults.
+    #[cfg(feature = "auto-map-env")]
+    pub fn auto_map_env_args(&self) -> AutoMapEnvArgs<'a> {
+        AutoMapEnvArgs {
+            divider: self.divider,
+            prefix: Some(self.config_variable_name),
+            transform: Box::new(self.transform),
+            ..AutoMapEnvArgs::default()
+        }
+    }
+}
+
+/// The name of the variable with the `prefix` for `key`, with the elements of the `key` in
+/// uppercase and separated by the `divider` (e.g. `MY_APP__DB__HOST` for `db.host`).
+fn uppercase_variable_name(prefix: &str, divider: &str, key: &TomlKeyPath) -> String {
+    let mut name = prefix.to_owned();
+    for element in &key.0 {
+        name.push_str(divider);
+        name.push_str(&element.to_string().to_uppercase());
+    }
+    name
+}
+
+/// Configuration sources found in the conventional locations used by containerized services,
+/// see [`ContainerConfig::detect()`].
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use serde::{Deserialize, Serialize};
+/// use toml_env::{initialize, ContainerConfig};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Config {
+///     value: String,
+/// }
+///
+/// let container = ContainerConfig::detect("my-app");
+/// println!("{container}");
+/// let config: Option<Config> = initialize(container.args()).unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct ContainerConfig {
+    /// The first config file which was found, see [`container_config_paths()`].
+    pub config_path: Option<PathBuf>,
+    /// The config file paths which were checked, in order, and whether they exist.
+    pub checked_paths: Vec<(PathBuf, bool)>,
+    /// The name of the environment variable which stores the config, which is also the prefix
+    /// for automatically mapped environment variables (the application name in uppercase, with
+    /// `-` replaced by `_`).
+    pub config_variable_name: String,
+    /// The names of the environment variables which were found with the prefix followed by the
+    /// [`DEFAULT_MAP_ENV_DIVIDER`] (which are automatically mapped), sorted by name.
+    pub variable_names: Vec<String>,
+}
+
+/// The conventional locations of the config file for an application named `app_name` in a
+/// container, in order of preference:
+///
+/// + `/config/config.toml`
+/// + `/var/run/secrets/<app_name>/config.toml`
+/// + `/run/secrets/<app_name>/config.toml`
+/// + `/etc/<app_name>/config.toml`
+pub fn container_config_paths(app_name: &str) -> Vec<PathBuf> {
+    vec![
+        PathBuf::from("/config/config.toml"),
+        Path::new("/var/run/secrets")
+            .join(app_name)
+            .join("config.toml"),
+        Path::new("/run/secrets").join(app_name).join("config.toml"),
+        Path::new("/etc").join(app_name).join("config.toml"),
+    ]
+}
+
+impl ContainerConfig {
+    /// Check the conventional locations of the config file (see [`container_config_paths()`])
+    /// and environment variables for an application named `app_name`.
+    pub fn detect(app_name: &str) -> Self {
+        Self::detect_paths(app_name, container_config_paths(app_name))
+    }
+
+    fn detect_paths(app_name: &str, paths: Vec<PathBuf>) -> Self {
+        let checked_paths: Vec<(PathBuf, bool)> = paths
+            .into_iter()
+            .map(|path| {
+                let exists = path.is_file();
+                (path, exists)
+            })
+            .collect();
+        let config_path = checked_paths
+            .iter()
+            .find(|(_, exists)| *exists)
+            .map(|(path, _)| path.clone());
+
+        let config_variable_name = app_name.to_uppercase().replace('-', "_");
+        let prefix = format!("{config_variable_name}{DEFAULT_MAP_ENV_DIVIDER}");
+        let mut variable_names: Vec<String> = std::env::vars_os()
+            .filter_map(|(name, _)| name.into_string().ok())
+            .filter(|name| name.starts_with(&prefix))
+            .collect();
+        variable_names.sort();
+
+        Self {
+            config_path,
+            checked_paths,
+            config_variable_name,
+            variable_names,
+        }
+    }
+
+    /// Create [`Args`] using the detected config file and environment variable prefix (with
+    /// automatic mapping enabled if the `auto-map-env` feature is enabled), with other options
+    /// set to their defaults.
+    pub fn args(&self) -> Args<'_> {
+        let conventions = Conventions {
+            config_variable_name: &self.config_variable_name,
+            ..Conventions::default()
+        };
+        Args {
+            config_path: self.config_path.as_deref(),
+            #[cfg(feature = "auto-map-env")]
+            auto_map_env: Some(conventions.auto_map_env_args()),
+            ..conventions.args()
+        }
+    }
+}
+
+impl std::fmt::Display for ContainerConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Config files:")?;
+        for (path, exists) in &self.checked_paths {
+            let status = match (exists, Some(path) == self.config_path.as_ref()) {
+                (true, true) => "using",
+                (true, false) => "found (ignored)",
+                (false, _) => "not found",
+            };
+            writeln!(f, "  {path:?}: {status}")?;
+        }
+        write!(
+            f,
+            "Environment variables with prefix {}: ",
+            self.config_variable_name
+        )?;
+        if self.variable_names.is_empty() {
+            write!(f, "none")
+        } else {
+            write!(f, "{}", self.variable_names.join(", "))
+        }
+    }
+}
+
+/// Automatically map environment variables into config.
+#[cfg(feature = "auto-map-env")]
+pub struct AutoMapEnvArgs<'a> {
+    /// The divider that separates different levels of the parent.child relationship for the
+    /// mapping. This will get replaced with `.` when converting the name of a variable to a [`TomlKeyPath`]. The default value is [`DEFAULT_MAP_ENV_DIVIDER`].
+    pub divider: &'a str,
+    /// Prefix for environment variables to be mapped. By default this will be [`DEFAULT_CONFIG_VARIABLE_NAME`].
+    pub prefix: Option<&'a str>,
+    /// A transform operation to perform on the environment variable before parsing it. By default
+    /// this transforms it to lowercase.
+    pub transform: Box<dyn Fn(&str) -> String>,
+    /// Whether to infer the types of the values of automatically mapped environment variables.
+    /// If `false`, values are inserted as strings unless a [`ValueHint`] or [`Args::schema`]
+    /// specifies otherwise. `true` by default.
+    pub infer_types: bool,
+    /// If set, values of automatically mapped environment variables which are mapped to an array
+    /// in [`Args::schema`] are split on this delimiter, see [`ValueHint::List`]. Values of other
+    /// variables are not split (use [`Args::value_hints`] for individual variables). Initialization
+    /// returns an error if this is set without an [`Args::schema`], rather than splitting every
+    /// value containing the delimiter. `None` by default.
+    pub list_delimiter: Option<char>,
+    /// How numbers are inferred from the values of automatically mapped environment variables,
+    /// overriding [`Args::number_inference`] if set. `None` by default.
+    pub number_inference: Option<NumberInference>,
+}
+
+#[cfg(feature = "auto-map-env")]
+impl Default for AutoMapEnvArgs<'_> {
+    fn default() -> Self {
+        Self {
+            divider: DEFAULT_MAP_ENV_DIVIDER,
+            prefix: None,
+            transform: Box::new(Conventions::default().transform),
+            infer_types: true,
+            list_delimiter: None,
+            number_inference: None,
+        }
+    }
+}
+
+#[cfg(feature = "auto-map-env")]
+impl AutoMapEnvArgs<'_> {
+    /// The name of the environment variable which is automatically mapped to `key` (e.g.
+    /// `MY_APP__RELAY__DOWNLINK_PORT` for `relay.downlink_port`), for use in error messages and
+    /// documentation. If [`AutoMapEnvArgs::prefix`] is `None`, the `config_variable_name` (see
+    /// [`Args::config_variable_name`]) is used as the prefix.
+    ///
+    /// The name is created in uppercase, and checked by mapping it back to a key using the
+    /// [`AutoMapEnvArgs::transform`]. Returns `None` if that does not produce the same `key`
+    /// (e.g. a key containing uppercase letters, or the `divider`, with the default transform).
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use toml_env::AutoMapEnvArgs;
+    ///
+    /// let auto_args = AutoMapEnvArgs {
+    ///     prefix: Some("MY_APP"),
+    ///     ..AutoMapEnvArgs::default()
+    /// };
+    /// assert_eq!(
+    ///     auto_args.variable_name(&"relay.downlink_port".parse().unwrap(), "CONFIG"),
+    ///     Some("MY_APP__RELAY__DOWNLINK_PORT".to_owned())
+    /// );
+    /// assert_eq!(auto_args.variable_name(&"relay.downlinkPort".parse().unwrap(), "CONFIG"), None);
+    /// ```
+    pub fn variable_name(&self, key: &TomlKeyPath, config_variable_name: &str) -> Option<String> {
+        let prefix = self.prefix.unwrap_or(config_variable_name);
+        let name = uppercase_variable_name(prefix, self.divider, key);
+        let mapped_key: TomlKeyPath = (self.transform)(&name[prefix.len() + self.divider.len()..])
+            .replace(self.divider, ".")
+            .parse()
+            .ok()?;
+        (!key.is_empty() && &mapped_key == key).then_some(name)
+    }
+}
+
+/// What to do when the [`Args::config_variable_key_path`] is not found, see
+/// [`Args::missing_key_path`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MissingKeyPath {
+    /// Ignore the config stored in the environment variable, logging the key path which was not
+    /// found.
+    #[default]
+    Silent,
+    /// Ignore the config stored in the environment variable, and add a warning to
+    /// [`InitializationReport::warnings`].
+    Warn,
+    /// Return an error.
+    Error,
+}
+
+/// How mapped environment variables which are set to an empty string are handled, see
+/// [`Args::empty_values`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EmptyValues {
+    /// Parse the empty string like any other value (usually as an empty string).
+    #[default]
+    Keep,
+    /// Treat the variable as a request to remove the key it is mapped to, including any value
+    /// for the key from the config file and [`Args::defaults`], so that `Option` fields are
+    /// deserialized as `None`. This is useful when deployment templates render unset values as
+    /// empty strings.
+    Unset,
+}
+
+/// Configuration supplied as a string instead of a file, see [`Args::config_inline`] and
+/// [`Args::dotenv_inline`]. Configuration from an [`std::io::Read`] can be loaded using
+/// [`initialize_reader()`].
+#[derive(Debug, Clone, Copy)]
+pub struct InlineSource<'a> {
+    /// A label describing where the configuration came from (e.g. `"socket"`), which is used in
+    /// [`ConfigSource::Inline`] and error messages.
+    pub label: &'a str,
+    /// The TOML contents.
+    pub content: &'a str,
+}
+
+/// Where to load the configuration file from, see [`Args::config_location`]. This can be parsed
+/// from a string (e.g. a command line argument) using [`ConfigLocation::parse()`].
+#[derive(Debug, Clone, Copy)]
+pub enum ConfigLocation<'a> {
+    /// A path to a file, the same as [`Args::config_path`].
+    Path(&'a Path),
+    /// The contents of the environment variable with this name, reported with a
+    /// [`ConfigSource::Environment`] source. Ignored if the variable is not set.
+    Env(&'a str),
+    /// The contents of standard input, reported with a [`ConfigSource::Inline`] source labelled
+    /// `stdin`.
+    Stdin,
+    /// Configuration supplied as a string, the same as [`Args::config_inline`].
+    Inline(InlineSource<'a>),
+    /// An `http://` or `https://` URL which the TOML configuration is fetched from using the
+    /// `Args::remote` options, reported with a [`ConfigSource::Remote`] source. Ignored if the
+    /// server responds with `404 Not Found`. Loading it returns an error unless the `remote`
+    /// feature is enabled.
+    Url(&'a str),
+}
+
+impl<'a> ConfigLocation<'a> {
+    /// Parse a location based on its scheme:
+    ///
+    /// + `file://path` or a plain `path`: [`ConfigLocation::Path`].
+    /// + `env://NAME`: [`ConfigLocation::Env`].
+    /// + `stdin://` or `-`: [`ConfigLocation::Stdin`].
+    /// + `http://` or `https://` with the `remote` feature: [`ConfigLocation::Url`].
+    ///
+    /// Other schemes (such as `s3://`) are not supported and return an error.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use toml_env::ConfigLocation;
+    ///
+    /// assert!(matches!(ConfigLocation::parse("config.toml"), Ok(ConfigLocation::Path(_))));
+    /// assert!(matches!(ConfigLocation::parse("file:///etc/app.toml"), Ok(ConfigLocation::Path(_))));
+    /// assert!(matches!(ConfigLocation::parse("env://APP_CONFIG"), Ok(ConfigLocation::Env("APP_CONFIG"))));
+    /// assert!(matches!(ConfigLocation::parse("-"), Ok(ConfigLocation::Stdin)));
+    /// assert!(ConfigLocation::parse("s3://bucket/app.toml").is_err());
+    /// ```
+    pub fn parse(location: &'a str) -> Result<Self> {
+        if location == "-" || location == "stdin://" {
+            return Ok(ConfigLocation::Stdin);
+        }
+        if let Some(path) = location.strip_prefix("file://") {
+            return Ok(ConfigLocation::Path(Path::new(path)));
+        }
+        if let Some(name) = location.strip_prefix("env://") {
+            if !name.is_empty() {
+                return Ok(ConfigLocation::Env(name));
+            }
+        }
+        #[cfg(feature = "remote")]
+        if location.starts_with("http://") || location.starts_with("https://") {
+            return Ok(ConfigLocation::Url(location));
+        }
+        if location.contains("://") {
+            return Err(InnerError::UnsupportedConfigLocation {
+                location: location.to_owned(),
+            }
+            .into());
+        }
+        Ok(ConfigLocation::Path(Path::new(location)))
+    }
+}
+
+/// The default timeout for fetching a [`ConfigLocation::Url`], see [`RemoteArgs::timeout`].
+#[cfg(feature = "remote")]
+pub const DEFAULT_REMOTE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Options for fetching a [`ConfigLocation::Url`], see [`Args::remote`].
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use std::time::Duration;
+/// use toml_env::{Args, ConfigLocation, RemoteArgs};
+///
+/// let token = std::env::var("CONFIG_SERVICE_TOKEN").unwrap();
+/// let config: Option<toml::Value> = toml_env::initialize(Args {
+///     config_location: Some(ConfigLocation::Url("https://config.internal/my-app.toml")),
+///     remote: RemoteArgs {
+///         timeout: Duration::from_secs(5),
+///         headers: vec![("Authorization".to_owned(), format!("Bearer {token}"))],
+///         ..RemoteArgs::default()
+///     },
+///     ..Args::default()
+/// })
+/// .unwrap();
+/// ```
+#[cfg(feature = "remote")]
+#[derive(Clone)]
+pub struct RemoteArgs {
+    /// The timeout for the whole request, including connecting and reading the response.
+    /// [`DEFAULT_REMOTE_TIMEOUT`] by default. Ignored if [`RemoteArgs::agent`] is set.
+    pub timeout: Duration,
+    /// Headers sent with the request (e.g. an `Authorization` header containing an auth token).
+    /// Their values are redacted in the [`Debug`] output. Empty by default.
+    pub headers: Vec<(String, String)>,
+    /// The agent used to make the request, which can be built with a custom TLS configuration
+    /// (e.g. trusting an internal certificate authority) or proxy using [`ureq::AgentBuilder`].
+    /// `None` by default, which uses an agent with the [`RemoteArgs::timeout`] and the `webpki`
+    /// root certificates.
+    pub agent: Option<ureq::Agent>,
+}
+
+#[cfg(feature = "remote")]
+impl Default for RemoteArgs {
+    fn default() -> Self {
+        Self {
+            timeout: DEFAULT_REMOTE_TIMEOUT,
+            headers: Vec::new(),
+            agent: None,
+        }
+    }
+}
+
+#[cfg(feature = "remote")]
+impl std::fmt::Debug for RemoteArgs {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let headers: Vec<(&str, &str)> = self
+            .headers
+            .iter()
+            .map(|(name, _)| (name.as_str(), REDACTED))
+            .collect();
+        f.debug_struct("RemoteArgs")
+            .field("timeout", &self.timeout)
+            .field("headers", &headers)
+            .field("agent", &self.agent)
+            .finish()
+    }
+}
+
+#[cfg(feature = "remote")]
+impl RemoteArgs {
+    /// Create a request using the [`RemoteArgs::agent`] (or an agent with the
+    /// [`RemoteArgs::timeout`]), with the [`RemoteArgs::headers`] set.
+    fn request(&self, method: &str, url: &str) -> ureq::Request {
+        let agent = match &self.agent {
+            Some(agent) => agent.clone(),
+            None => ureq::AgentBuilder::new().timeout(self.timeout).build(),
+        };
+        let mut request = agent.request(method, url);
+        for (name, value) in &self.headers {
+            request = request.set(name, value);
+        }
+        request
+    }
+}
+
+/// Fetch the configuration at `url`, returning `None` if it was not found.
+#[cfg(feature = "remote")]
+fn fetch_remote_config(url: &str, remote: &RemoteArgs) -> InnerResult<Option<String>> {
+    let fetch_error =
+        |error: Box<dyn std::error::Error + Send + Sync>| InnerError::ErrorFetchingRemoteConfig {
+            url: url.to_owned(),
+            error,
+        };
+    let response = match remote.request("GET", url).call() {
+        Ok(response) => response,
+        Err(ureq::Error::Status(404, _)) => return Ok(None),
+        Err(error) => return Err(fetch_error(Box::new(error))),
+    };
+    response
+        .into_string()
+        .map(Some)
+        .map_err(|error| fetch_error(Box::new(error)))
+}
+
+/// Args as input to [`initialize()`].
+pub struct Args<'a> {
+    /// Path to `.env.toml` format file. The value is [`DEFAULT_DOTENV_PATH`] by default.
+    #[cfg(feature = "dotenv")]
+    pub dotenv_path: &'a Path,
+    /// How line endings in values set as environment variables from the `.env.toml` file are
+    /// handled. [`LineEndings::Lf`] by default.
+    #[cfg(feature = "dotenv")]
+    pub dotenv_line_endings: LineEndings,
+    /// How non-finite floats in the `.env.toml` file are handled when they are set as environment
+    /// variables. [`NonFiniteFloats::Allow`] by default.
+    #[cfg(feature = "dotenv")]
+    pub dotenv_non_finite_floats: NonFiniteFloats,
+    /// How variables in the `.env.toml` file which are already set in the environment are
+    /// handled. [`DotEnvOverlap::Overwrite`] by default.
+    #[cfg(feature = "dotenv")]
+    pub dotenv_overlap: DotEnvOverlap,
+    /// Path to a `.env` file which has already been loaded into the environment (e.g. by
+    /// `dotenvy`), for applications migrating to the `.env.toml` format. Mapped environment
+    /// variables which are set in this file are reported with a
+    /// [`ConfigSource::PreloadedDotEnv`] source instead of [`ConfigSource::Environment`]. Only
+    /// the names of the variables are read from the file. `None` by default.
+    pub preloaded_dotenv_path: Option<&'a Path>,
+    /// Path to a plain `.env` file (with `KEY=value` lines, as used by `dotenv`/`dotenvy`), whose
+    /// variables are set in the environment before environment variables are mapped (so they can
+    /// be mapped by [`Args::map_env`] or [`Args::auto_map_env`]). Lines may have an `export`
+    /// prefix, values may be single quoted (literal) or double quoted (with `\n`, `\"` and `\\`
+    /// escapes), and `#` starts a comment (except within quotes, or in the middle of an unquoted
+    /// value). Multi-line values are not supported, so quoted values must end on the same line
+    /// (use `\n` escapes instead). Variables which are already set are handled according to the
+    /// [`Args::dotenv_overlap`], and variables in the `.env.toml` file take precedence. Mapped
+    /// variables from this file are reported with a [`ConfigSource::PreloadedDotEnv`] source.
+    /// Skipped if the file doesn't exist. `None` by default.
+    #[cfg(feature = "dotenv")]
+    pub env_file_path: Option<&'a Path>,
+    /// The contents of a `.env.toml` format file, used instead of reading the
+    /// [`Args::dotenv_path`] (e.g. for a process which receives it from a pipe or an embedded
+    /// asset store). `None` by default.
+    #[cfg(feature = "dotenv")]
+    pub dotenv_inline: Option<InlineSource<'a>>,
+    /// Path to a `.env.toml` format file with overrides which take precedence over the
+    /// [`Args::dotenv_path`] (e.g. [`DEFAULT_DOTENV_LOCAL_PATH`], for per-developer secrets which
+    /// are not committed to version control). Environment variables which it sets overwrite those
+    /// set by the [`Args::dotenv_path`] file regardless of the [`Args::dotenv_overlap`], and its
+    /// configuration is reported with a separate [`ConfigSource::DotEnv`] source. Skipped if the
+    /// file doesn't exist. `None` by default.
+    #[cfg(feature = "dotenv")]
+    pub dotenv_local_path: Option<&'a Path>,
+    /// Path to a config file to load. With the `archive` feature, this may refer to a file
+    /// within a `.zip`, `.tar` or `.tar.gz` archive (e.g. `bundle.zip!config/config.toml`). With
+    /// the `json5` feature, files with the `.json5` extension are parsed as JSON5 (`null` values
+    /// are not supported), and with the `ini` feature, files with the `.ini` or `.cfg` extension
+    /// are parsed as INI (sections become tables).
+    pub config_path: Option<&'a Path>,
+    /// Config files (e.g. a base file and a site-specific override file) which are merged in
+    /// order from lowest to highest precedence, below the [`Args::config_path`] (and other
+    /// sources). Files which don't exist are skipped. Empty by default.
+    pub config_paths: Vec<&'a Path>,
+    /// A directory of drop-in config files (e.g. `/etc/my-app/conf.d`), whose `*.toml` files (and
+    /// `*.json5`, `*.ini` and `*.cfg` files with the `json5` and `ini` features) are merged in
+    /// lexicographic order of their names, taking precedence over the [`Args::config_path`].
+    /// Skipped if the directory doesn't exist. `None` by default.
+    pub config_dir: Option<&'a Path>,
+    /// The contents of a config file (e.g. received over a socket), which takes precedence over
+    /// the [`Args::config_path`]. `None` by default.
+    pub config_inline: Option<InlineSource<'a>>,
+    /// Where to load the config file from, which takes precedence over (and replaces) both the
+    /// [`Args::config_path`] and the [`Args::config_inline`] when it is set. `None` by default.
+    pub config_location: Option<ConfigLocation<'a>>,
+    /// How a [`ConfigLocation::Url`] is fetched. See [`RemoteArgs`] for the defaults.
+    #[cfg(feature = "remote")]
+    pub remote: RemoteArgs,
+    /// An absolute directory which relative paths (the [`Args::dotenv_path`], the
+    /// [`Args::config_path`], a file referred to by the [`Args::config_variable_name`] variable,
+    /// and files referred to by [`Args::file_suffix`] variables) are resolved against, instead of the current working directory. Set this to the current
+    /// directory captured at startup (using [`std::env::current_dir()`]) to load the same files
+    /// after the application has changed directory (e.g. when daemonizing). The resolved paths are
+    /// recorded in [`ConfigSource`] values. Initialization returns an error if this is a relative
+    /// path, which would itself depend on the current directory. `None` by default.
+    pub base_dir: Option<&'a Path>,
+    /// A TOML document containing default values for the configuration (e.g. using
+    /// `include_str!("defaults.toml")`), which has the lowest precedence of all sources. `None`
+    /// by default.
+    pub defaults: Option<&'a str>,
+    /// Name of the environment variable to use that stores the config. The value is [`DEFAULT_CONFIG_VARIABLE_NAME`] by default.
+    pub config_variable_name: &'a str,
+    /// Path to the table within the config stored in the `config_variable_name` environment
+    /// variable (or the file it refers to) which contains the configuration. This allows a shared
+    /// config containing the configuration for multiple applications (e.g. under `[my_app]`) to be
+    /// used. The value is the root of the config by default.
+    pub config_variable_key_path: TomlKeyPath,
+    /// What to do when the config stored in the `config_variable_name` environment variable does
+    /// not contain the [`Args::config_variable_key_path`] (e.g. because of a typo).
+    /// [`MissingKeyPath::Silent`] by default.
+    pub missing_key_path: MissingKeyPath,
+    /// What method of logging to use (if any). [`Logging::None`] by default.
+    pub logging: Logging,
+    /// Map the specified environment variables into config keys.
+    pub map_env: HashMap<&'a str, TomlKeyPath>,
+    /// See [`AutoMapEnvArgs`].
+    #[cfg(feature = "auto-map-env")]
+    pub auto_map_env: Option<AutoMapEnvArgs<'a>>,
+    /// How numbers are inferred from the values of environment variables.
+    /// [`NumberInference::PreferFloat`] by default.
+    pub number_inference: NumberInference,
+    /// How mapped environment variables which are set to an empty string are handled.
+    /// [`EmptyValues::Keep`] by default.
+    pub empty_values: EmptyValues,
+    /// How the values of environment variables containing integers which are out of range for an
+    /// `i64` are parsed. [`LargeIntegers::Infer`] by default.
+    pub large_integers: LargeIntegers,
+    /// If set, a mapped environment variable with this value (e.g. [`DEFAULT_UNSET_SENTINEL`])
+    /// removes the key it is mapped to, including any value for the key from the config file and
+    /// [`Args::defaults`], in the same way as [`EmptyValues::Unset`]. `None` by default.
+    pub unset_sentinel: Option<&'a str>,
+    /// A custom parser for the values of mapped environment variables, called with the value and
+    /// the key it is mapped to before [`Args::value_hints`], [`Args::schema`] and type inference
+    /// are used. If it returns `None`, the value is parsed as usual. `None` by default.
+    pub value_parser: Option<Box<ValueParser>>,
+    /// Decode the values of mapped environment variables with the [`BASE64_PREFIX`] (e.g.
+    /// `base64:aGVsbG8=`) as base64, inserting the decoded value as a string. This is useful for
+    /// multiline secrets. `false` by default.
+    pub decode_base64: bool,
+    /// If set, a mapped environment variable which is not set can instead be read from the file
+    /// referred to by an environment variable with the same name and this suffix (the
+    /// Docker/Kubernetes secrets convention), e.g. with [`DEFAULT_FILE_SUFFIX`]
+    /// `DB_PASSWORD_FILE=/run/secrets/db_password` for `DB_PASSWORD`. A trailing newline in the
+    /// file is removed. This applies to both [`Args::map_env`] and automatically mapped
+    /// variables. An automatically mapped variable with the suffix is only read as a file for the
+    /// key without the suffix if that key is a value (not a table) in the [`Args::schema`] (e.g.
+    /// `MY_APP__LOG_FILE` only sets `log` if the schema contains `log = ""`), and always sets the
+    /// key ending with the suffix (`log_file`). `None` by default.
+    pub file_suffix: Option<&'a str>,
+    /// When inferring the type of values from environment variables, parse values which are
+    /// valid TOML arrays (e.g. `[1, 2, 3]`) or inline tables (e.g. `{ host = "x", port = 1 }`)
+    /// as such, allowing a single variable to set a whole table. `false` by default.
+    pub inline_toml: bool,
+    /// When inferring the type of values from environment variables, parse values which look like
+    /// datetimes (e.g. `2024-01-01` or `12:00:00`) as [`toml::value::Datetime`]. If `false`, they
+    /// are kept as strings unless a [`ValueHint::Datetime`] is specified for the variable (or by
+    /// the [`Args::schema`]). `true` by default.
+    pub infer_datetimes: bool,
+    /// When inferring the type of values from environment variables, parse values which are JSON
+    /// objects or arrays (e.g. `{"host": "x", "port": 1}`) into the equivalent TOML tables or
+    /// arrays. `null` values in objects are omitted, and values which are not valid JSON or
+    /// cannot be represented in TOML (e.g. arrays containing `null`) are parsed as usual. `false`
+    /// by default.
+    #[cfg(feature = "json")]
+    pub json: bool,
+    /// A skeleton of the configuration, used to determine the type that values from environment
+    /// variables should be parsed as (instead of inferring it). For example if the value at
+    /// `server.port` in the schema is an integer, then the value of an environment variable
+    /// mapped to `server.port` will be parsed as an integer, or if it is a string the value will
+    /// be kept as a string. Keys which are not present in the schema (or are tables or arrays)
+    /// have their type inferred. `None` by default.
+    ///
+    /// A schema can be created from the `Default` implementation of the configuration using
+    /// `toml::Value::try_from(Config::default())`.
+    ///
+    /// If a key is an array of tables in the schema (and there is no [`ValueHint`] for the
+    /// variable), the value is parsed as a TOML array literal (e.g.
+    /// `MY_APP__ENDPOINTS='[{ url = "a" }, { url = "b" }]'`), or a JSON array with the `json`
+    /// feature, regardless of [`Args::inline_toml`]. Each element is checked against the first
+    /// element of the array in the schema (so the schema needs at least one element): it must be
+    /// a table, and its values for keys which are in the schema must have the same type.
+    pub schema: Option<Value>,
+    /// Hints for the type that the values of environment variables (by name) should be parsed
+    /// as, which take precedence over [`Args::schema`] and type inference. Empty by default.
+    pub value_hints: HashMap<&'a str, ValueHint>,
+    /// Keys containing sensitive values, which are replaced with [`REDACTED`] in log output,
+    /// error messages, and [`InitializationReport::describe()`]. A `*` element matches any key
+    /// (e.g. `*.password`), and the values of all keys within a redacted table are redacted.
+    /// Empty by default.
+    pub redact: Vec<TomlKeyPath>,
+    /// How much of the parsed configuration is logged, which can be limited for very large
+    /// configurations. [`ConfigLog::Full`] by default.
+    #[cfg(feature = "pretty-logging")]
+    pub config_log: ConfigLog,
+    /// Policies restricting which kinds of source may set particular keys, checked before the
+    /// sources are merged. Empty by default.
+    pub key_policies: Vec<KeyPolicy>,
+    /// Kinds of source which are optional. If reading or parsing an optional source fails, it is
+    /// skipped with a warning (see [`InitializationReport::warnings`]) instead of failing
+    /// initialization. For [`SourceKind::Environment`] this applies to the config stored in the
+    /// [`Args::config_variable_name`] environment variable, but not to mapped environment
+    /// variables. Empty by default.
+    pub optional_sources: Vec<SourceKind>,
+    /// Paths of individual files which are optional, in addition to the
+    /// [`Args::optional_sources`], e.g. an overlay among the [`Args::config_paths`] while the
+    /// others are required. Applies to the [`Args::dotenv_path`], [`Args::dotenv_local_path`] and
+    /// the config files, with relative paths resolved against the [`Args::base_dir`]. Custom
+    /// sources are made optional using [`Source::optional`]. Empty by default.
+    pub optional_paths: Vec<&'a Path>,
+    /// Custom sources of configuration, merged with the other sources according to their
+    /// [`Source::precedence`]. Empty by default.
+    pub sources: Vec<Box<dyn Source>>,
+    /// Initialize [`env_logger`] using the `log.level` and `log.format` keys of the merged
+    /// configuration before it is deserialized, see [`init_logger()`]. `false` by default.
+    #[cfg(feature = "log-init")]
+    pub init_logger: bool,
+}
+
+impl Default for Args<'static> {
+    fn default() -> Self {
+        Self {
+            #[cfg(feature = "dotenv")]
+            dotenv_path: Path::new(DEFAULT_DOTENV_PATH),
+            #[cfg(feature = "dotenv")]
+            dotenv_line_endings: LineEndings::default(),
+            #[cfg(feature = "dotenv")]
+            dotenv_non_finite_floats: NonFiniteFloats::default(),
+            #[cfg(feature = "dotenv")]
+            dotenv_overlap: DotEnvOverlap::default(),
+            preloaded_dotenv_path: None,
+            #[cfg(feature = "dotenv")]
+            env_file_path: None,
+            #[cfg(feature = "dotenv")]
+            dotenv_inline: None,
+            #[cfg(feature = "dotenv")]
+            dotenv_local_path: None,
+            config_path: None,
+            config_paths: Vec::new(),
+            config_dir: None,
+            config_inline: None,
+            config_location: None,
+            #[cfg(feature = "remote")]
+            remote: RemoteArgs::default(),
+            base_dir: None,
+            defaults: None,
+            config_variable_name: DEFAULT_CONFIG_VARIABLE_NAME,
+            config_variable_key_path: TomlKeyPath::default(),
+            missing_key_path: MissingKeyPath::Silent,
+            logging: Logging::default(),
+            map_env: HashMap::default(),
+            #[cfg(feature = "auto-map-env")]
+            auto_map_env: None,
+            number_inference: NumberInference::PreferFloat,
+            empty_values: EmptyValues::Keep,
+            large_integers: LargeIntegers::Infer,
+            unset_sentinel: None,
+            value_parser: None,
+            decode_base64: false,
+            file_suffix: None,
+            inline_toml: false,
+            infer_datetimes: true,
+            #[cfg(feature = "json")]
+            json: false,
+            schema: None,
+            value_hints: HashMap::default(),
+            redact: Vec::new(),
+            #[cfg(feature = "pretty-logging")]
+            config_log: ConfigLog::default(),
+            key_policies: Vec::new(),
+            optional_sources: Vec::new(),
+            optional_paths: Vec::new(),
+            sources: Vec::new(),
+            #[cfg(feature = "log-init")]
+            init_logger: false,
+        }
+    }
+}
+
+/// Owned versions of the fields of [`Args`] which borrow paths and strings, for building args
+/// from runtime values (e.g. command line arguments or computed prefixes) and storing them in a
+/// struct. Convert into [`Args`] using [`ArgsOwned::args()`] (or [`From`]).
+///
+/// Every field of [`Args`] is included except for the following, which are set to their defaults
+/// by [`ArgsOwned::args()`] and must be re-applied using struct update syntax:
+///
+/// + [`Args::config_location`], because it is usually parsed from a borrowed command line
+///   argument using [`ConfigLocation::parse()`].
+/// + [`Args::value_parser`] and [`Args::sources`], because they hold trait objects which can't be
+///   cloned.
+///
+/// # Example
+///
+/// ```rust
+/// use toml_env::{Args, ArgsOwned, Logging};
+///
+/// let app_name = String::from("my-app");
+/// let owned = ArgsOwned {
+///     config_variable_name: app_name.to_uppercase().replace('-', "_"),
+///     config_path: Some(format!("/etc/{app_name}/config.toml").into()),
+///     logging: Logging::StdOut,
+///     ..ArgsOwned::default()
+/// };
+/// let args = Args {
+///     value_parser: None,
+///     ..owned.args()
+/// };
+/// assert_eq!(args.config_variable_name, "MY_APP");
+/// assert!(matches!(args.logging, Logging::StdOut));
+/// ```
+#[derive(Debug, Clone)]
+pub struct ArgsOwned {
+    /// See [`Args::dotenv_path`].
+    #[cfg(feature = "dotenv")]
+    pub dotenv_path: PathBuf,
+    /// See [`Args::dotenv_local_path`].
+    #[cfg(feature = "dotenv")]
+    pub dotenv_local_path: Option<PathBuf>,
+    /// See [`Args::dotenv_inline`].
+    #[cfg(feature = "dotenv")]
+    pub dotenv_inline: Option<InlineSourceOwned>,
+    /// See [`Args::dotenv_line_endings`].
+    #[cfg(feature = "dotenv")]
+    pub dotenv_line_endings: LineEndings,
+    /// See [`Args::dotenv_non_finite_floats`].
+    #[cfg(feature = "dotenv")]
+    pub dotenv_non_finite_floats: NonFiniteFloats,
+    /// See [`Args::dotenv_overlap`].
+    #[cfg(feature = "dotenv")]
+    pub dotenv_overlap: DotEnvOverlap,
+    /// See [`Args::preloaded_dotenv_path`].
+    pub preloaded_dotenv_path: Option<PathBuf>,
+    /// See [`Args::env_file_path`].
+    #[cfg(feature = "dotenv")]
+    pub env_file_path: Option<PathBuf>,
+    /// See [`Args::config_path`].
+    pub config_path: Option<PathBuf>,
+    /// See [`Args::config_paths`].
+    pub config_paths: Vec<PathBuf>,
+    /// See [`Args::config_dir`].
+    pub config_dir: Option<PathBuf>,
+    /// See [`Args::config_inline`].
+    pub config_inline: Option<InlineSourceOwned>,
+    /// See [`Args::optional_paths`].
+    pub optional_paths: Vec<PathBuf>,
+    /// See [`Args::base_dir`].
+    pub base_dir: Option<PathBuf>,
+    /// See [`Args::defaults`].
+    pub defaults: Option<String>,
+    /// See [`Args::config_variable_name`].
+    pub config_variable_name: String,
+    /// See [`Args::config_variable_key_path`].
+    pub config_variable_key_path: TomlKeyPath,
+    /// See [`Args::missing_key_path`].
+    pub missing_key_path: MissingKeyPath,
+    /// See [`Args::logging`].
+    pub logging: Logging,
+    /// See [`Args::map_env`].
+    pub map_env: HashMap<String, TomlKeyPath>,
+    /// See [`Args::auto_map_env`].
+    #[cfg(feature = "auto-map-env")]
+    pub auto_map_env: Option<AutoMapEnvArgsOwned>,
+    /// See [`Args::number_inference`].
+    pub number_inference: NumberInference,
+    /// See [`Args::empty_values`].
+    pub empty_values: EmptyValues,
+    /// See [`Args::large_integers`].
+    pub large_integers: LargeIntegers,
+    /// See [`Args::unset_sentinel`].
+    pub unset_sentinel: Option<String>,
+    /// See [`Args::decode_base64`].
+    pub decode_base64: bool,
+    /// See [`Args::file_suffix`].
+    pub file_suffix: Option<String>,
+    /// See [`Args::inline_toml`].
+    pub inline_toml: bool,
+    /// See [`Args::infer_datetimes`].
+    pub infer_datetimes: bool,
+    /// See [`Args::json`].
+    #[cfg(feature = "json")]
+    pub json: bool,
+    /// See [`Args::schema`].
+    pub schema: Option<Value>,
+    /// See [`Args::value_hints`].
+    pub value_hints: HashMap<String, ValueHint>,
+    /// See [`Args::redact`].
+    pub redact: Vec<TomlKeyPath>,
+    /// See [`Args::config_log`].
+    #[cfg(feature = "pretty-logging")]
+    pub config_log: ConfigLog,
+    /// See [`Args::key_policies`].
+    pub key_policies: Vec<KeyPolicy>,
+    /// See [`Args::optional_sources`].
+    pub optional_sources: Vec<SourceKind>,
+    /// See [`Args::remote`].
+    #[cfg(feature = "remote")]
+    pub remote: RemoteArgs,
+    /// See [`Args::init_logger`].
+    #[cfg(feature = "log-init")]
+    pub init_logger: bool,
+}
+
+impl Default for ArgsOwned {
+    fn default() -> Self {
+        Self {
+            #[cfg(feature = "dotenv")]
+            dotenv_path: PathBuf::from(DEFAULT_DOTENV_PATH),
+            #[cfg(feature = "dotenv")]
+            dotenv_local_path: None,
+            #[cfg(feature = "dotenv")]
+            dotenv_inline: None,
+            #[cfg(feature = "dotenv")]
+            dotenv_line_endings: LineEndings::default(),
+            #[cfg(feature = "dotenv")]
+            dotenv_non_finite_floats: NonFiniteFloats::default(),
+            #[cfg(feature = "dotenv")]
+            dotenv_overlap: DotEnvOverlap::default(),
+            preloaded_dotenv_path: None,
+            #[cfg(feature = "dotenv")]
+            env_file_path: None,
+            config_path: None,
+            config_paths: Vec::new(),
+            config_dir: None,
+            config_inline: None,
+            optional_paths: Vec::new(),
+            base_dir: None,
+            defaults: None,
+            config_variable_name: DEFAULT_CONFIG_VARIABLE_NAME.to_owned(),
+            config_variable_key_path: TomlKeyPath::default(),
+            missing_key_path: MissingKeyPath::Silent,
+            logging: Logging::default(),
+            map_env: HashMap::new(),
+            #[cfg(feature = "auto-map-env")]
+            auto_map_env: None,
+            number_inference: NumberInference::PreferFloat,
+            empty_values: EmptyValues::Keep,
+            large_integers: LargeIntegers::Infer,
+            unset_sentinel: None,
+            decode_base64: false,
+            file_suffix: None,
+            inline_toml: false,
+            infer_datetimes: true,
+            #[cfg(feature = "json")]
+            json: false,
+            schema: None,
+            value_hints: HashMap::new(),
+            redact: Vec::new(),
+            #[cfg(feature = "pretty-logging")]
+            config_log: ConfigLog::default(),
+            key_policies: Vec::new(),
+            optional_sources: Vec::new(),
+            #[cfg(feature = "remote")]
+            remote: RemoteArgs::default(),
+            #[cfg(feature = "log-init")]
+            init_logger: false,
+        }
+    }
+}
+
+impl ArgsOwned {
+    /// Borrow these fields as [`Args`], with [`Args::config_location`], [`Args::value_parser`]
+    /// and [`Args::sources`] set to their defaults.
+    pub fn args(&self) -> Args<'_> {
+        Args {
+            #[cfg(feature = "dotenv")]
+            dotenv_path: &self.dotenv_path,
+            #[cfg(feature = "dotenv")]
+            dotenv_local_path: self.dotenv_local_path.as_deref(),
+            #[cfg(feature = "dotenv")]
+            dotenv_inline: self.dotenv_inline.as_ref().map(InlineSourceOwned::source),
+            #[cfg(feature = "dotenv")]
+            dotenv_line_endings: self.dotenv_line_endings,
+            #[cfg(feature = "dotenv")]
+            dotenv_non_finite_floats: self.dotenv_non_finite_floats,
+            #[cfg(feature = "dotenv")]
+            dotenv_overlap: self.dotenv_overlap,
+            preloaded_dotenv_path: self.preloaded_dotenv_path.as_deref(),
+            #[cfg(feature = "dotenv")]
+            env_file_path: self.env_file_path.as_deref(),
+            config_path: self.config_path.as_deref(),
+            config_paths: self.config_paths.iter().map(PathBuf::as_path).collect(),
+            config_dir: self.config_dir.as_deref(),
+            config_inline: self.config_inline.as_ref().map(InlineSourceOwned::source),
+            optional_paths: self.optional_paths.iter().map(PathBuf::as_path).collect(),
+            base_dir: self.base_dir.as_deref(),
+            defaults: self.defaults.as_deref(),
+            config_variable_name: &self.config_variable_name,
+            config_variable_key_path: self.config_variable_key_path.clone(),
+            missing_key_path: self.missing_key_path,
+            logging: self.logging,
+            map_env: self
+                .map_env
+                .iter()
+                .map(|(name, key)| (name.as_str(), key.clone()))
+                .collect(),
+            #[cfg(feature = "auto-map-env")]
+            auto_map_env: self
+                .auto_map_env
+                .as_ref()
+                .map(AutoMapEnvArgsOwned::auto_map_env_args),
+            number_inference: self.number_inference,
+            empty_values: self.empty_values,
+            large_integers: self.large_integers,
+            unset_sentinel: self.unset_sentinel.as_deref(),
+            decode_base64: self.decode_base64,
+            file_suffix: self.file_suffix.as_deref(),
+            inline_toml: self.inline_toml,
+            infer_datetimes: self.infer_datetimes,
+            #[cfg(feature = "json")]
+            json: self.json,
+            schema: self.schema.clone(),
+            value_hints: self
+                .value_hints
+                .iter()
+                .map(|(name, hint)| (name.as_str(), *hint))
+                .collect(),
+            redact: self.redact.clone(),
+            #[cfg(feature = "pretty-logging")]
+            config_log: self.config_log,
+            key_policies: self.key_policies.clone(),
+            optional_sources: self.optional_sources.clone(),
+            #[cfg(feature = "remote")]
+            remote: self.remote.clone(),
+            #[cfg(feature = "log-init")]
+            init_logger: self.init_logger,
+            ..Args::default()
+        }
+    }
+}
+
+impl<'a> From<&'a ArgsOwned> for Args<'a> {
+    fn from(owned: &'a ArgsOwned) -> Self {
+        owned.args()
+    }
+}
+
+/// An owned version of [`InlineSource`], see [`ArgsOwned`].
+#[derive(Debug, Clone, Default)]
+pub struct InlineSourceOwned {
+    /// See [`InlineSource::label`].
+    pub label: String,
+    /// See [`InlineSource::content`].
+    pub content: String,
+}
+
+impl InlineSourceOwned {
+    /// Borrow this as an [`InlineSource`].
+    pub fn source(&self) -> InlineSource<'_> {
+        InlineSource {
+            label: &self.label,
+            content: &self.content,
+        }
+    }
+}
+
+/// Owned versions of the fields of [`AutoMapEnvArgs`], see [`ArgsOwned`]. The
+/// [`AutoMapEnvArgs::transform`] is the default, because it cannot be cloned.
+#[cfg(feature = "auto-map-env")]
+#[derive(Debug, Clone)]
+pub struct AutoMapEnvArgsOwned {
+    /// See [`AutoMapEnvArgs::divider`].
+    pub divider: String,
+    /// See [`AutoMapEnvArgs::prefix`].
+    pub prefix: Option<String>,
+    /// See [`AutoMapEnvArgs::infer_types`].
+    pub infer_types: bool,
+    /// See [`AutoMapEnvArgs::list_delimiter`].
+    pub list_delimiter: Option<char>,
+    /// See [`AutoMapEnvArgs::number_inference`].
+    pub number_inference: Option<NumberInference>,
+}
+
+#[cfg(feature = "auto-map-env")]
+impl Default for AutoMapEnvArgsOwned {
+    fn default() -> Self {
+        let defaults = AutoMapEnvArgs::default();
+        Self {
+            divider: defaults.divider.to_owned(),
+            prefix: None,
+            infer_types: defaults.infer_types,
+            list_delimiter: defaults.list_delimiter,
+            number_inference: defaults.number_inference,
+        }
+    }
+}
+
+#[cfg(feature = "auto-map-env")]
+impl AutoMapEnvArgsOwned {
+    /// Borrow these fields as [`AutoMapEnvArgs`], with the default transform.
+    pub fn auto_map_env_args(&self) -> AutoMapEnvArgs<'_> {
+        AutoMapEnvArgs {
+            divider: &self.divider,
+            prefix: self.prefix.as_deref(),
+            infer_types: self.infer_types,
+            list_delimiter: self.list_delimiter,
+            number_inference: self.number_inference,
+            ..AutoMapEnvArgs::default()
+        }
+    }
+}
+
+/// Create a [`Builder`] for loading configuration, starting with the default [`Args`].
+///
+/// # Example
+///
+/// ```rust
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Config {
+///     value: String,
+/// }
+///
+/// std::env::set_var("BUILDER_EXAMPLE_VALUE", "Hello World");
+/// let config_path: Option<&str> = None; // e.g. decided at runtime.
+///
+/// let config: Config = toml_env::builder()
+///     .env("BUILDER_EXAMPLE_VALUE", "value".parse().unwrap())
+///     .file_opt(config_path)
+///     .load()
+///     .unwrap()
+///     .unwrap();
+/// assert_eq!(config.value, "Hello World");
+/// ```
+pub fn builder() -> Builder<'static> {
+    Builder::default()
+}
+
+/// A builder for [`Args`], to load configuration without constructing [`Args`] using a struct
+/// literal, see [`builder()`]. Options which don't have a method can be set using
+/// [`Builder::configure()`].
+#[derive(Default)]
+pub struct Builder<'a> {
+    args: Args<'a>,
+}
+
+impl<'a> From<Args<'a>> for Builder<'a> {
+    fn from(args: Args<'a>) -> Self {
+        Self { args }
+    }
+}
+
+impl<'a> Builder<'a> {
+    /// Set the [`Args::dotenv_path`].
+    #[cfg(feature = "dotenv")]
+    pub fn dotenv<P: AsRef<Path> + ?Sized>(mut self, path: &'a P) -> Self {
+        self.args.dotenv_path = path.as_ref();
+        self
+    }
+
+    /// Set the [`Args::config_path`].
+    pub fn file<P: AsRef<Path> + ?Sized>(self, path: &'a P) -> Self {
+        self.file_opt(Some(path))
+    }
+
+    /// Set the [`Args::config_path`] if `path` is `Some`, otherwise clear it.
+    pub fn file_opt<P: AsRef<Path> + ?Sized>(mut self, path: Option<&'a P>) -> Self {
+        self.args.config_path = path.map(AsRef::as_ref);
+        self
+    }
+
+    /// Add a file to the [`Args::config_paths`], which takes precedence over the files added
+    /// before it.
+    pub fn layered_file<P: AsRef<Path> + ?Sized>(mut self, path: &'a P) -> Self {
+        self.args.config_paths.push(path.as_ref());
+        self
+    }
+
+    /// Set the [`Args::config_dir`].
+    pub fn dir<P: AsRef<Path> + ?Sized>(mut self, path: &'a P) -> Self {
+        self.args.config_dir = Some(path.as_ref());
+        self
+    }
+
+    /// Set the [`Args::config_location`], e.g. parsed from a command line argument using
+    /// [`ConfigLocation::parse()`].
+    pub fn location(mut self, location: ConfigLocation<'a>) -> Self {
+        self.args.config_location = Some(location);
+        self
+    }
+
+    /// Set the [`Args::defaults`].
+    pub fn defaults(mut self, defaults: &'a str) -> Self {
+        self.args.defaults = Some(defaults);
+        self
+    }
+
+    /// Use `name` as the [`Args::config_variable_name`] and the prefix for automatically mapped
+    /// environment variables (see [`Args::auto_map_env`]), using the default [`Conventions`]
+    /// otherwise.
+    #[cfg(feature = "auto-map-env")]
+    pub fn auto_env(mut self, name: &'a str) -> Self {
+        let conventions = Conventions {
+            config_variable_name: name,
+            ..Conventions::default()
+        };
+        self.args.config_variable_name = name;
+        self.args.auto_map_env = Some(conventions.auto_map_env_args());
+        self
+    }
+
+    /// Map the environment variable `name` into the config `key` (see [`Args::map_env`]).
+    pub fn env(mut self, name: &'a str, key: TomlKeyPath) -> Self {
+        self.args.map_env.insert(name, key);
+        self
+    }
+
+    /// Set the [`Args::logging`].
+    pub fn logging(mut self, logging: Logging) -> Self {
+        self.args.logging = logging;
+        self
+    }
+
+    /// Set the [`Args::schema`].
+    pub fn schema(mut self, schema: Value) -> Self {
+        self.args.schema = Some(schema);
+        self
+    }
+
+    /// Add a key to [`Args::redact`].
+    pub fn redact(mut self, key: TomlKeyPath) -> Self {
+        self.args.redact.push(key);
+        self
+    }
+
+    /// Modify any of the [`Args`] directly, for options which don't have a method.
+    pub fn configure(mut self, configure: impl FnOnce(&mut Args<'a>)) -> Self {
+        configure(&mut self.args);
+        self
+    }
+
+    /// The [`Args`] which have been built.
+    pub fn args(self) -> Args<'a> {
+        self.args
+    }
+
+    /// Load the configuration, see [`initialize()`].
+    pub fn load<C>(self) -> Result<Option<C>>
+    where
+        C: DeserializeOwned + Serialize,
+    {
+        initialize(self.args)
+    }
+
+    /// Load the configuration with a report of its sources, see [`initialize_with_report()`].
+    pub fn load_with_report<C>(self) -> Result<(Option<C>, InitializationReport)>
+    where
+        C: DeserializeOwned + Serialize,
+    {
+        initialize_with_report(self.args)
+    }
+}
+
+fn log_info(logging: Logging, args: std::fmt::Arguments<'_>) {
+    match logging {
+        Logging::None => {}
+        Logging::StdOut => println!("INFO {}: {}", module_path!(), std::fmt::format(args)),
+        #[cfg(feature = "log")]
+        Logging::Log => log::info!("{}", std::fmt::format(args)),
+    }
+}
+
+fn log_warn(logging: Logging, args: std::fmt::Arguments<'_>) {
+    match logging {
+        Logging::None => {}
+        Logging::StdOut => println!("WARN {}: {}", module_path!(), std::fmt::format(args)),
+        #[cfg(feature = "log")]
+        Logging::Log => log::warn!("{}", std::fmt::format(args)),
+    }
+}
+
+/// Terminal escape code used to highlight values in log messages.
+#[cfg(feature = "pretty-logging")]
+const HIGHLIGHT: &str = "\x1b[34m";
+#[cfg(not(feature = "pretty-logging"))]
+const HIGHLIGHT: &str = "";
+
+/// Terminal escape code used to reset the highlighting of values in log messages.
+#[cfg(feature = "pretty-logging")]
+const RESET: &str = "\x1b[0m";
+#[cfg(not(feature = "pretty-logging"))]
+const RESET: &str = "";
+
+/// Reads and parses the .env.toml file at `dotenv_path` (or the `inline_content`, in which case
+/// `dotenv_path` is its label, see [`Args::dotenv_inline`]). Returns `Some(C)` if the file
+/// contains a table with the name matching the [`Args::config_variable_name`]. The other top
+/// level entries in the file are inserted into `variables`, and set as environment variables
+/// using the `env_guard`. Variables which were set by a previous file (see
+/// [`Args::dotenv_local_path`]) are in `set_variables`, and are overwritten regardless of the
+/// [`Args::dotenv_overlap`].
+#[cfg(feature = "dotenv")]
+fn initialize_dotenv_toml<C: DeserializeOwned + Serialize>(
+    args: &Args<'_>,
+    dotenv_path: &Path,
+    inline_content: Option<&str>,
+    variables: &mut toml::Table,
+    set_variables: &mut BTreeSet<String>,
+    env_guard: &mut EnvGuard,
+) -> InnerResult<Option<C>> {
+    let config_variable_name = args.config_variable_name;
+    let line_endings = args.dotenv_line_endings;
+    let non_finite_floats = args.dotenv_non_finite_floats;
+    let overlap = args.dotenv_overlap;
+    let logging = args.logging;
+
+    let path = dotenv_path;
+    let env_str = match inline_content {
+        Some(content) => {
+            log_info(
+                logging,
+                format_args!(
+                    "Loading config and environment variables from inline dotenv {path:?}"
+                ),
+            );
+            content.to_owned()
+        }
+        None => {
+            if !path.exists() {
+                return Ok(None);
+            }
+            log_info(
+                logging,
+                format_args!("Loading config and environment variables from dotenv {path:?}"),
+            );
+            std::fs::read_to_string(path).map_err(|error| InnerError::ErrorReadingFile {
+                path: path.to_owned(),
+                error,
+            })?
+        }
+    };
+    let env: Value =
+        toml::from_str(&env_str).map_err(|error| InnerError::ErrorParsingTomlFile {
+            path: path.to_owned(),
+            error: error.into(),
+        })?;
+    let table: toml::value::Table = match env {
+        Value::Table(table) => table,
+        unexpected => {
+            return Err(InnerError::UnexpectedTomlDotEnvFileFormat {
+                path: path.to_owned(),
+                value: unexpected,
+            });
+        }
+    };
+
+    if table.is_empty() {
+        return Ok(None);
+    }
+
+    // The whole file is validated before any variables are set, so that a file which fails
+    // part way through (for example an optional source, see [`Args::optional_sources`]) leaves
+    // the environment untouched.
+    let mut config: Option<C> = None;
+    let mut file_variables = toml::Table::new();
+    let mut to_set: Vec<(String, String)> = Vec::new();
+    for (key, value) in table {
+        if !value.is_table() {
+            file_variables.insert(key.clone(), value.clone());
+        }
+        let value_string = match value {
+            Value::Table(_) => {
+                if key.as_str() != config_variable_name {
+                    return Err(InnerError::CannotParseTomlDotEnvFile {
+                        key,
+                        path: path.to_owned(),
+                        advice: format!("Only a table with {config_variable_name} is allowed in a .toml.env format file."),
+                    });
+                }
+                match C::deserialize(value.clone()) {
+                    Ok(c) => config = Some(c),
+                    Err(error) => {
+                        return Err(InnerError::ErrorParsingTomlDotEnvFileKey {
+                            name: key,
+                            path: path.to_owned(),
+                            error: error.into(),
+                        })
+                    }
+                }
+                None
+            }
+            Value::String(value) => Some(line_endings.apply(value)),
+            Value::Integer(value) => Some(value.to_string()),
+            Value::Float(value)
+                if !value.is_finite() && non_finite_floats == NonFiniteFloats::Error =>
+            {
+                return Err(InnerError::NonFiniteDotEnvFloat {
+                    key,
+                    path: path.to_owned(),
+                    value,
+                })
+            }
+            Value::Float(value) => Some(format_float(value)),
+            Value::Boolean(value) => Some(value.to_string()),
+            Value::Datetime(value) => Some(value.to_string()),
+            Value::Array(value) => {
+                return Err(InnerError::CannotParseTomlDotEnvFile {
+                    key,
+                    path: path.to_owned(),
+                    advice: format!("Array values are not supported: {value:?}"),
+                })
+            }
+        };
+
+        if let Some(value_string) = value_string {
+            if std::env::var_os(key.as_str()).is_some() && !set_variables.contains(&key) {
+                match overlap {
+                    DotEnvOverlap::Overwrite => {}
+                    DotEnvOverlap::Keep => continue,
+                    DotEnvOverlap::Error => {
+                        return Err(InnerError::DotEnvVariableAlreadySet {
+                            key,
+                            path: path.to_owned(),
+                        })
+                    }
+                }
+            }
+            to_set.push((key, value_string));
+        }
+    }
+
+    variables.extend(file_variables);
+    let mut set_keys: Vec<String> = Vec::with_capacity(to_set.len());
+    for (key, value_string) in to_set {
+        env_guard.set_var(key.as_str(), value_string);
+        set_variables.insert(key.clone());
+        set_keys.push(key);
+    }
+
+    // Sorted so that the log output is stable regardless of the order of keys in the file.
+    set_keys.sort();
+    let set_keys: String = set_keys.iter().map(|key| format!("\n{key}")).collect();
+
+    log_info(
+        logging,
+        format_args!(
+            "Set environment variables specified in {dotenv_path:?}:{HIGHLIGHT}{set_keys}{RESET}"
+        ),
+    );
+    Ok(config)
+}
+
+/// An error returned by [`insert_toml_value()`].
+#[derive(Debug, thiserror::Error)]
+pub enum InsertTomlValueError {
+    /// A table property in the path was used to index into a value which is not a table.
+    #[error("Table property {property:?} can only be used to index into a table. Cannot index into {value:?}")]
+    TablePropertyCannotIndex {
+        /// The table property in the path.
+        property: String,
+        /// The value which could not be indexed into.
+        value: toml::Value,
+    },
+    /// An array index in the path was used to index into a value which is not an array.
+    #[error(
+        "Array index {index} can only be used to index into an array. Cannot index into {value:?}"
+    )]
+    ArrayIndexCannotIndex {
+        /// The array index in the path.
+        index: usize,
+        /// The value which could not be indexed into.
+        value: toml::Value,
+    },
+    /// A `+` element in the path was used to append to a value which is not an array.
+    #[error("Append `+` can only be used to append to an array. Cannot append to {value:?}")]
+    AppendCannotIndex {
+        /// The value which could not be appended to.
+        value: toml::Value,
+    },
+    /// A `*` element in the path, which can only match existing values.
+    #[error("Wildcard `*` cannot be used to insert into {value:?}")]
+    WildcardCannotInsert {
+        /// The value which could not be inserted into.
+        value: toml::Value,
+    },
+    /// An array index in the path is greater than the length of the array, so the value cannot
+    /// be inserted without leaving a gap.
+    #[error("Array index {index} cannot be greater than the length of {array:?}")]
+    ArrayOutOfBounds {
+        /// The array index in the path.
+        index: usize,
+        /// The array being indexed into.
+        array: Vec<toml::Value>,
+    },
+}
+
+/// Insert a `new_value` into a `value` at the location specified by `path`, creating any required
+/// tables or arrays if they are missing. If the `path` is empty, it will replace the value
+/// entirely. An array index equal to the length of the array appends to it, as does a `+` element
+/// (e.g. `tags.+`) regardless of the length of the array. Appending to a value which is not an
+/// array is an error.
+///
+/// This can be used to apply overrides (e.g. from command line arguments) to a configuration
+/// before deserializing it, such as the result of [`initialize_value()`].
+///
+/// # Example
+///
+/// ```rust
+/// use toml_env::{insert_toml_value, InsertTomlValueError, TomlKeyPath};
+///
+/// let mut value: toml::Value = toml::from_str(r#"
+/// [server]
+/// port = 8080
+/// "#).unwrap();
+///
+/// let port: TomlKeyPath = "server.port".parse().unwrap();
+/// insert_toml_value(&mut value, port, toml::Value::Integer(9090)).unwrap();
+/// assert_eq!(value["server"]["port"].as_integer(), Some(9090));
+///
+/// // Missing tables and arrays are created.
+/// let host: TomlKeyPath = "server.hosts.0".parse().unwrap();
+/// insert_toml_value(&mut value, host, "localhost".into()).unwrap();
+/// assert_eq!(value["server"]["hosts"][0].as_str(), Some("localhost"));
+///
+/// let tag: TomlKeyPath = "server.tags.+".parse().unwrap();
+/// insert_toml_value(&mut value, tag.clone(), "a".into()).unwrap();
+/// insert_toml_value(&mut value, tag, "b".into()).unwrap();
+/// assert_eq!(value["server"]["tags"][1].as_str(), Some("b"));
+///
+/// let invalid: TomlKeyPath = "server.port.0".parse().unwrap();
+/// let error = insert_toml_value(&mut value, invalid, "value".into()).unwrap_err();
+/// assert!(matches!(error, InsertTomlValueError::ArrayIndexCannotIndex { .. }));
+/// ```
+pub fn insert_toml_value(
+    value: &mut toml::Value,
+    path: TomlKeyPath,
+    new_value: Value,
+) -> std::result::Result<(), InsertTomlValueError> {
+    insert_toml_value_at(value, &path.0, new_value)
+}
+
+/// See [`insert_toml_value()`], without taking ownership of the `path`.
+fn insert_toml_value_at(
+    value: &mut toml::Value,
+    path: &[PathElement],
+    new_value: Value,
+) -> std::result::Result<(), InsertTomlValueError> {
+    let (current_key, path) = match path.split_first() {
+        Some(split) => split,
+        None => {
+            *value = new_value;
+            return Ok(());
+        }
+    };
+
+    let current_key = match (current_key, &*value) {
+        (PathElement::Append, Value::Array(array)) => {
+            Cow::Owned(PathElement::ArrayIndex(array.len()))
+        }
+        (key, _) => Cow::Borrowed(key),
+    };
+    let next_key = path.first();
+
+    match (current_key.as_ref(), value) {
+        (PathElement::TableProperty(property), Value::Table(table)) => {
+            let next_value = table.get_mut(&**property);
+            match (next_value, next_key) {
+                (None, None) => {
+                    table.insert(property.to_string(), new_value);
+                    Ok(())
+                }
+                (None, Some(PathElement::Append | PathElement::ArrayIndex(_))) => {
+                    table.insert(
+                        property.to_string(),
+                        toml::Value::Array(Vec::with_capacity(1)),
+                    );
+                    insert_toml_value_at(
+                        table
+                            .get_mut(&**property)
+                            .expect("Expect inserted property to be present"),
+                        path,
+                        new_value,
+                    )
+                }
+                (None, Some(_)) => {
+                    table.insert(
+                        property.to_string(),
+                        toml::Value::Table(toml::Table::with_capacity(1)),
+                    );
+                    insert_toml_value_at(
+                        table
+                            .get_mut(&**property)
+                            .expect("Expect inserted property to be present"),
+                        path,
+                        new_value,
+                    )
+                }
+                (Some(next_value), None) => {
+                    *next_value = new_value;
+                    Ok(())
+                }
+                (Some(next_value), Some(_)) => insert_toml_value_at(next_value, path, new_value),
+            }
+        }
+        (PathElement::TableProperty(property), value) => {
+            Err(InsertTomlValueError::TablePropertyCannotIndex {
+                property: property.to_string(),
+                value: value.clone(),
+            })
+        }
+        (PathElement::ArrayIndex(index), Value::Array(array)) => {
+            let index = *index;
+            if index > array.len() {
+                return Err(InsertTomlValueError::ArrayOutOfBounds {
+                    index,
+                    array: array.clone(),
+                });
+            }
+            let next_value = array.get_mut(index);
+            match (next_value, next_key) {
+                (None, None) => {
+                    array.insert(index, new_value);
+                    Ok(())
+                }
+                (None, Some(PathElement::Append | PathElement::ArrayIndex(_))) => {
+                    array.insert(index, toml::Value::Array(Vec::with_capacity(1)));
+                    insert_toml_value_at(
+                        array
+                            .get_mut(index)
+                            .expect("Expect inserted element to be present"),
+                        path,
+                        new_value,
+                    )
+                }
+                (None, Some(_)) => {
+                    array.insert(index, toml::Value::Table(toml::Table::with_capacity(1)));
+                    insert_toml_value_at(
+                        array
+                            .get_mut(index)
+                            .expect("Expect inserted element to be present"),
+                        path,
+                        new_value,
+                    )
+                }
+                (Some(next_value), None) => {
+                    *next_value = new_value;
+                    Ok(())
+                }
+                (Some(next_value), Some(_)) => insert_toml_value_at(next_value, path, new_value),
+            }
+        }
+        (PathElement::ArrayIndex(index), value) => {
+            Err(InsertTomlValueError::ArrayIndexCannotIndex {
+                index: *index,
+                value: value.clone(),
+            })
+        }
+        (PathElement::Wildcard, value) => Err(InsertTomlValueError::WildcardCannotInsert {
+            value: value.clone(),
+        }),
+        (PathElement::Append, value) => Err(InsertTomlValueError::AppendCannotIndex {
+            value: value.clone(),
+        }),
+    }
+}
+
+/// Scan the current environment for variables matching the prefix specified in `auto_args`, and
+/// add their mappings to `map_env`. Explicit mappings already present in `map_env` take
+/// precedence. The [`Args::config_variable_name`], [`Args::file_suffix`] and [`Args::schema`] of
+/// the `args` are used.
+#[cfg(feature = "auto-map-env")]
+fn auto_map_env(
+    auto_args: &AutoMapEnvArgs<'_>,
+    args: &Args<'_>,
+    map_env: &mut BTreeMap<Cow<'_, str>, TomlKeyPath>,
+    string_variables: &mut BTreeSet<String>,
+    list_delimiters: &mut BTreeMap<String, char>,
+    number_inferences: &mut BTreeMap<String, NumberInference>,
+) {
+    let mut prefix = auto_args
+        .prefix
+        .unwrap_or(args.config_variable_name)
+        .to_owned();
+    prefix.push_str(auto_args.divider);
+    for (key, _) in std::env::vars_os() {
+        // Filter on the raw bytes first to avoid unnecessary work for the (potentially thousands
+        // of) variables in the environment which do not match the prefix.
+        if !key.as_encoded_bytes().starts_with(prefix.as_bytes()) {
+            continue;
+        }
+
+        let key = if let Ok(key) = key.into_string() {
+            key
+        } else {
+            continue;
+        };
+
+        let toml_key = |key: &str| -> Option<TomlKeyPath> {
+            let key_transformed = (auto_args.transform)(&key[prefix.len()..]);
+            key_transformed.replace(auto_args.divider, ".").parse().ok()
+        };
+
+        // Also map the variable without the suffix, which will be read from the file if it is not
+        // set, but only if its key is a value in the schema. Otherwise a variable for a key which
+        // happens to end with the suffix (e.g. `log_file`) would read an unrelated file into
+        // another key (or a table, e.g. `[log]`).
+        let unsuffixed = args
+            .file_suffix
+            .and_then(|file_suffix| key.strip_suffix(file_suffix))
+            .filter(|unsuffixed| unsuffixed.len() > prefix.len())
+            .and_then(|unsuffixed| {
+                let toml_key = toml_key(unsuffixed)?;
+                toml_key
+                    .resolve(args.schema.as_ref()?)
+                    .filter(|value| !value.is_table())?;
+                Some((unsuffixed.to_owned(), toml_key))
+            });
+        let suffixed = toml_key(&key).map(|toml_key| (key, toml_key));
+
+        for (key, toml_key) in suffixed.into_iter().chain(unsuffixed) {
+            if let std::collections::btree_map::Entry::Vacant(entry) = map_env.entry(key.into()) {
+                if !auto_args.infer_types {
+                    string_variables.insert(entry.key().clone().into_owned());
+                }
+                if let Some(delimiter) = auto_args.list_delimiter {
+                    list_delimiters.insert(entry.key().clone().into_owned(), delimiter);
+                }
+                if let Some(number_inference) = auto_args.number_inference {
+                    number_inferences.insert(entry.key().clone().into_owned(), number_inference);
+                }
+                entry.insert(toml_key);
+            }
+        }
+    }
+}
+
+/// How numbers are inferred from the values of environment variables, see
+/// [`Args::number_inference`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum NumberInference {
+    /// Parse numbers as floats (e.g. `1883` as `1883.0`), falling back to integers for values
+    /// which cannot be parsed as floats.
+    #[default]
+    PreferFloat,
+    /// Parse values which look like integers (e.g. `1883`) as integers, and other numbers as
+    /// floats. Integers which are out of range for an `i64` are kept as strings instead of being
+    /// converted to floats.
+    PreferInteger,
+    /// Keep values which look like numbers as strings.
+    StringsOnly,
+}
+
+/// How environment variables containing integers which are out of range for an `i64` (e.g. large
+/// `u64` IDs) are parsed, unless [`NumberInference::StringsOnly`] is used, see
+/// [`Args::large_integers`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LargeIntegers {
+    /// Keep the value as a string when it is parsed as an integer (using
+    /// [`NumberInference::PreferInteger`] or [`ValueHint::Integer`]), otherwise parse it as a
+    /// float (using [`NumberInference::PreferFloat`]).
+    #[default]
+    Infer,
+    /// Keep the value as a string, without losing precision. It can be deserialized into a `u64`
+    /// or `u128` field using [`big_int`].
+    String,
+    /// Parse the value as a float, which may lose precision.
+    Float,
+    /// Return an error.
+    Error,
+}
+
+/// Whether the `value` is an integer (which may be out of range for an `i64`).
+fn is_integer(value: &str) -> bool {
+    let digits = value.strip_prefix(['+', '-']).unwrap_or(value);
+    !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Parse the value of an environment variable into a [`toml::Value`], inferring its type.
+///
+/// Numbers are parsed according to `number_inference`.
+///
+/// If `inline_toml` is `true`, values which are valid TOML arrays (e.g. `[1, 2, 3]`) or inline
+/// tables (e.g. `{ host = "x", port = 1 }`) will be parsed as such.
+fn parse_toml_value(value: String, number_inference: NumberInference, inline_toml: bool) -> Value {
+    if inline_toml && (value.starts_with('[') || value.starts_with('{')) {
+        if let Ok(mut table) = toml::from_str::<toml::Table>(&format!("value = {value}")) {
+            if let Some(value) = table.remove("value") {
+                return value;
+            }
+        }
+    }
+    if let Ok(value) = bool::from_str(&value) {
+        return Value::Boolean(value);
+    }
+    match number_inference {
+        NumberInference::PreferFloat => {
+            if let Ok(value) = f64::from_str(&value) {
+                return Value::Float(value);
+            }
+            if let Ok(value) = i64::from_str(&value) {
+                return Value::Integer(value);
+            }
+        }
+        NumberInference::PreferInteger => {
+            if let Ok(value) = i64::from_str(&value) {
+                return Value::Integer(value);
+            }
+            if is_integer(&value) {
+                return Value::String(value);
+            }
+            if let Ok(value) = f64::from_str(&value) {
+                return Value::Float(value);
+            }
+        }
+        NumberInference::StringsOnly => {
+            if f64::from_str(&value).is_ok() || i64::from_str(&value).is_ok() {
+                return Value::String(value);
+            }
+        }
+    }
+    if let Ok(value) = toml::value::Datetime::from_str(&value) {
+        return Value::Datetime(value);
+    }
+
+    Value::String(value)
+}
+
+/// Parse `value` as JSON if it is a JSON object or array, converting it into the equivalent
+/// [`toml::Value`]. `None` if it is not valid JSON, or cannot be represented in TOML (e.g. an
+/// array containing `null`).
+#[cfg(feature = "json")]
+fn parse_json_value(value: &str) -> Option<Value> {
+    if !(value.starts_with('{') || value.starts_with('[')) {
+        return None;
+    }
+    let json: serde_json::Value = serde_json::from_str(value).ok()?;
+    Value::try_from(json).ok()
+}
+
+/// A hint for the type that the value of an environment variable should be parsed as, see
+/// [`Args::value_hints`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ValueHint {
+    /// Keep the value as a string (e.g. for version numbers like `1.10` or zip codes like
+    /// `01234`).
+    String,
+    /// Parse the value as an integer. Integers which are out of range for an `i64` (e.g. large
+    /// `u64` values) are kept as strings by default, which can be deserialized using [`big_int`]
+    /// (see [`Args::large_integers`]).
+    Integer,
+    /// Parse the value as a float.
+    Float,
+    /// Parse the value as a boolean.
+    Bool,
+    /// Parse the value as a datetime.
+    Datetime,
+    /// Infer the type of the value, even if the key has a different type in [`Args::schema`].
+    Infer,
+    /// Split the value on the specified delimiter into an array (e.g. `a.com,b.com` into
+    /// `["a.com", "b.com"]`). Whitespace around each element is trimmed, and the type of each
+    /// element is inferred, or uses the type of the first element of the array in
+    /// [`Args::schema`]. An empty value is parsed as an empty array.
+    List(char),
+    /// Parse a human readable duration (e.g. `30s`, `5m` or `2h 30m`) using [`humantime`],
+    /// converted into a value using the specified [`DurationFormat`].
+    #[cfg(feature = "humantime")]
+    Duration(DurationFormat),
+    /// Parse a human readable byte size (e.g. `10MB`, `512KiB` or `1.5 GiB`) as an integer number
+    /// of bytes, see [`parse_byte_size()`].
+    #[cfg(feature = "bytesize")]
+    ByteSize,
+}
+
+/// Parse a human readable byte size (e.g. `10MB`, `512KiB` or `1.5 GiB`) into a number of bytes.
+/// Units are case insensitive, with decimal (`KB`, `MB`, `GB`, `TB`, `PB`) and binary (`KiB`,
+/// `MiB`, `GiB`, `TiB`, `PiB`) units supported. A value without a unit (or with the unit `B`) is
+/// a number of bytes. Returns `None` if the value is not a valid byte size, or is out of range.
+///
+/// # Example
+///
+/// ```rust
+/// use toml_env::parse_byte_size;
+///
+/// assert_eq!(parse_byte_size("10MB"), Some(10_000_000));
+/// assert_eq!(parse_byte_size("512KiB"), Some(524_288));
+/// assert_eq!(parse_byte_size("1.5 KiB"), Some(1536));
+/// assert_eq!(parse_byte_size("10 furlongs"), None);
+/// ```
+#[cfg(feature = "bytesize")]
+pub fn parse_byte_size(value: &str) -> Option<i64> {
+    let value = value.trim();
+    let unit_start = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(unit_start);
+    let multiplier: i64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" => 1_000,
+        "ki" | "kib" => 1 << 10,
+        "m" | "mb" => 1_000_000,
+        "mi" | "mib" => 1 << 20,
+        "g" | "gb" => 1_000_000_000,
+        "gi" | "gib" => 1 << 30,
+        "t" | "tb" => 1_000_000_000_000,
+        "ti" | "tib" => 1 << 40,
+        "p" | "pb" => 1_000_000_000_000_000,
+        "pi" | "pib" => 1 << 50,
+        _ => return None,
+    };
+
+    if let Ok(number) = i64::from_str(number) {
+        return number.checked_mul(multiplier);
+    }
+    let bytes = f64::from_str(number).ok()? * multiplier as f64;
+    // `i64::MAX as f64` rounds up to 2^63, which is out of range.
+    (bytes.is_finite() && bytes < i64::MAX as f64).then_some(bytes as i64)
+}
+
+/// How a duration parsed using [`ValueHint::Duration`] is represented in the configuration.
+#[cfg(feature = "humantime")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DurationFormat {
+    /// An integer number of whole seconds.
+    Seconds,
+    /// An integer number of whole milliseconds.
+    Milliseconds,
+    /// A table with `secs` and `nanos` keys, which can be deserialized as a
+    /// [`std::time::Duration`].
+    Structured,
+}
+
+#[cfg(feature = "humantime")]
+impl DurationFormat {
+    /// Convert the `duration` into a value in this format, `None` if it is out of range.
+    fn to_value(self, duration: std::time::Duration) -> Option<Value> {
+        match self {
+            DurationFormat::Seconds => i64::try_from(duration.as_secs()).ok().map(Value::Integer),
+            DurationFormat::Milliseconds => {
+                i64::try_from(duration.as_millis()).ok().map(Value::Integer)
+            }
+            DurationFormat::Structured => {
+                let mut table = toml::Table::new();
+                table.insert(
+                    "secs".to_owned(),
+                    Value::Integer(i64::try_from(duration.as_secs()).ok()?),
+                );
+                table.insert(
+                    "nanos".to_owned(),
+                    Value::Integer(duration.subsec_nanos().into()),
+                );
+                Some(Value::Table(table))
+            }
+        }
+    }
+}
+
+impl ValueHint {
+    /// The hint for the type of a value in a schema, `None` for tables and arrays.
+    fn from_schema(value: &Value) -> Option<Self> {
+        match value {
+            Value::String(_) => Some(Self::String),
+            Value::Integer(_) => Some(Self::Integer),
+            Value::Float(_) => Some(Self::Float),
+            Value::Boolean(_) => Some(Self::Bool),
+            Value::Datetime(_) => Some(Self::Datetime),
+            Value::Array(_) | Value::Table(_) => None,
+        }
+    }
+}
+
+/// A custom parser for the values of environment variables, see [`Args::value_parser`].
+pub type ValueParser = dyn Fn(&str, &TomlKeyPath) -> Option<Value>;
+
+/// Options for parsing the values of environment variables, see [`parse_env_value()`].
+struct EnvValueOptions<'a> {
+    /// See [`Args::number_inference`].
+    number_inference: NumberInference,
+    /// See [`Args::inline_toml`].
+    inline_toml: bool,
+    /// See [`Args::infer_datetimes`].
+    infer_datetimes: bool,
+    /// See [`Args::json`].
+    #[cfg(feature = "json")]
+    json: bool,
+    /// See [`Args::value_parser`].
+    value_parser: Option<&'a ValueParser>,
+    /// See [`Args::decode_base64`].
+    decode_base64: bool,
+    /// See [`Args::redact`].
+    redact: &'a [TomlKeyPath],
+    /// See [`Args::empty_values`].
+    empty_values: EmptyValues,
+    /// See [`Args::large_integers`].
+    large_integers: LargeIntegers,
+    /// See [`Args::unset_sentinel`].
+    unset_sentinel: Option<&'a str>,
+    /// See [`Args::base_dir`].
+    base_dir: Option<&'a Path>,
+    /// See [`Args::file_suffix`].
+    file_suffix: Option<&'a str>,
+    /// See [`Args::schema`].
+    schema: Option<&'a Value>,
+    /// See [`Args::value_hints`].
+    value_hints: &'a HashMap<&'a str, ValueHint>,
+    /// Variables whose values are kept as strings instead of inferring their type, see
+    /// [`AutoMapEnvArgs::infer_types`].
+    string_variables: &'a BTreeSet<String>,
+    /// Delimiters for variables whose values are parsed as arrays if the key is an array in the
+    /// schema, see [`AutoMapEnvArgs::list_delimiter`].
+    list_delimiters: &'a BTreeMap<String, char>,
+    /// Variables with a different [`NumberInference`] to [`EnvValueOptions::number_inference`],
+    /// see [`AutoMapEnvArgs::number_inference`].
+    number_inferences: &'a BTreeMap<String, NumberInference>,
+    /// A snapshot of the environment to read variables from instead of the process environment,
+    /// see [`initialize_env_only()`].
+    environment: Option<&'a HashMap<String, String>>,
+}
+
+impl<'a> EnvValueOptions<'a> {
+    /// Options for the `args`, and the per-variable options from [`auto_map_env()`].
+    fn new(
+        args: &'a Args<'_>,
+        string_variables: &'a BTreeSet<String>,
+        list_delimiters: &'a BTreeMap<String, char>,
+        number_inferences: &'a BTreeMap<String, NumberInference>,
+    ) -> Self {
+        Self {
+            number_inference: args.number_inference,
+            inline_toml: args.inline_toml,
+            infer_datetimes: args.infer_datetimes,
+            value_parser: args.value_parser.as_deref(),
+            decode_base64: args.decode_base64,
+            redact: &args.redact,
+            empty_values: args.empty_values,
+            large_integers: args.large_integers,
+            unset_sentinel: args.unset_sentinel,
+            base_dir: args.base_dir,
+            file_suffix: args.file_suffix,
+            #[cfg(feature = "json")]
+            json: args.json,
+            schema: args.schema.as_ref(),
+            value_hints: &args.value_hints,
+            string_variables,
+            list_delimiters,
+            number_inferences,
+            environment: None,
+        }
+    }
+
+    /// Read the environment variable `name` from the [`EnvValueOptions::environment`] snapshot if
+    /// there is one, otherwise from the process environment.
+    fn var(&self, name: &str) -> std::result::Result<String, std::env::VarError> {
+        match self.environment {
+            Some(environment) => environment
+                .get(name)
+                .cloned()
+                .ok_or(std::env::VarError::NotPresent),
+            None => std::env::var(name),
+        }
+    }
+
+    /// The `value` mapped to `key` as it should appear in error messages, [`REDACTED`] if the
+    /// `key` is redacted.
+    fn displayed_value(&self, value: String, key: &TomlKeyPath) -> String {
+        if is_redacted(key, self.redact) {
+            REDACTED.to_owned()
+        } else {
+            value
+        }
+    }
+}
+
+/// Parse the `value` of the environment variable `name` which is mapped to `key`, using the
+/// [`Args::value_parser`] if it returns a value, otherwise the type specified by a [`ValueHint`]
+/// for the variable, or for the `key` in the schema if there is one, otherwise inferring the type
+/// (unless it is one of [`EnvValueOptions::string_variables`]).
+fn parse_env_value(
+    name: &str,
+    value: String,
+    key: &TomlKeyPath,
+    options: &EnvValueOptions<'_>,
+) -> InnerResult<Value> {
+    if let Some(parsed) = options.value_parser.and_then(|parser| parser(&value, key)) {
+        return Ok(parsed);
+    }
+
+    if options.decode_base64 {
+        if let Some(encoded) = value.strip_prefix(BASE64_PREFIX) {
+            return decode_base64(encoded)
+                .and_then(|decoded| String::from_utf8(decoded).ok())
+                .map(Value::String)
+                .ok_or_else(|| InnerError::ErrorCoercingEnvironmentVariable {
+                    name: name.to_owned(),
+                    key: Box::new(key.clone()),
+                    expected: "base64 encoded UTF-8 string",
+                    value: options.displayed_value(value, key),
+                });
+        }
+    }
+
+    let schema = options.schema.and_then(|schema| key.resolve(schema));
+    if let Some(Value::Array(schema)) = schema {
+        if let (Some(element_schema @ Value::Table(_)), None) =
+            (schema.first(), options.value_hints.get(name))
+        {
+            return parse_table_array(name, value, key, element_schema, options);
+        }
+    }
+    let delimiter = options.list_delimiters.get(name).copied();
+    let string_hint = options
+        .string_variables
+        .contains(name)
+        .then_some(ValueHint::String);
+    let hint = options
+        .value_hints
+        .get(name)
+        .copied()
+        .or_else(|| match (schema?, delimiter) {
+            (Value::Array(_), Some(delimiter)) => Some(ValueHint::List(delimiter)),
+            (schema, _) => ValueHint::from_schema(schema),
+        })
+        .or(string_hint);
+    let number_inference = options
+        .number_inferences
+        .get(name)
+        .copied()
+        .unwrap_or(options.number_inference);
+
+    let parsed = match hint {
+        Some(ValueHint::List(delimiter)) => {
+            let element_hint = schema
+                .and_then(Value::as_array)
+                .and_then(|array| array.first())
+                .and_then(ValueHint::from_schema)
+                .or(string_hint);
+            value
+                .split(delimiter)
+                .filter(|_| !value.is_empty())
+                .map(|element| {
+                    parse_hinted_value(element.trim(), element_hint, number_inference, options)
+                })
+                .collect::<std::result::Result<Vec<_>, _>>()
+                .map(Value::Array)
+        }
+        hint => parse_hinted_value(&value, hint, number_inference, options),
+    };
+
+    parsed.map_err(|expected| InnerError::ErrorCoercingEnvironmentVariable {
+        name: name.to_owned(),
+        value: options.displayed_value(value, key),
+        key: Box::new(key.clone()),
+        expected,
+    })
+}
+
+/// Parse the `value` of the environment variable `name` which is mapped to `key`, an array of
+/// tables in the schema, as a TOML array literal (or a JSON array with the `json` feature),
+/// checking the shape of each element against the `element_schema`.
+fn parse_table_array(
+    name: &str,
+    value: String,
+    key: &TomlKeyPath,
+    element_schema: &Value,
+    options: &EnvValueOptions<'_>,
+) -> InnerResult<Value> {
+    let parsed = toml::from_str::<toml::Table>(&format!("value = {value}"))
+        .ok()
+        .and_then(|mut table| table.remove("value"));
+    #[cfg(feature = "json")]
+    let parsed = parsed.or_else(|| parse_json_value(&value));
+    let elements = match parsed {
+        Some(Value::Array(elements)) => elements,
+        _ => {
+            return Err(InnerError::ErrorCoercingEnvironmentVariable {
+                name: name.to_owned(),
+                value: options.displayed_value(value, key),
+                key: Box::new(key.clone()),
+                expected: "array of tables",
+            })
+        }
+    };
+    for (index, element) in elements.iter().enumerate() {
+        let mut path = key.clone();
+        path.push(index);
+        check_shape(element, element_schema, &mut path).map_err(|expected| {
+            InnerError::UnexpectedEnvironmentVariableShape {
+                name: name.to_owned(),
+                key: Box::new(key.clone()),
+                path: Box::new(path),
+                expected,
+            }
+        })?;
+    }
+    Ok(Value::Array(elements))
+}
+
+/// Check that `value` has the same type as the `schema`, recursively for the keys of tables which
+/// are in the schema (other keys are not checked) and the elements of arrays. Integers are
+/// accepted for floats. Otherwise returns the expected type, with `path` left at the value which
+/// has the wrong type.
+fn check_shape(
+    value: &Value,
+    schema: &Value,
+    path: &mut TomlKeyPath,
+) -> std::result::Result<(), &'static str> {
+    match (schema, value) {
+        (Value::Table(schema), Value::Table(table)) => {
+            for (key, value) in table {
+                if let Some(schema) = schema.get(key) {
+                    path.push(key.as_str());
+                    check_shape(value, schema, path)?;
+                    path.pop();
+                }
+            }
+            Ok(())
+        }
+        (Value::Array(schema), Value::Array(array)) => {
+            if let Some(schema) = schema.first() {
+                for (index, value) in array.iter().enumerate() {
+                    path.push(index);
+                    check_shape(value, schema, path)?;
+                    path.pop();
+                }
+            }
+            Ok(())
+        }
+        (Value::Float(_), Value::Integer(_)) => Ok(()),
+        (schema, value) if std::mem::discriminant(schema) == std::mem::discriminant(value) => {
+            Ok(())
+        }
+        (schema, _) => Err(schema.type_str()),
+    }
+}
+
+/// Decode a standard base64 `encoded` string (with optional padding, ignoring whitespace), `None`
+/// if it is not valid base64.
+fn decode_base64(encoded: &str) -> Option<Vec<u8>> {
+    let mut decoded = Vec::with_capacity(encoded.len() * 3 / 4);
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+    let encoded = encoded.trim_end_matches(|c: char| c == '=' || c.is_ascii_whitespace());
+    for byte in encoded.bytes() {
+        let value = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ if byte.is_ascii_whitespace() => continue,
+            _ => return None,
+        };
+        buffer = (buffer << 6) | u32::from(value);
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            decoded.push((buffer >> bits) as u8);
+        }
+    }
+    // A single leftover character cannot encode a whole byte.
+    (bits < 6).then_some(decoded)
+}
+
+/// Encode `bytes` as standard base64 with padding.
+#[cfg(feature = "kv-tree")]
+fn encode_base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let buffer = chunk.iter().enumerate().fold(0u32, |buffer, (i, byte)| {
+            buffer | u32::from(*byte) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(char::from(ALPHABET[(buffer >> (18 - 6 * i)) as usize & 63]));
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// Percent-encode a URL `path`, leaving unreserved characters and `/` as they are.
+#[cfg(feature = "kv-tree")]
+fn percent_encode_path(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~' | b'/') {
+            encoded.push(char::from(byte));
+        } else {
+            encoded.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    encoded
+}
+
+/// Parse a single `value` using the type specified by the `hint`, otherwise inferring the type
+/// (with numbers inferred according to `number_inference`). Returns the name of the expected type
+/// if the value could not be parsed.
+fn parse_hinted_value(
+    value: &str,
+    hint: Option<ValueHint>,
+    number_inference: NumberInference,
+    options: &EnvValueOptions<'_>,
+) -> std::result::Result<Value, &'static str> {
+    let (parsed, expected) = match hint {
+        // Nested lists are not supported, so the elements of a list have their type inferred.
+        None | Some(ValueHint::Infer) | Some(ValueHint::List(_)) => {
+            if number_inference != NumberInference::StringsOnly
+                && is_integer(value)
+                && i64::from_str(value).is_err()
+            {
+                if let Some(parsed) = parse_large_integer(value, options.large_integers) {
+                    return parsed;
+                }
+            }
+            #[cfg(feature = "json")]
+            if options.json {
+                if let Some(value) = parse_json_value(value) {
+                    return Ok(value);
+                }
+            }
+            return Ok(
+                match parse_toml_value(value.to_owned(), number_inference, options.inline_toml) {
+                    Value::Datetime(_) if !options.infer_datetimes => {
+                        Value::String(value.to_owned())
+                    }
+                    parsed => parsed,
+                },
+            );
+        }
+        Some(ValueHint::String) => return Ok(Value::String(value.to_owned())),
+        // Integers which are out of range for an `i64` are kept as strings by default, see
+        // `big_int`.
+        Some(ValueHint::Integer) if is_integer(value) => {
+            return match i64::from_str(value) {
+                Ok(integer) => Ok(Value::Integer(integer)),
+                Err(_) => parse_large_integer(value, options.large_integers)
+                    .unwrap_or_else(|| Ok(Value::String(value.to_owned()))),
+            }
+        }
+        Some(ValueHint::Integer) => (None, "integer"),
+        Some(ValueHint::Float) => (f64::from_str(value).ok().map(Value::Float), "float"),
+        Some(ValueHint::Bool) => (bool::from_str(value).ok().map(Value::Boolean), "boolean"),
+        Some(ValueHint::Datetime) => (
+            toml::value::Datetime::from_str(value)
+                .ok()
+                .map(Value::Datetime),
+            "datetime",
+        ),
+        #[cfg(feature = "bytesize")]
+        Some(ValueHint::ByteSize) => (parse_byte_size(value).map(Value::Integer), "byte size"),
+        #[cfg(feature = "humantime")]
+        Some(ValueHint::Duration(format)) => (
+            humantime::parse_duration(value)
+                .ok()
+                .and_then(|duration| format.to_value(duration)),
+            "duration",
+        ),
+    };
+
+    parsed.ok_or(expected)
+}
+
+/// Parse an integer `value` which is out of range for an `i64` according to the `large_integers`
+/// policy, or `None` if it is parsed the same as other numbers ([`LargeIntegers::Infer`]).
+fn parse_large_integer(
+    value: &str,
+    large_integers: LargeIntegers,
+) -> Option<std::result::Result<Value, &'static str>> {
+    match large_integers {
+        LargeIntegers::Infer => None,
+        LargeIntegers::String => Some(Ok(Value::String(value.to_owned()))),
+        LargeIntegers::Float => Some(f64::from_str(value).map(Value::Float).map_err(|_| "float")),
+        LargeIntegers::Error => Some(Err("integer in the range of an i64")),
+    }
+}
+
+/// Read the value for the environment variable `name` from the file referred to by the
+/// `{name}{file_suffix}` environment variable (see [`Args::file_suffix`]), returning the name of
+/// that variable and the contents of the file without a trailing newline. `None` if there is no
+/// `file_suffix` or the variable is not set. Relative paths are resolved against the `base_dir`.
+fn read_env_file(
+    name: &str,
+    file_suffix: Option<&str>,
+    base_dir: Option<&Path>,
+) -> InnerResult<Option<(String, String)>> {
+    let file_variable_name = match file_suffix {
+        Some(file_suffix) => format!("{name}{file_suffix}"),
+        None => return Ok(None),
+    };
+    let path = match std::env::var_os(&file_variable_name) {
+        Some(path) => resolve_path(base_dir, Path::new(&path)).into_owned(),
+        None => return Ok(None),
+    };
+    let mut value = std::fs::read_to_string(&path)
+        .map_err(|error| InnerError::ErrorReadingFile { path, error })?;
+    if value.ends_with('\n') {
+        value.pop();
+        if value.ends_with('\r') {
+            value.pop();
+        }
+    }
+    Ok(Some((file_variable_name, value)))
+}
+
+/// Parse the `contents` of a plain `.env` file (see [`Args::env_file_path`]) into its variables,
+/// in order. Returns the line number (starting at 1) of the first line which could not be parsed.
+#[cfg(feature = "dotenv")]
+fn parse_env_file(contents: &str) -> std::result::Result<Vec<(String, String)>, usize> {
+    let mut variables = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line).trim_start();
+        let (name, value) = line.split_once('=').ok_or(index + 1)?;
+        let name = name.trim_end();
+        let valid = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.');
+        if !valid {
+            return Err(index + 1);
+        }
+        let value = value.trim_start();
+        let (value, rest) = if let Some(quoted) = value.strip_prefix('\'') {
+            let (value, rest) = quoted.split_once('\'').ok_or(index + 1)?;
+            (value.to_owned(), rest)
+        } else if let Some(quoted) = value.strip_prefix('"') {
+            let mut unescaped = String::new();
+            let mut chars = quoted.char_indices();
+            let end = loop {
+                match chars.next() {
+                    Some((_, '\\')) => match chars.next() {
+                        Some((_, 'n')) => unescaped.push('\n'),
+                        Some((_, c)) => unescaped.push(c),
+                        None => return Err(index + 1),
+                    },
+                    Some((i, '"')) => break i,
+                    Some((_, c)) => unescaped.push(c),
+                    None => return Err(index + 1),
+                }
+            };
+            (unescaped, &quoted[end + 1..])
+        } else {
+            let value = match value.find(" #") {
+                Some(comment) => &value[..comment],
+                None => value,
+            };
+            (value.trim_end().to_owned(), "")
+        };
+        let rest = rest.trim_start();
+        if !(rest.is_empty() || rest.starts_with('#')) {
+            return Err(index + 1);
+        }
+        variables.push((name.to_owned(), value));
+    }
+    Ok(variables)
+}
+
+/// Set the variables in the plain `.env` file at `path` (see [`Args::env_file_path`]) as
+/// environment variables using the `env_guard`, adding them to `set_variables`. Returns the names
+/// of the variables in the file, `None` if it does not exist.
+#[cfg(feature = "dotenv")]
+fn initialize_env_file(
+    args: &Args<'_>,
+    path: &Path,
+    set_variables: &mut BTreeSet<String>,
+    env_guard: &mut EnvGuard,
+) -> InnerResult<Option<BTreeSet<String>>> {
+    if !path.is_file() {
+        return Ok(None);
+    }
+    log_info(
+        args.logging,
+        format_args!("Loading environment variables from .env file {path:?}"),
+    );
+    let contents = std::fs::read_to_string(path).map_err(|error| InnerError::ErrorReadingFile {
+        path: path.to_owned(),
+        error,
+    })?;
+    let variables = parse_env_file(&contents).map_err(|line| InnerError::ErrorParsingEnvFile {
+        path: path.to_owned(),
+        line,
+    })?;
+    // Checked before any variables are set, as for the .env.toml file.
+    let mut to_set = Vec::with_capacity(variables.len());
+    for (name, value) in variables {
+        if std::env::var_os(&name).is_some() && !set_variables.contains(&name) {
+            match args.dotenv_overlap {
+                DotEnvOverlap::Overwrite => {}
+                // The value comes from the environment rather than this file.
+                DotEnvOverlap::Keep => continue,
+                DotEnvOverlap::Error => {
+                    return Err(InnerError::DotEnvVariableAlreadySet {
+                        key: name,
+                        path: path.to_owned(),
+                    })
+                }
+            }
+        }
+        to_set.push((name, value));
+    }
+    let mut names = BTreeSet::new();
+    for (name, value) in to_set {
+        env_guard.set_var(name.as_str(), value);
+        set_variables.insert(name.clone());
+        names.insert(name);
+    }
+    Ok(Some(names))
+}
+
+/// Read the names of the variables set in the `.env` file at `path` (see
+/// [`Args::preloaded_dotenv_path`]), empty if the file does not exist.
+fn read_dotenv_variable_names(path: &Path) -> InnerResult<BTreeSet<String>> {
+    if !path.is_file() {
+        return Ok(BTreeSet::new());
+    }
+    let contents = std::fs::read_to_string(path).map_err(|error| InnerError::ErrorReadingFile {
+        path: path.to_owned(),
+        error,
+    })?;
+    Ok(contents
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim_start();
+            let line = line.strip_prefix("export ").unwrap_or(line).trim_start();
+            let (name, _) = line.split_once('=')?;
+            let name = name.trim_end();
+            let valid = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.');
+            valid.then(|| name.to_owned())
+        })
+        .collect())
+}
+
+/// Resolve a relative `path` against the `base_dir` (see [`Args::base_dir`]).
+fn resolve_path<'p>(base_dir: Option<&Path>, path: &'p Path) -> Cow<'p, Path> {
+    match base_dir {
+        Some(base_dir) if path.is_relative() => Cow::Owned(base_dir.join(path)),
+        _ => Cow::Borrowed(path),
+    }
+}
+
+/// Initialize from environment variables.
+fn initialize_env(
+    logging: Logging,
+    map_env: BTreeMap<Cow<'_, str>, TomlKeyPath>,
+    options: &EnvValueOptions<'_>,
+    unset_keys: &mut Vec<TomlKeyPath>,
+) -> InnerResult<Option<Layer>> {
+    if map_env.is_empty() {
+        return Ok(None);
+    }
+
+    if !matches!(logging, Logging::None) {
+        let mut buffer = String::new();
+        buffer.push_str(HIGHLIGHT);
+        for (k, v) in &map_env {
+            if options.var(k).is_ok() {
+                buffer.push_str(&format!("\n{k} => {v}"));
+            } else if let Some(file_suffix) = options.file_suffix {
+                if options.var(&format!("{k}{file_suffix}")).is_ok() {
+                    buffer.push_str(&format!("\n{k}{file_suffix} => {v}"));
+                }
+            }
+        }
+        buffer.push_str(RESET);
+        log_info(
+            logging,
+            format_args!("Loading config from current environment variables: {buffer}"),
+        );
+    }
+
+    log_info(logging, format_args!("Loading config from environment"));
+
+    let mut config = toml::Value::Table(toml::Table::new());
+    let mut variables = Vec::new();
+    for (variable_name, toml_key) in map_env {
+        let (value, read_name) = match options.var(&variable_name) {
+            Ok(value) => (value, variable_name.clone().into_owned()),
+            Err(std::env::VarError::NotPresent) => {
+                match read_env_file(&variable_name, options.file_suffix, options.base_dir)? {
+                    Some((file_variable_name, value)) => (value, file_variable_name),
+                    None => continue,
+                }
+            }
+            Err(error) => {
+                return Err(InnerError::ErrorReadingEnvironmentVariable {
+                    name: (*variable_name.into_owned()).to_owned(),
+                    error,
+                })
+            }
+        };
+        if (value.is_empty() && options.empty_values == EmptyValues::Unset)
+            || options.unset_sentinel == Some(value.as_str())
+        {
+            log_info(
+                logging,
+                format_args!("Removing {toml_key} because {read_name} is {value:?}"),
+            );
+            unset_keys.push(toml_key);
+            continue;
+        }
+        let value = parse_env_value(&variable_name, value, &toml_key, options)?;
+        insert_toml_value_at(&mut config, &toml_key.0, value)?;
+        variables.push((read_name, toml_key));
+    }
+
+    Ok(Some(Layer {
+        value: config,
+        source: ConfigSource::Environment {
+            variable_names: variables.iter().map(|(name, _)| name.clone()).collect(),
+        },
+        variables,
+        read_at: SystemTime::now(),
+    }))
+}
+
+/// Remove the table property at `key` from `value`, if it exists. Array elements are not removed,
+/// because that would change the indices of the following elements.
+fn remove_toml_value(value: &mut Value, key: &TomlKeyPath) {
+    if let Some(PathElement::TableProperty(_)) = key.0.last() {
+        key.remove(value);
+    }
+}
+
+/// Recursively sort the keys of all tables in `value`, so that the rendered output is stable
+/// regardless of the order in which keys were inserted (e.g. from a `HashMap`, or when the `toml`
+/// crate's `preserve_order` feature is enabled).
+fn sort_keys(value: Value) -> Value {
+    match value {
+        Value::Table(table) => {
+            let mut entries: Vec<(String, Value)> = table.into_iter().collect();
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            Value::Table(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| (key, sort_keys(value)))
+                    .collect(),
+            )
+        }
+        Value::Array(array) => Value::Array(array.into_iter().map(sort_keys).collect()),
+        value => value,
+    }
+}
+
+/// The error produced when merging two configurations fails.
+#[cfg(feature = "merge")]
+type MergeError = serde_toml_merge::Error;
+
+/// The error produced when merging two configurations fails.
+#[cfg(not(feature = "merge"))]
+#[derive(Debug, Error)]
+#[error("Incompatible types at path {path}, expected {expected} received {existing}.")]
+struct MergeError {
+    path: String,
+    expected: &'static str,
+    existing: &'static str,
+}
+
+/// Merge `from` into `into`, values in `from` take precedence.
+#[cfg(feature = "merge")]
+fn merge_values(into: Value, from: Value) -> std::result::Result<Value, MergeError> {
+    serde_toml_merge::merge(into, from)
+}
+
+/// Merge `from` into `into`, values in `from` take precedence. This is a minimal implementation
+/// of the same behaviour as [`serde_toml_merge::merge()`] for when the `merge` feature is
+/// disabled.
+#[cfg(not(feature = "merge"))]
+fn merge_values(into: Value, from: Value) -> std::result::Result<Value, MergeError> {
+    fn merge_impl(into: Value, from: Value, path: &str) -> std::result::Result<Value, MergeError> {
+        match (into, from) {
+            (Value::Table(mut into), Value::Table(from)) => {
+                for (key, from_value) in from {
+                    let value = match into.remove(&key) {
+                        Some(into_value) => {
+                            merge_impl(into_value, from_value, &format!("{path}.{key}"))?
+                        }
+                        None => from_value,
+                    };
+                    into.insert(key, value);
+                }
+                Ok(Value::Table(into))
+            }
+            (Value::Array(mut into), Value::Array(from)) => {
+                into.extend(from);
+                Ok(Value::Array(into))
+            }
+            (into, from) if into.same_type(&from) => Ok(from),
+            (into, from) => Err(MergeError {
+                path: path.to_owned(),
+                expected: into.type_str(),
+                existing: from.type_str(),
+            }),
+        }
+    }
+
+    merge_impl(into, from, "$")
+}
+
+/// A single source of configuration which is merged with the others.
+struct Layer {
+    value: Value,
+    source: ConfigSource,
+    /// For mapped environment variables, the names of the variables which were read and the keys
+    /// they were mapped to. Used to narrow down the source of individual keys.
+    variables: Vec<(String, TomlKeyPath)>,
+    /// The time at which the source was read.
+    read_at: SystemTime,
+}
+
+impl Layer {
+    fn new(value: Value, source: ConfigSource) -> Self {
+        Self {
+            value,
+            source,
+            variables: Vec::new(),
+            read_at: SystemTime::now(),
+        }
+    }
+
+    /// The source of the value at `key` in this layer.
+    fn key_source(&self, key: &TomlKeyPath) -> ConfigSource {
+        if self.variables.is_empty() {
+            return self.source.clone();
+        }
+
+        let variable_names = self
+            .variables
+            .iter()
+            .filter(|(_, variable_key)| {
+                variable_key.0.starts_with(&key.0) || key.0.starts_with(&variable_key.0)
+            })
+            .map(|(name, _)| name.clone())
+            .collect();
+        match &self.source {
+            ConfigSource::PreloadedDotEnv { path, .. } => ConfigSource::PreloadedDotEnv {
+                path: path.clone(),
+                variable_names,
+            },
+            _ => ConfigSource::Environment { variable_names },
+        }
+    }
+}
+
+/// Find the first key where the types of `into` and `from` are incompatible for merging.
+fn find_merge_conflict(
+    path: &mut PathElements,
+    into: &Value,
+    from: &Value,
+) -> Option<(TomlKeyPath, &'static str, &'static str)> {
+    match (into, from) {
+        (Value::Table(into), Value::Table(from)) => from.iter().find_map(|(key, from_value)| {
+            let into_value = into.get(key)?;
+            path.push(PathElement::TableProperty(key.as_str().into()));
+            let conflict = find_merge_conflict(path, into_value, from_value);
+            path.pop();
+            conflict
+        }),
+        (into, from) if into.same_type(from) => None,
+        (into, from) => Some((TomlKeyPath(path.clone()), into.type_str(), from.type_str())),
+    }
+}
+
+/// Check that no layer sets a key which it is not allowed to by the `policies`.
+fn check_key_policies(layers: &[Layer], policies: &[KeyPolicy]) -> InnerResult<()> {
+    for layer in layers {
+        let kind = match layer.source.kind() {
+            Some(kind) => kind,
+            None => continue,
+        };
+        for policy in policies {
+            if policy.allows(kind) || policy.key().resolve(&layer.value).is_none() {
+                continue;
+            }
+            return Err(InnerError::KeyPolicyViolation {
+                key: Box::new(policy.key().clone()),
+                kind,
+                config_source: layer.key_source(policy.key()),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Merge configuration layers, ordered from lowest to highest precedence.
+fn merge_layers(layers: &[Layer]) -> InnerResult<Option<(Value, ConfigSource)>> {
+    let mut merged: Option<(Value, ConfigSource)> = None;
+    for (i, layer) in layers.iter().enumerate() {
+        let (into_value, into_source) = match merged.take() {
+            None => {
+                merged = Some((layer.value.clone(), layer.source.clone()));
+                continue;
+            }
+            Some(merged) => merged,
+        };
+
+        if let Some((key, into_type, from_type)) =
+            find_merge_conflict(&mut PathElements::new(), &into_value, &layer.value)
+        {
+            // Name the source with the highest precedence that contributed the conflicting key.
+            let into = layers[..i]
+                .iter()
+                .rev()
+                .find(|into_layer| key.resolve(&into_layer.value).is_some())
+                .map(|into_layer| into_layer.key_source(&key))
+                .unwrap_or_else(|| into_source.clone());
+            return Err(InnerError::MergeConflict(Box::new(MergeConflict {
+                from: layer.key_source(&key),
+                key,
+                from_type,
+                into_type,
+                into,
+            })));
+        }
+
+        let value = merge_values(into_value, layer.value.clone()).map_err(|error| {
+            InnerError::ErrorMerging {
+                from: layer.source.clone().into(),
+                into: into_source.clone().into(),
+                error,
+            }
+        })?;
+        let source = ConfigSource::Merged {
+            from: layer.source.clone().into(),
+            into: into_source.into(),
+        };
+        merged = Some((value, source));
+    }
+    Ok(merged)
+}
+
+/// Determine the source of each value in the merged configuration `value`, keyed by the path to
+/// the value. Tables are descended into, all other values (including arrays) are attributed to
+/// the layer with the highest precedence which contains them.
+fn provenance(value: &Value, layers: &[Layer]) -> BTreeMap<String, ConfigSource> {
+    fn provenance_impl(
+        path: &mut PathElements,
+        value: &Value,
+        layers: &[Layer],
+        provenance: &mut BTreeMap<String, ConfigSource>,
+    ) {
+        if let Value::Table(table) = value {
+            for (key, value) in table {
+                path.push(PathElement::TableProperty(key.as_str().into()));
+                provenance_impl(path, value, layers, provenance);
+                path.pop();
+            }
+            return;
+        }
+
+        let key = TomlKeyPath(path.clone());
+        if let Some(layer) = layers
+            .iter()
+            .rev()
+            .find(|layer| key.resolve(&layer.value).is_some())
+        {
+            provenance.insert(key.to_string(), layer.key_source(&key));
+        }
+    }
+
+    let mut provenance = BTreeMap::new();
+    provenance_impl(&mut PathElements::new(), value, layers, &mut provenance);
+    provenance
+}
+
+/// A [`serde::Deserializer`] which only succeeds for types which deserialize from a unit (such
+/// as `()` or `struct Unit;`), used to detect unit targets in [`deserialize_config()`].
+struct UnitProbe;
+
+impl<'de> serde::Deserializer<'de> for UnitProbe {
+    type Error = serde::de::value::Error;
+
+    fn deserialize_any<V>(self, _visitor: V) -> std::result::Result<V::Value, Self::Error>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        Err(serde::de::Error::custom("not a unit type"))
+    }
+
+    fn deserialize_unit<V>(self, visitor: V) -> std::result::Result<V::Value, Self::Error>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> std::result::Result<V::Value, Self::Error>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+        option newtype_struct seq tuple tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+/// Deserialize the merged configuration. Unit targets (such as `()`) are supported for callers
+/// which only want the side effects of initialization (e.g. setting environment variables from
+/// `.env.toml`), and will ignore the contents of the configuration.
+fn deserialize_config<C: DeserializeOwned>(
+    config: Value,
+) -> std::result::Result<C, toml::de::Error> {
+    C::deserialize(config).or_else(|error| C::deserialize(UnitProbe).map_err(|_| error))
+}
+
+/// The configuration sources loaded by [`load()`].
+struct Loaded {
+    /// The merged configuration and its source, `None` if no configuration was found.
+    config: Option<(Value, ConfigSource)>,
+    /// The layers of configuration which were merged, ordered from lowest to highest precedence.
+    layers: Vec<Layer>,
+    /// The time at which loading started.
+    loaded_at: SystemTime,
+    /// Errors from sources which were skipped.
+    warnings: Vec<SourceWarning>,
+    /// See [`InitializationReport::dotenv_variables`].
+    #[cfg(feature = "dotenv")]
+    dotenv_variables: toml::Table,
+}
+
+/// Load the configuration stored in the `config_variable_name` environment variable (or the file
+/// that it refers to).
+fn load_config_variable(args: &Args<'_>) -> InnerResult<Option<Layer>> {
+    let config_variable_name = args.config_variable_name;
+    let logging = args.logging;
+
+    let mut source = ConfigSource::Environment {
+        variable_names: vec![config_variable_name.to_owned()],
+    };
+    let config: Option<Value> = match std::env::var(config_variable_name) {
+        Ok(variable_value) => match toml::from_str(&variable_value) {
+            Ok(config) => {
+                log_info(
+                    logging,
+                    format_args!(
+                        "Options loaded from `{config_variable_name}` environment variable"
+                    ),
+                );
+                Ok(Some(config))
+            }
+            Err(error) => {
+                let path = resolve_path(args.base_dir, Path::new(&variable_value));
+                if path.is_file() {
+                    log_info(
+                        args.logging,
+                        format_args!("Loading environment variables from {path:?}"),
+                    );
+
+                    let config_str = std::fs::read_to_string(&path).map_err(|error| {
+                        InnerError::ErrorReadingFile {
+                            path: path.clone().into_owned(),
+                            error,
+                        }
+                    })?;
+                    let config: Value = toml::from_str(&config_str).map_err(|error| {
+                        InnerError::ErrorParsingTomlFile {
+                            path: path.clone().into_owned(),
+                            error: error.into(),
+                        }
+                    })?;
+                    log_info(logging, format_args!("Options loaded from file specified in `{config_variable_name}` environment variable: {path:?}"));
+                    source = ConfigSource::File(path.into_owned());
+                    Ok(Some(config))
+                } else {
+                    Err(InnerError::ErrorParsingEnvironmentVariableAsConfigOrFile {
+                        name: config_variable_name.to_owned(),
+                        value: variable_value,
+                        error: error.into(),
+                    })
+                }
+            }
+        },
+        Err(std::env::VarError::NotPresent) => {
+            log_info(
+                logging,
+                format_args!(
+                    "No environment variable with the name {config_variable_name} found, using default options."
+                ),
+            );
+            Ok(None)
+        }
+        Err(error) => Err(InnerError::ErrorReadingEnvironmentVariable {
+            name: config_variable_name.to_owned(),
+            error,
+        }),
+    }?;
+
+    let config = match config {
+        Some(config) => config,
+        None => return Ok(None),
+    };
+    let config = match args.config_variable_key_path.resolve(&config) {
+        Some(config) => config.clone(),
+        None if args.missing_key_path == MissingKeyPath::Silent => {
+            log_info(
+                logging,
+                format_args!(
+                    "No config found at {} in `{config_variable_name}` environment variable",
+                    args.config_variable_key_path
+                ),
+            );
+            return Ok(None);
+        }
+        None => {
+            return Err(InnerError::ConfigKeyPathNotFound {
+                name: config_variable_name.to_owned(),
+                key: Box::new(args.config_variable_key_path.clone()),
+            })
+        }
+    };
+
+    Ok(Some(Layer::new(config, source)))
+}
+
+/// The path of the config file referred to by the [`Args::config_variable_name`] environment
+/// variable, `None` if it is not set or contains the configuration itself.
+fn config_variable_path(args: &Args<'_>) -> Option<PathBuf> {
+    let variable_value = std::env::var(args.config_variable_name).ok()?;
+    if toml::from_str::<Value>(&variable_value).is_ok() {
+        return None;
+    }
+    Some(resolve_path(args.base_dir, Path::new(&variable_value)).into_owned())
+}
+
+/// Read the config file at `path`, `None` if it does not exist.
+///
+/// With the `archive` feature, the path may refer to a file within a `.zip`, `.tar` or `.tar.gz`
+/// archive (e.g. `bundle.zip!config/config.toml`).
+fn read_config_file(path: &Path) -> std::io::Result<Option<String>> {
+    #[cfg(feature = "archive")]
+    if let Some((archive_path, format, entry)) = split_archive_path(path) {
+        return read_archive_entry(archive_path, format, entry);
+    }
+
+    if !path.is_file() {
+        return Ok(None);
+    }
+    std::fs::read_to_string(path).map(Some)
+}
+
+/// The format of an archive containing a config file, see [`Args::config_path`].
+#[cfg(feature = "archive")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ArchiveFormat {
+    /// A `.zip` archive.
+    Zip,
+    /// A `.tar` archive.
+    Tar,
+    /// A gzip compressed `.tar.gz` archive.
+    TarGz,
+}
+
+#[cfg(feature = "archive")]
+impl ArchiveFormat {
+    /// The format of the archive at `path` according to its file name, `None` if it is not an
+    /// archive.
+    fn from_path(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_str()?;
+        if name.ends_with(".zip") {
+            Some(Self::Zip)
+        } else if name.ends_with(".tar") {
+            Some(Self::Tar)
+        } else if name.ends_with(".tar.gz") {
+            Some(Self::TarGz)
+        } else {
+            None
+        }
+    }
+}
+
+/// Split a `path` of the form `bundle.zip!config/config.toml` into the path to the archive, its
+/// format and the path of the entry within it, `None` if the path does not refer to a `.zip`,
+/// `.tar` or `.tar.gz` archive.
+#[cfg(feature = "archive")]
+fn split_archive_path(path: &Path) -> Option<(&Path, ArchiveFormat, &str)> {
+    let (archive_path, entry) = path.to_str()?.split_once('!')?;
+    let archive_path = Path::new(archive_path);
+    let format = ArchiveFormat::from_path(archive_path)?;
+    Some((archive_path, format, entry))
+}
+
+/// Read the `entry` from the archive at `archive_path`, `None` if the archive or the entry do not
+/// exist.
+#[cfg(feature = "archive")]
+fn read_archive_entry(
+    archive_path: &Path,
+    format: ArchiveFormat,
+    entry: &str,
+) -> std::io::Result<Option<String>> {
+    use std::io::Read;
+
+    if !archive_path.is_file() {
+        return Ok(None);
+    }
+    let file = std::fs::File::open(archive_path)?;
+    let mut contents = String::new();
+
+    let reader: Box<dyn Read> = match format {
+        ArchiveFormat::Zip => {
+            let mut archive = zip::ZipArchive::new(file)?;
+            let result = archive.by_name(entry);
+            match result {
+                Ok(mut file) => {
+                    file.read_to_string(&mut contents)?;
+                }
+                Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+                Err(error) => return Err(error.into()),
+            }
+            return Ok(Some(contents));
+        }
+        ArchiveFormat::Tar => Box::new(file),
+        ArchiveFormat::TarGz => Box::new(flate2::read::GzDecoder::new(file)),
+    };
+
+    // Archives created with e.g. `tar -C dir -cf bundle.tar .` prefix their entries with `./`.
+    let entry = Path::new(entry);
+    let entry = entry.strip_prefix(".").unwrap_or(entry);
+    let mut archive = tar::Archive::new(reader);
+    for archive_entry in archive.entries()? {
+        let mut archive_entry = archive_entry?;
+        let path = archive_entry.path()?;
+        if path.strip_prefix(".").unwrap_or(&path) == entry {
+            archive_entry.read_to_string(&mut contents)?;
+            return Ok(Some(contents));
+        }
+    }
+    Ok(None)
+}
+
+/// The environment variables mapped to keys in the configuration, using [`Args::map_env`] and
+/// [`Args::auto_map_env`].
+struct MappedVariables<'a> {
+    /// Using a BTreeMap to ensure values are sorted by environment variable, so that array indices
+    /// are in the correct order of insertion to avoid an out of bounds.
+    map_env: BTreeMap<Cow<'a, str>, TomlKeyPath>,
+    /// See [`EnvValueOptions::string_variables`].
+    string_variables: BTreeSet<String>,
+    /// See [`EnvValueOptions::list_delimiters`].
+    list_delimiters: BTreeMap<String, char>,
+    /// See [`EnvValueOptions::number_inferences`].
+    number_inferences: BTreeMap<String, NumberInference>,
+}
+
+impl<'a> MappedVariables<'a> {
+    fn new(args: &'a Args<'_>) -> Self {
+        #[allow(unused_mut)]
+        let mut variables = Self {
+            map_env: args
+                .map_env
+                .iter()
+                .map(|(key, value)| (Cow::Borrowed(*key), value.clone()))
+                .collect(),
+            string_variables: BTreeSet::new(),
+            list_delimiters: BTreeMap::new(),
+            number_inferences: BTreeMap::new(),
+        };
+
+        #[cfg(feature = "auto-map-env")]
+        if let Some(auto_args) = &args.auto_map_env {
+            auto_map_env(
+                auto_args,
+                args,
+                &mut variables.map_env,
+                &mut variables.string_variables,
+                &mut variables.list_delimiters,
+                &mut variables.number_inferences,
+            );
+        }
+
+        variables
+    }
+}
+
+/// List the resources that [`initialize()`] may access with the specified `args`, so that a
+/// sandbox (e.g. using Landlock and seccomp) can grant access to exactly these resources before
+/// initializing. This includes the paths listed by [`accessed_paths()`], the
+/// [`ConfigLocation::Url`] endpoint, and the [`Source::accesses`] of the [`Args::sources`]. No
+/// other resources are accessed, unless a custom [`Source`] doesn't declare its accesses.
+///
+/// # Example
+///
+/// ```rust
+/// use toml_env::{Access, Args, CommandSource};
+///
+/// let accesses = toml_env::accesses(&Args {
+///     sources: vec![Box::new(CommandSource::new("pass").args(["show", "my-app"]))],
+///     ..Args::default()
+/// });
+/// assert!(accesses.contains(&Access::Command("pass".into())));
+/// ```
+pub fn accesses(args: &Args<'_>) -> Vec<Access> {
+    let mut accesses: Vec<Access> = file_paths(args).into_iter().map(Access::Path).collect();
+    #[cfg(feature = "remote")]
+    if let Some(ConfigLocation::Url(url)) = args.config_location {
+        accesses.push(Access::Endpoint(url.to_owned()));
+    }
+    for source in &args.sources {
+        accesses.extend(source.accesses());
+    }
+    accesses
+}
+
+/// List the filesystem paths that [`initialize()`] may access with the specified `args`, so that
+/// a sandbox (e.g. using Landlock) can grant access to exactly these paths before initializing.
+/// See [`accesses()`] to also list network endpoints and commands.
+///
+/// This includes the [`Args::dotenv_path`] (unless [`Args::dotenv_inline`] is used), the
+/// [`Args::dotenv_local_path`], the [`Args::env_file_path`], the
+/// [`Args::preloaded_dotenv_path`], the [`Args::config_paths`] and [`Args::config_path`] (or the
+/// archives containing them), the [`Args::config_dir`] and the files in it, the files referred to
+/// by [`Args::file_suffix`] variables which are currently set, and the paths declared by the
+/// [`Source::accesses`] of the [`Args::sources`]. The paths may not exist.
+///
+/// # Example
+///
+/// ```rust
+/// use std::path::Path;
+///
+/// let paths = toml_env::accessed_paths(&toml_env::Args {
+///     config_path: Some(Path::new("config.toml")),
+///     base_dir: Some(Path::new("/app")),
+///     ..toml_env::Args::default()
+/// });
+/// assert!(paths.contains(&Path::new("/app/config.toml").to_owned()));
+/// ```
+pub fn accessed_paths(args: &Args<'_>) -> Vec<PathBuf> {
+    accesses(args)
+        .into_iter()
+        .filter_map(|access| match access {
+            Access::Path(path) => Some(path),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The paths of the files (and directories) which are accessed by the built in sources, see
+/// [`accessed_paths()`].
+fn file_paths(args: &Args<'_>) -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+
+    #[cfg(feature = "dotenv")]
+    if args.dotenv_inline.is_none() {
+        paths.push(resolve_path(args.base_dir, args.dotenv_path).into_owned());
+    }
+
+    #[cfg(feature = "dotenv")]
+    if let Some(dotenv_local_path) = args.dotenv_local_path {
+        paths.push(resolve_path(args.base_dir, dotenv_local_path).into_owned());
+    }
+
+    #[cfg(feature = "dotenv")]
+    if let Some(env_file_path) = args.env_file_path {
+        paths.push(resolve_path(args.base_dir, env_file_path).into_owned());
+    }
+
+    if let Some(preloaded_dotenv_path) = args.preloaded_dotenv_path {
+        paths.push(resolve_path(args.base_dir, preloaded_dotenv_path).into_owned());
+    }
+
+    paths.extend(config_variable_path(args));
+
+    for config_path in args.config_paths.iter().copied().chain(config_path(args)) {
+        let config_path = resolve_path(args.base_dir, config_path);
+        #[cfg(feature = "archive")]
+        if let Some((archive_path, _, _)) = split_archive_path(&config_path) {
+            paths.push(archive_path.to_owned());
+        } else {
+            paths.push(config_path.into_owned());
+        }
+        #[cfg(not(feature = "archive"))]
+        paths.push(config_path.into_owned());
+    }
+
+    if let Some(config_dir) = args.config_dir {
+        let config_dir = resolve_path(args.base_dir, config_dir);
+        paths.extend(config_dir_paths(&config_dir).unwrap_or_default());
+        paths.push(config_dir.into_owned());
+    }
+
+    if let Some(file_suffix) = args.file_suffix {
+        for name in MappedVariables::new(args).map_env.keys() {
+            if std::env::var_os(name.as_ref()).is_some() {
+                continue;
+            }
+            if let Some(path) = std::env::var_os(format!("{name}{file_suffix}")) {
+                paths.push(resolve_path(args.base_dir, Path::new(&path)).into_owned());
+            }
+        }
+    }
+
+    paths
+}
+
+/// Load the config file at `path`, `None` if it does not exist.
+fn load_config_file(path: &Path) -> InnerResult<Option<Layer>> {
+    let file_string = match read_config_file(path) {
+        Ok(Some(file_string)) => file_string,
+        Ok(None) => return Ok(None),
+        Err(error) => {
+            return Err(InnerError::ErrorReadingFile {
+                path: path.to_owned(),
+                error,
+            })
+        }
+    };
+    let value = parse_config_file(path, &file_string)?;
+    Ok(Some(Layer::new(value, ConfigSource::File(path.to_owned()))))
+}
+
+/// Parse the `contents` of the config file at `path` according to its extension: JSON5 for
+/// `.json5` files with the `json5` feature, INI for `.ini` and `.cfg` files with the `ini`
+/// feature, otherwise TOML.
+fn parse_config_file(path: &Path, contents: &str) -> InnerResult<Value> {
+    #[cfg(feature = "json5")]
+    if path.extension() == Some(OsStr::new("json5")) {
+        return json5::from_str(contents).map_err(|error| InnerError::ErrorParsingJson5File {
+            path: path.to_owned(),
+            error: Box::new(error),
+        });
+    }
+    #[cfg(feature = "ini")]
+    if matches!(
+        path.extension().and_then(OsStr::to_str),
+        Some("ini" | "cfg")
+    ) {
+        return parse_ini(path, contents);
+    }
+    toml::from_str(contents).map_err(|error| InnerError::ErrorParsingTomlFile {
+        path: path.to_owned(),
+        error: error.into(),
+    })
+}
+
+/// Whether the file at `path` has the extension of a supported config file format (see
+/// [`parse_config_file()`]).
+fn is_config_file(path: &Path) -> bool {
+    match path.extension().and_then(OsStr::to_str) {
+        Some("toml") => true,
+        #[cfg(feature = "json5")]
+        Some("json5") => true,
+        #[cfg(feature = "ini")]
+        Some("ini" | "cfg") => true,
+        _ => false,
+    }
+}
+
+/// Parse the `contents` of an INI file into a table, with the properties before the first section
+/// at the top level, and each section as a table. Values have their type inferred in the same way
+/// as environment variables, with [`NumberInference::PreferInteger`]. A top level property with the
+/// same name as a section is an error.
+#[cfg(feature = "ini")]
+fn parse_ini(path: &Path, contents: &str) -> InnerResult<Value> {
+    let ini =
+        ini::Ini::load_from_str(contents).map_err(|error| InnerError::ErrorParsingIniFile {
+            path: path.to_owned(),
+            error: Box::new(error),
+        })?;
+    let conflict = |name: &str| InnerError::IniPropertyConflictsWithSection {
+        path: path.to_owned(),
+        name: name.to_owned(),
+    };
+    let mut table = toml::Table::new();
+    for (section, properties) in ini.iter() {
+        let properties = properties.iter().map(|(key, value)| {
+            let value = parse_toml_value(value.to_owned(), NumberInference::PreferInteger, false);
+            (key.to_owned(), value)
+        });
+        match section {
+            Some(section) => match table.get_mut(section) {
+                Some(Value::Table(section_table)) => section_table.extend(properties),
+                Some(_) => return Err(conflict(section)),
+                None => {
+                    table.insert(section.to_owned(), Value::Table(properties.collect()));
+                }
+            },
+            None => {
+                for (key, value) in properties {
+                    if table.get(&key).is_some_and(Value::is_table) {
+                        return Err(conflict(&key));
+                    }
+                    table.insert(key, value);
+                }
+            }
+        }
+    }
+    Ok(Value::Table(table))
+}
+
+/// The paths of the config files in the `config_dir` (see [`Args::config_dir`] and
+/// [`is_config_file()`]), sorted by name.
+/// Empty if the directory does not exist.
+fn config_dir_paths(config_dir: &Path) -> InnerResult<Vec<PathBuf>> {
+    if !config_dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let read_error = |error| InnerError::ErrorReadingFile {
+        path: config_dir.to_owned(),
+        error,
+    };
+    let mut paths = Vec::new();
+    for entry in std::fs::read_dir(config_dir).map_err(read_error)? {
+        let path = entry.map_err(read_error)?.path();
+        if is_config_file(&path) && path.is_file() {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+/// The path of the config file, from the [`Args::config_location`] or the [`Args::config_path`].
+fn config_path<'a>(args: &Args<'a>) -> Option<&'a Path> {
+    match args.config_location {
+        Some(ConfigLocation::Path(path)) => Some(path),
+        Some(_) => None,
+        None => args.config_path,
+    }
+}
+
+/// Read the configuration which is not from a file, from the [`Args::config_location`] or the
+/// [`Args::config_inline`], returning its contents, source, and a label for error messages.
+fn read_inline_config<'a>(
+    args: &Args<'a>,
+) -> InnerResult<Option<(Cow<'a, str>, ConfigSource, String)>> {
+    let inline = match args.config_location {
+        Some(ConfigLocation::Path(_)) => None,
+        Some(ConfigLocation::Inline(inline)) => Some(inline),
+        Some(ConfigLocation::Env(name)) => {
+            return Ok(std::env::var(name).ok().map(|content| {
+                let source = ConfigSource::Environment {
+                    variable_names: vec![name.to_owned()],
+                };
+                (Cow::Owned(content), source, format!("env://{name}"))
+            }))
+        }
+        #[cfg(feature = "remote")]
+        Some(ConfigLocation::Url(url)) => {
+            return Ok(fetch_remote_config(url, &args.remote)?.map(|content| {
+                let source = ConfigSource::Remote {
+                    url: url.to_owned(),
+                };
+                (Cow::Owned(content), source, url.to_owned())
+            }))
+        }
+        #[cfg(not(feature = "remote"))]
+        Some(ConfigLocation::Url(url)) => {
+            return Err(InnerError::RemoteFeatureDisabled {
+                url: url.to_owned(),
+            })
+        }
+        Some(ConfigLocation::Stdin) => {
+            let mut content = String::new();
+            std::io::Read::read_to_string(&mut std::io::stdin(), &mut content)
+                .map_err(|error| InnerError::ErrorReadingStdin { error })?;
+            let label = "stdin".to_owned();
+            let source = ConfigSource::Inline {
+                label: label.clone(),
+            };
+            return Ok(Some((Cow::Owned(content), source, label)));
+        }
+        None => args.config_inline,
+    };
+    Ok(inline.map(|inline| {
+        let source = ConfigSource::Inline {
+            label: inline.label.to_owned(),
+        };
+        (
+            Cow::Borrowed(inline.content),
+            source,
+            inline.label.to_owned(),
+        )
+    }))
+}
+
+/// Load and merge the configuration from the sources specified in `args`. Environment variables
+/// which are set while loading are kept.
+fn load(args: &Args<'_>) -> InnerResult<Loaded> {
+    let mut env_guard = EnvGuard::new();
+    let loaded = load_with_env_guard(args, &mut env_guard);
+    env_guard.keep();
+    loaded
+}
+
+/// Load and merge the configuration from the sources specified in `args`, setting environment
+/// variables using the `env_guard`.
+#[cfg_attr(not(feature = "dotenv"), allow(unused_variables))]
+fn load_with_env_guard(args: &Args<'_>, env_guard: &mut EnvGuard) -> InnerResult<Loaded> {
+    let loaded_at = SystemTime::now();
+    let logging = args.logging;
+
+    if let Some(base_dir) = args.base_dir.filter(|base_dir| base_dir.is_relative()) {
+        return Err(InnerError::RelativeBaseDir {
+            path: base_dir.to_owned(),
+        });
+    }
+
+    #[cfg(feature = "auto-map-env")]
+    if args.schema.is_none()
+        && args
+            .auto_map_env
+            .as_ref()
+            .is_some_and(|auto_args| auto_args.list_delimiter.is_some())
+    {
+        return Err(InnerError::ListDelimiterWithoutSchema);
+    }
+
+    let mut warnings = Vec::new();
+    let optional_path = |path: &Path| {
+        args.optional_paths
+            .iter()
+            .any(|optional| resolve_path(args.base_dir, optional) == path)
+    };
+    let mut optional = |kind: SourceKind,
+                        optional_source: bool,
+                        result: InnerResult<Option<Layer>>| match result {
+        Err(error)
+            if optional_source
+                || args.optional_sources.contains(&kind)
+                || (args.missing_key_path == MissingKeyPath::Warn
+                    && matches!(error, InnerError::ConfigKeyPathNotFound { .. })) =>
+        {
+            let warning = SourceWarning {
+                kind,
+                error: Arc::new(error.into()),
+            };
+            log_warn(logging, format_args!("{warning}"));
+            warnings.push(warning);
+            Ok(None)
+        }
+        result => result,
+    };
+
+    let config_env_config = optional(SourceKind::Environment, false, load_config_variable(args))?;
+
+    #[cfg(feature = "dotenv")]
+    let (dotenv_path, dotenv_source) = match args.dotenv_inline {
+        Some(inline) => (
+            Cow::Borrowed(Path::new(inline.label)),
+            ConfigSource::Inline {
+                label: inline.label.to_owned(),
+            },
+        ),
+        None => {
+            let path = resolve_path(args.base_dir, args.dotenv_path);
+            let source = ConfigSource::DotEnv(path.clone().into_owned());
+            (path, source)
+        }
+    };
+    #[cfg(feature = "dotenv")]
+    let mut dotenv_variables = toml::Table::new();
+    #[cfg(feature = "dotenv")]
+    let mut set_variables = BTreeSet::new();
+    // Names of the variables in `.env` files, which mapped variables are reported as coming from.
+    let mut preloaded_dotenvs: Vec<(PathBuf, BTreeSet<String>)> = Vec::new();
+    #[cfg(feature = "dotenv")]
+    if let Some(path) = args.env_file_path {
+        let path = resolve_path(args.base_dir, path).into_owned();
+        if let Some(names) = initialize_env_file(args, &path, &mut set_variables, env_guard)? {
+            preloaded_dotenvs.push((path, names));
+        }
+    }
+    #[cfg(feature = "dotenv")]
+    let dotenv_config = optional(
+        dotenv_source.kind().unwrap_or(SourceKind::DotEnv),
+        args.dotenv_inline.is_none() && optional_path(&dotenv_path),
+        initialize_dotenv_toml(
+            args,
+            &dotenv_path,
+            args.dotenv_inline.map(|inline| inline.content),
+            &mut dotenv_variables,
+            &mut set_variables,
+            env_guard,
+        )
+        .map(|config| config.map(|config| Layer::new(config, dotenv_source))),
+    )?;
+    #[cfg(feature = "dotenv")]
+    let dotenv_local_config = match args.dotenv_local_path {
+        Some(path) => {
+            let path = resolve_path(args.base_dir, path);
+            optional(
+                SourceKind::DotEnv,
+                optional_path(&path),
+                initialize_dotenv_toml(
+                    args,
+                    &path,
+                    None,
+                    &mut dotenv_variables,
+                    &mut set_variables,
+                    env_guard,
+                )
+                .map(|config| {
+                    config.map(|config| Layer::new(config, ConfigSource::DotEnv(path.into_owned())))
+                }),
+            )?
+        }
+        None => None,
+    };
+    #[cfg(not(feature = "dotenv"))]
+    let (dotenv_config, dotenv_local_config): (Option<Layer>, Option<Layer>) = (None, None);
+    // Variables which were overwritten by the `.env.toml` files are not reported as coming from
+    // the `.env` file.
+    #[cfg(feature = "dotenv")]
+    for (_, names) in &mut preloaded_dotenvs {
+        names.retain(|name| !dotenv_variables.contains_key(name));
+    }
+
+    let MappedVariables {
+        mut map_env,
+        string_variables,
+        list_delimiters,
+        number_inferences,
+    } = MappedVariables::new(args);
+
+    let env_value_options = EnvValueOptions::new(
+        args,
+        &string_variables,
+        &list_delimiters,
+        &number_inferences,
+    );
+    if let Some(path) = args.preloaded_dotenv_path {
+        let path = resolve_path(args.base_dir, path).into_owned();
+        let names = read_dotenv_variable_names(&path)?;
+        preloaded_dotenvs.push((path, names));
+    }
+
+    let mut unset_keys = Vec::new();
+    let mut preloaded_configs = Vec::new();
+    for (path, names) in preloaded_dotenvs {
+        let (preloaded_map_env, rest): (BTreeMap<_, _>, BTreeMap<_, _>) = map_env
+            .into_iter()
+            .partition(|(name, _)| names.contains(name.as_ref()));
+        map_env = rest;
+        let layer = initialize_env(
+            args.logging,
+            preloaded_map_env,
+            &env_value_options,
+            &mut unset_keys,
+        )?;
+        preloaded_configs.extend(layer.map(|mut layer| {
+            layer.source = ConfigSource::PreloadedDotEnv {
+                path,
+                variable_names: layer
+                    .variables
+                    .iter()
+                    .map(|(name, _)| name.clone())
+                    .collect(),
+            };
+            layer
+        }));
+    }
+    let env_config = initialize_env(args.logging, map_env, &env_value_options, &mut unset_keys)?;
+
+    let mut file_configs = Vec::new();
+    for path in args.config_paths.iter().copied().chain(config_path(args)) {
+        let path = resolve_path(args.base_dir, path);
+        file_configs.extend(optional(
+            SourceKind::File,
+            optional_path(&path),
+            load_config_file(&path),
+        )?);
+    }
+    if let Some(config_dir) = args.config_dir {
+        let config_dir = resolve_path(args.base_dir, config_dir);
+        let paths = match config_dir_paths(&config_dir) {
+            Ok(paths) => paths,
+            Err(error) => {
+                optional(SourceKind::File, optional_path(&config_dir), Err(error))?;
+                Vec::new()
+            }
+        };
+        for path in paths {
+            file_configs.extend(optional(
+                SourceKind::File,
+                optional_path(&path),
+                load_config_file(&path),
+            )?);
+        }
+    }
+
+    let defaults_config = optional(
+        SourceKind::Defaults,
+        false,
+        args.defaults
+            .map(|defaults| {
+                toml::from_str(defaults)
+                    .map(|config| Layer::new(config, ConfigSource::Defaults))
+                    .map_err(|error| InnerError::ErrorParsingDefaults {
+                        error: error.into(),
+                    })
+            })
+            .transpose(),
+    )?;
+
+    let inline_config = match read_inline_config(args) {
+        Ok(Some((content, source, label))) => optional(
+            source.kind().unwrap_or(SourceKind::Inline),
+            false,
+            toml::from_str(&content)
+                .map(|config| Some(Layer::new(config, source)))
+                .map_err(|error| InnerError::ErrorParsingInlineConfig {
+                    label,
+                    error: error.into(),
+                }),
+        ),
+        Ok(None) => Ok(None),
+        #[cfg(feature = "remote")]
+        Err(error @ InnerError::ErrorFetchingRemoteConfig { .. }) => {
+            optional(SourceKind::Remote, false, Err(error))
+        }
+        #[cfg(not(feature = "remote"))]
+        Err(error @ InnerError::RemoteFeatureDisabled { .. }) => {
+            optional(SourceKind::Remote, false, Err(error))
+        }
+        Err(error) => optional(SourceKind::Inline, false, Err(error)),
+    }?;
+
+    let mut after_defaults_configs = Vec::new();
+    let mut after_file_configs = Vec::new();
+    let mut highest_configs = Vec::new();
+    for source in &args.sources {
+        let description = source.description();
+        let kind = source.kind();
+        let layer = source
+            .load()
+            .map(|value| {
+                value.map(|value| {
+                    Layer::new(
+                        value,
+                        ConfigSource::Custom {
+                            description: description.clone(),
+                            kind,
+                        },
+                    )
+                })
+            })
+            .map_err(|error| InnerError::ErrorLoadingSource { description, error });
+        if let Some(layer) = optional(kind, source.optional(), layer)? {
+            match source.precedence() {
+                SourcePrecedence::AfterDefaults => after_defaults_configs.push(layer),
+                SourcePrecedence::AfterFile => after_file_configs.push(layer),
+                SourcePrecedence::Highest => highest_configs.push(layer),
+            }
+        }
+    }
+
+    // Ordered from lowest to highest precedence. Environment variables take precedence over the
+    // config file, defaults and most custom sources, so keys which they unset (see
+    // `Args::empty_values` and `Args::unset_sentinel`) are removed from those layers.
+    let mut layers: Vec<Layer> = defaults_config
+        .into_iter()
+        .chain(after_defaults_configs)
+        .chain(file_configs)
+        .chain(inline_config)
+        .chain(after_file_configs)
+        .map(|mut layer| {
+            for key in &unset_keys {
+                remove_toml_value(&mut layer.value, key);
+            }
+            layer
+        })
+        .collect();
+    layers.extend(
+        preloaded_configs
+            .into_iter()
+            .chain(env_config)
+            .chain(config_env_config)
+            .chain(dotenv_config)
+            .chain(dotenv_local_config)
+            .chain(highest_configs),
+    );
+    check_key_policies(&layers, &args.key_policies)?;
+    let config = merge_layers(&layers)?;
+
+    Ok(Loaded {
+        config,
+        layers,
+        loaded_at,
+        warnings,
+        #[cfg(feature = "dotenv")]
+        dotenv_variables,
+    })
+}
+
+/// Convert the `config` to a [`Value`] for logging, with the `redact_keys` redacted. Keys are
+/// sorted, unless the `preserve-order` feature is enabled, in which case they are kept in the
+/// declaration order of the fields of `C`.
+fn config_value<C: Serialize>(
+    config: &C,
+    redact_keys: &[TomlKeyPath],
+) -> std::result::Result<Value, toml::ser::Error> {
+    let mut config = Value::try_from(config)?;
+    redact(&mut config, redact_keys);
+    #[cfg(not(feature = "preserve-order"))]
+    let config = sort_keys(config);
+    Ok(config)
+}
+
+/// Count the values within `value`, where tables are recursed into and arrays count as a single
+/// value.
+#[cfg(feature = "pretty-logging")]
+fn count_values(value: &Value) -> usize {
+    match value {
+        Value::Table(table) => table.values().map(count_values).sum(),
+        _ => 1,
+    }
+}
+
+/// Copy of `value` containing only the first `remaining` values (see [`count_values()`]).
+#[cfg(feature = "pretty-logging")]
+fn take_values(value: &Value, remaining: &mut usize) -> Value {
+    match value {
+        Value::Table(table) => Value::Table(
+            table
+                .iter()
+                .map_while(|(key, value)| {
+                    if *remaining == 0 {
+                        return None;
+                    }
+                    let value = match value {
+                        Value::Table(_) => take_values(value, remaining),
+                        _ => {
+                            *remaining -= 1;
+                            value.clone()
+                        }
+                    };
+                    Some((key.clone(), value))
+                })
+                .collect(),
+        ),
+        _ => value.clone(),
+    }
+}
+
+/// Render the first `max_keys` values of `value` as TOML, reducing the number of values until
+/// the result fits within `max_bytes`, see [`ConfigLog::Truncated`].
+#[cfg(feature = "pretty-logging")]
+fn render_truncated(
+    value: &Value,
+    max_keys: Option<usize>,
+    max_bytes: Option<usize>,
+) -> std::result::Result<String, toml::ser::Error> {
+    let total = count_values(value);
+    let render = |mut keys: usize| toml::to_string_pretty(&take_values(value, &mut keys));
+
+    let mut keys = max_keys.map_or(total, |max_keys| max_keys.min(total));
+    let mut rendered = render(keys)?;
+    if let Some(max_bytes) = max_bytes {
+        if rendered.len() > max_bytes {
+            // Binary search for the largest number of values which fits, where `low` always fits
+            // and `keys` never does.
+            let mut low = 0;
+            while keys - low > 1 {
+                let mid = low + (keys - low) / 2;
+                if render(mid)?.len() <= max_bytes {
+                    low = mid;
+                } else {
+                    keys = mid;
+                }
+            }
+            keys = low;
+            rendered = render(keys)?;
+        }
+    }
+    if keys < total {
+        rendered.push_str(&format!("... {} more keys omitted\n", total - keys));
+    }
+    Ok(rendered)
+}
+
+/// Render the top level keys of `value` with the number of values within each of them, see
+/// [`ConfigLog::Summary`].
+#[cfg(feature = "pretty-logging")]
+fn render_summary(value: &Value) -> String {
+    let table = match value {
+        Value::Table(table) => table,
+        _ => return String::new(),
+    };
+    table
+        .iter()
+        .map(|(key, value)| {
+            let count = count_values(value);
+            let noun = if count == 1 { "key" } else { "keys" };
+            format!("{key}: {count} {noun}\n")
+        })
+        .collect()
+}
+
+/// A [`serde::Deserializer`] for the `value` at `key` in the merged configuration, which provides
+/// the key and source of [`WithSource`] fields, see
+/// [`InitializationReport::deserialize_with_sources()`].
+struct SourceDeserializer<'a> {
+    value: Value,
+    key: TomlKeyPath,
+    /// See [`InitializationReport::provenance`].
+    provenance: &'a BTreeMap<String, ConfigSource>,
+    /// Whether an error has already been annotated with the key where it occurred.
+    annotated: &'a std::cell::Cell<bool>,
+}
+
+impl<'a> SourceDeserializer<'a> {
+    /// A deserializer for the `value` at the `element` within this deserializer's value.
+    fn child(&self, element: PathElement, value: Value) -> Self {
+        let mut key = self.key.clone();
+        key.push(element);
+        SourceDeserializer {
+            value,
+            key,
+            provenance: self.provenance,
+            annotated: self.annotated,
+        }
+    }
+
+    /// Add the key to the innermost `error`, which is where it occurred.
+    fn annotate(&self, error: toml::de::Error) -> toml::de::Error {
+        if self.key.is_empty() || self.annotated.replace(true) {
+            return error;
+        }
+        serde::de::Error::custom(format!("{} for key `{}`", error.message(), self.key))
+    }
+
+    /// The description of the source of the value, from the closest key (arrays are a single
+    /// value in the provenance) which has a source.
+    fn source(&self) -> Option<String> {
+        let mut key = self.key.clone();
+        loop {
+            if let Some(source) = self.provenance.get(&key.to_string()) {
+                return Some(source.to_string());
+            }
+            key.pop()?;
+        }
+    }
+}
+
+impl<'de> serde::Deserializer<'de> for SourceDeserializer<'_> {
+    type Error = toml::de::Error;
+
+    fn deserialize_any<V>(mut self, visitor: V) -> std::result::Result<V::Value, Self::Error>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        // The value is moved out so that its entries aren't cloned.
+        let result = match std::mem::replace(&mut self.value, Value::Boolean(false)) {
+            Value::Table(table) => visitor.visit_map(SourceAccess::new(
+                &self,
+                table.into_iter().map(|(key, value)| (key.into(), value)),
+            )),
+            Value::Array(array) => visitor.visit_seq(SourceAccess::new(
+                &self,
+                array
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, value)| (index.into(), value)),
+            )),
+            value => value.deserialize_any(visitor),
+        };
+        result.map_err(|error| self.annotate(error))
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> std::result::Result<V::Value, Self::Error>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> std::result::Result<V::Value, Self::Error>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        if name != WITH_SOURCE {
+            return visitor.visit_newtype_struct(self);
+        }
+        let key = self.key.to_string();
+        let source = self.source();
+        let mut elements = vec![Value::String(key)];
+        elements.extend(source.map(Value::String));
+        visitor.visit_seq(WithSourceAccess {
+            value: Some(self),
+            elements: elements.into_iter(),
+        })
+    }
+
+    fn deserialize_enum<V>(
+        mut self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> std::result::Result<V::Value, Self::Error>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        let value = std::mem::replace(&mut self.value, Value::Boolean(false));
+        let result = value.deserialize_enum(name, variants, visitor);
+        result.map_err(|error| self.annotate(error))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+        unit unit_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+/// The entries of a table or array being deserialized by a [`SourceDeserializer`].
+struct SourceAccess<'a, 'b> {
+    parent: &'b SourceDeserializer<'a>,
+    /// The remaining entries, in reverse order.
+    entries: Vec<(PathElement, Value)>,
+    /// The entry whose key was visited and whose value is next.
+    value: Option<(PathElement, Value)>,
+}
+
+impl<'a, 'b> SourceAccess<'a, 'b> {
+    fn new(
+        parent: &'b SourceDeserializer<'a>,
+        entries: impl Iterator<Item = (PathElement, Value)>,
+    ) -> Self {
+        let mut entries: Vec<_> = entries.collect();
+        entries.reverse();
+        SourceAccess {
+            parent,
+            entries,
+            value: None,
+        }
+    }
+}
+
+impl<'de> serde::de::MapAccess<'de> for SourceAccess<'_, '_> {
+    type Error = toml::de::Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> std::result::Result<Option<K::Value>, Self::Error>
+    where
+        K: serde::de::DeserializeSeed<'de>,
+    {
+        let (element, value) = match self.entries.pop() {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+        let key = seed.deserialize(Value::String(element.to_string()))?;
+        self.value = Some((element, value));
+        Ok(Some(key))
+    }
+
+    fn next_value_seed<T>(&mut self, seed: T) -> std::result::Result<T::Value, Self::Error>
+    where
+        T: serde::de::DeserializeSeed<'de>,
+    {
+        let (element, value) = self
+            .value
+            .take()
+            .ok_or_else(|| serde::de::Error::custom("value is missing"))?;
+        seed.deserialize(self.parent.child(element, value))
+    }
+}
+
+impl<'de> serde::de::SeqAccess<'de> for SourceAccess<'_, '_> {
+    type Error = toml::de::Error;
+
+    fn next_element_seed<T>(
+        &mut self,
+        seed: T,
+    ) -> std::result::Result<Option<T::Value>, Self::Error>
+    where
+        T: serde::de::DeserializeSeed<'de>,
+    {
+        match self.entries.pop() {
+            Some((element, value)) => seed
+                .deserialize(self.parent.child(element, value))
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+/// The value, key and source (if known) visited by a [`WithSource`], see
+/// [`SourceDeserializer::deserialize_newtype_struct()`].
+struct WithSourceAccess<'a> {
+    value: Option<SourceDeserializer<'a>>,
+    elements: std::vec::IntoIter<Value>,
+}
+
+impl<'de> serde::de::SeqAccess<'de> for WithSourceAccess<'_> {
+    type Error = toml::de::Error;
+
+    fn next_element_seed<T>(
+        &mut self,
+        seed: T,
+    ) -> std::result::Result<Option<T::Value>, Self::Error>
+    where
+        T: serde::de::DeserializeSeed<'de>,
+    {
+        match self.value.take() {
+            Some(value) => seed.deserialize(value).map(Some),
+            None => self
+                .elements
+                .next()
+                .map(|element| seed.deserialize(element))
+                .transpose(),
+        }
+    }
+}
+
+/// Render the `config` as TOML for logging according to `config_log` (see [`config_value()`]).
+/// Tables are always placed after the other values in a table.
+#[cfg(feature = "pretty-logging")]
+fn render_config_log<C: Serialize>(
+    config: &C,
+    redact_keys: &[TomlKeyPath],
+    config_log: ConfigLog,
+) -> std::result::Result<String, toml::ser::Error> {
+    let value = config_value(config, redact_keys)?;
+    match config_log {
+        ConfigLog::Full => toml::to_string_pretty(&value),
+        ConfigLog::Truncated {
+            max_keys,
+            max_bytes,
+        } => render_truncated(&value, max_keys, max_bytes),
+        ConfigLog::Summary => Ok(render_summary(&value)),
+    }
+}
+
+/// Deserialize the merged configuration, and log the result.
+fn deserialize_loaded<C>(
+    config: Option<(Value, ConfigSource)>,
+    args: &Args<'_>,
+) -> InnerResult<Option<C>>
+where
+    C: DeserializeOwned + Serialize,
+{
+    let config = Option::transpose(config.map(|(config, source)| {
+        deserialize_config(config).map_err(|error| InnerError::ErrorParsingMergedToml {
+            source,
+            error: error.into(),
+        })
+    }))?;
+
+    if let Some(config) = &config {
+        log_config(config, args);
+    }
+
+    Ok(config)
+}
+
+/// Log the parsed `config`, according to [`Args::config_log`] (in full without the
+/// `pretty-logging` feature).
+fn log_config<C: Serialize>(config: &C, args: &Args<'_>) {
+    let logging = args.logging;
+    if matches!(logging, Logging::None) {
+        return;
+    }
+    #[cfg(feature = "pretty-logging")]
+    let rendered = render_config_log(config, &args.redact, args.config_log);
+    #[cfg(not(feature = "pretty-logging"))]
+    let rendered =
+        config_value(config, &args.redact).and_then(|value| toml::to_string_pretty(&value));
+    match rendered {
+        Ok(config_string) => log_info(
+            logging,
+            format_args!("Parsed configuration:\n{HIGHLIGHT}{config_string}{RESET}"),
+        ),
+        // e.g. for unit targets like `()` which cannot be represented in TOML.
+        Err(error) => log_info(
+            logging,
+            format_args!("Parsed configuration (unable to display as TOML: {error})"),
+        ),
+    }
+}
+
+/// Initialize [`env_logger`] using the `log.level` and `log.format` keys of a configuration
+/// `value`, before it is deserialized. This allows the logger to be configured using the same
+/// sources as the rest of the configuration, see [`Args::init_logger`].
+///
+/// + `log.level` A filter in the format of the `RUST_LOG` environment variable (e.g. `info` or
+///   `warn,my_app=debug`). If not specified, the `RUST_LOG` environment variable is used.
+/// + `log.format` Either `full` (the default) or `compact`, which omits the timestamp and
+///   module.
+///
+/// Returns an error if the keys have unexpected values, or a logger has already been
+/// initialized.
+#[cfg(feature = "log-init")]
+pub fn init_logger(value: &Value) -> Result<()> {
+    let mut builder = env_logger::Builder::from_default_env();
+
+    match value.get("log").and_then(|log| log.get("level")) {
+        None => {}
+        Some(Value::String(level)) => {
+            builder.parse_filters(level);
+        }
+        Some(level) => {
+            return Err(InnerError::InvalidLoggerConfig {
+                key: "log.level",
+                value: level.clone(),
+                expected: "a string",
+            }
+            .into())
+        }
+    }
+
+    match value.get("log").and_then(|log| log.get("format")) {
+        None => {}
+        Some(Value::String(format)) if format == "full" => {}
+        Some(Value::String(format)) if format == "compact" => {
+            builder.format_timestamp(None).format_target(false);
+        }
+        Some(format) => {
+            return Err(InnerError::InvalidLoggerConfig {
+                key: "log.format",
+                value: format.clone(),
+                expected: "\"full\" or \"compact\"",
+            }
+            .into())
+        }
+    }
+
+    builder
+        .try_init()
+        .map_err(|error| InnerError::ErrorInitializingLogger { error })?;
+    Ok(())
+}
+
+/// Call [`init_logger()`] with the loaded configuration if [`Args::init_logger`] is enabled.
+#[cfg(feature = "log-init")]
+fn init_logger_loaded(args: &Args<'_>, loaded: &Loaded) -> Result<()> {
+    if !args.init_logger {
+        return Ok(());
+    }
+    match &loaded.config {
+        Some((value, _)) => init_logger(value),
+        None => init_logger(&Value::Table(toml::Table::new())),
+    }
+}
+
+/// Initialize configuration from available sources specified in [`Args`].
+///
+/// If no configuration was found, will return `None`.
+///
+/// A unit type (such as `()`) can be used for `C` if only the side effects of initialization are
+/// required (setting environment variables from the `.env.toml`, validation of the
+/// configuration sources), the contents of the configuration are ignored.
+///
+/// See [`toml-env`](crate).
+pub fn initialize<C>(args: Args<'_>) -> Result<Option<C>>
+where
+    C: DeserializeOwned + Serialize,
+{
+    let loaded = load(&args)?;
+    #[cfg(feature = "log-init")]
+    init_logger_loaded(&args, &loaded)?;
+    Ok(deserialize_loaded(loaded.config, &args)?)
+}
+
+/// Records the previous values of environment variables which are changed using it, and restores
+/// them when it is dropped, see [`initialize_scoped()`]. Variables which were not previously set
+/// are removed.
+///
+/// This makes temporary changes to the environment safe in tests and in tools which load
+/// configuration repeatedly. Note that the environment is shared by the whole process, so
+/// changes are still visible to (and may race with) other threads while the guard is alive.
+///
+/// # Example
+///
+/// ```rust
+/// use toml_env::EnvGuard;
+///
+/// std::env::set_var("ENV_GUARD_EXAMPLE_KEPT", "before");
+/// std::env::remove_var("ENV_GUARD_EXAMPLE_NEW");
+/// {
+///     let mut guard = EnvGuard::new();
+///     guard.set_var("ENV_GUARD_EXAMPLE_KEPT", "during");
+///     guard.set_var("ENV_GUARD_EXAMPLE_NEW", "during");
+///     assert_eq!(std::env::var("ENV_GUARD_EXAMPLE_KEPT").unwrap(), "during");
+/// }
+/// assert_eq!(std::env::var("ENV_GUARD_EXAMPLE_KEPT").unwrap(), "before");
+/// assert!(std::env::var("ENV_GUARD_EXAMPLE_NEW").is_err());
+/// ```
+#[derive(Debug, Default)]
+#[must_use = "the environment is restored when the guard is dropped"]
+pub struct EnvGuard {
+    /// The previous value of each variable which was changed, in the order they were changed.
+    previous: Vec<(OsString, Option<OsString>)>,
+}
+
+impl EnvGuard {
+    /// Create a guard which has not changed any environment variables yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the environment variable `key` to `value`, restoring its previous value when the guard
+    /// is dropped.
+    pub fn set_var(&mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) {
+        let key = key.as_ref();
+        self.previous.push((key.to_owned(), std::env::var_os(key)));
+        std::env::set_var(key, value);
+    }
+
+    /// Remove the environment variable `key`, restoring its previous value when the guard is
+    /// dropped.
+    pub fn remove_var(&mut self, key: impl AsRef<OsStr>) {
+        let key = key.as_ref();
+        self.previous.push((key.to_owned(), std::env::var_os(key)));
+        std::env::remove_var(key);
+    }
+
+    /// Keep the changes made to the environment instead of restoring them.
+    pub fn keep(mut self) {
+        self.previous.clear();
+    }
+}
+
+impl Drop for EnvGuard {
+    fn drop(&mut self) {
+        // In reverse order, so that a variable changed more than once ends up with its original
+        // value.
+        for (key, value) in self.previous.drain(..).rev() {
+            match value {
+                Some(value) => std::env::set_var(key, value),
+                None => std::env::remove_var(key),
+            }
+        }
+    }
+}
+
+/// The same as [`initialize()`], but environment variables set from the `.env.toml` file are
+/// restored to their previous values when the returned [`EnvGuard`] is dropped (or if
+/// initialization fails), instead of being set permanently.
+///
+/// # Example
+///
+/// ```rust
+/// use toml_env::{initialize_scoped, Args, InlineSource};
+///
+/// std::env::remove_var("SCOPED_EXAMPLE_TOKEN");
+/// let (_config, guard) = initialize_scoped::<toml::Value>(Args {
+///     dotenv_inline: Some(InlineSource {
+///         label: "example",
+///         content: "SCOPED_EXAMPLE_TOKEN = \"secret\"",
+///     }),
+///     ..Args::default()
+/// })
+/// .unwrap();
+/// assert_eq!(std::env::var("SCOPED_EXAMPLE_TOKEN").unwrap(), "secret");
+/// drop(guard);
+/// assert!(std::env::var("SCOPED_EXAMPLE_TOKEN").is_err());
+/// ```
+#[cfg(feature = "dotenv")]
+pub fn initialize_scoped<C>(args: Args<'_>) -> Result<(Option<C>, EnvGuard)>
+where
+    C: DeserializeOwned + Serialize,
+{
+    let mut env_guard = EnvGuard::new();
+    let loaded = load_with_env_guard(&args, &mut env_guard)?;
+    #[cfg(feature = "log-init")]
+    init_logger_loaded(&args, &loaded)?;
+    let config = deserialize_loaded(loaded.config, &args)?;
+    Ok((config, env_guard))
+}
+
+/// The same as [`initialize()`], but with the contents of the `.env.toml` file and the config
+/// file supplied as strings (see [`Args::dotenv_inline`] and [`Args::config_inline`]), for tests
+/// and configuration embedded in a binary. If `dotenv` is `None` the [`Args::dotenv_inline`] from
+/// the `args` is used (the [`Args::dotenv_path`] is never read), and if `config` is `None` the
+/// [`Args::config_inline`] or [`Args::config_path`] from the `args` is used.
+///
+/// # Example
+///
+/// ```rust
+/// use toml_env::{initialize_str, Args};
+///
+/// let config: toml::Value = initialize_str(
+///     None,
+///     Some("[server]\nport = 8080"),
+///     Args::default(),
+/// )
+/// .unwrap()
+/// .unwrap();
+/// assert_eq!(config["server"]["port"].as_integer(), Some(8080));
+/// ```
+#[cfg(feature = "dotenv")]
+pub fn initialize_str<'a, C>(
+    dotenv: Option<&'a str>,
+    config: Option<&'a str>,
+    args: Args<'a>,
+) -> Result<Option<C>>
+where
+    C: DeserializeOwned + Serialize,
+{
+    let config_inline = match config {
+        Some(content) => Some(InlineSource {
+            label: "config",
+            content,
+        }),
+        None => args.config_inline,
+    };
+    let dotenv_inline = match dotenv {
+        Some(content) => InlineSource {
+            label: "dotenv",
+            content,
+        },
+        None => args.dotenv_inline.unwrap_or(InlineSource {
+            label: "dotenv",
+            content: "",
+        }),
+    };
+    initialize(Args {
+        dotenv_inline: Some(dotenv_inline),
+        config_inline,
+        ..args
+    })
+}
+
+/// The same as [`initialize()`], but also returns an [`InitializationReport`] describing where
+/// the configuration came from.
+pub fn initialize_with_report<C>(args: Args<'_>) -> Result<(Option<C>, InitializationReport)>
+where
+    C: DeserializeOwned + Serialize,
+{
+    let loaded = load(&args)?;
+    #[cfg(feature = "log-init")]
+    init_logger_loaded(&args, &loaded)?;
+    let report = InitializationReport {
+        source: loaded.config.as_ref().map(|(_, source)| source.clone()),
+        provenance: loaded
+            .config
+            .as_ref()
+            .map(|(value, _)| provenance(value, &loaded.layers))
+            .unwrap_or_default(),
+        value: loaded.config.as_ref().map(|(value, _)| value.clone()),
+        loaded_at: loaded.loaded_at,
+        warnings: loaded.warnings,
+        variable_names: variable_names(&loaded.layers),
+        sources: source_freshness(&loaded.layers),
+        #[cfg(feature = "dotenv")]
+        dotenv_variables: loaded.dotenv_variables,
+        redact: args.redact.clone(),
+    };
+    let config = deserialize_loaded(loaded.config, &args)?;
+    Ok((config, report))
+}
+
+/// The same as [`initialize()`], but with the config file read from the `reader` (e.g. a pipe or
+/// a socket), instead of the [`Args::config_location`] or [`Args::config_path`]. The `label`
+/// describes where the configuration came from, see [`InlineSource::label`].
+///
+/// # Example
+///
+/// ```rust
+/// use toml_env::{initialize_reader, Args};
+///
+/// let reader = std::io::Cursor::new("[server]\nport = 8080");
+/// let config: toml::Value = initialize_reader(reader, "socket", Args::default())
+///     .unwrap()
+///     .unwrap();
+/// assert_eq!(config["server"]["port"].as_integer(), Some(8080));
+/// ```
+pub fn initialize_reader<C>(
+    mut reader: impl std::io::Read,
+    label: &str,
+    args: Args<'_>,
+) -> Result<Option<C>>
+where
+    C: DeserializeOwned + Serialize,
+{
+    let mut content = String::new();
+    reader
+        .read_to_string(&mut content)
+        .map_err(|error| InnerError::ErrorReadingConfig {
+            label: label.to_owned(),
+            error,
+        })?;
+    initialize(Args {
+        config_location: Some(ConfigLocation::Inline(InlineSource {
+            label,
+            content: &content,
+        })),
+        ..args
+    })
+}
+
+/// Constructors for configuration types, as a type-first alternative to [`initialize()`].
+/// Implemented for all types which can be used with [`initialize()`].
+///
+/// # Example
+///
+/// ```rust
+/// use serde::{Deserialize, Serialize};
+/// use toml_env::{map_env, Args, FromTomlEnv};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Config {
+///     name: String,
+/// }
+///
+/// std::env::set_var("FROM_TOML_ENV_EXAMPLE", "example");
+/// let config = Config::from_args(Args {
+///     map_env: map_env! { "FROM_TOML_ENV_EXAMPLE" => "name" },
+///     ..Args::default()
+/// })
+/// .unwrap()
+/// .unwrap();
+/// assert_eq!(config.name, "example");
+/// ```
+pub trait FromTomlEnv: Sized {
+    /// Initialize using the default [`Args`], see [`initialize()`].
+    fn from_env() -> Result<Option<Self>>;
+    /// Initialize using the `args`, see [`initialize()`].
+    fn from_args(args: Args<'_>) -> Result<Option<Self>>;
+    /// Initialize using the `args`, also returning an [`InitializationReport`] describing the
+    /// layers which were merged to produce the configuration, see [`initialize_with_report()`].
+    fn layers(args: Args<'_>) -> Result<(Option<Self>, InitializationReport)>;
+}
+
+impl<C> FromTomlEnv for C
+where
+    C: DeserializeOwned + Serialize,
+{
+    fn from_env() -> Result<Option<Self>> {
+        initialize(Args::default())
+    }
+
+    fn from_args(args: Args<'_>) -> Result<Option<Self>> {
+        initialize(args)
+    }
+
+    fn layers(args: Args<'_>) -> Result<(Option<Self>, InitializationReport)> {
+        initialize_with_report(args)
+    }
+}
+
+/// The same as [`initialize()`], but returns the merged configuration as a [`toml::Value`]
+/// without deserializing it, for applications which don't have a single configuration struct
+/// (e.g. with plugins that deserialize their own sections). Unlike `initialize::<toml::Value>()`,
+/// datetimes are kept as [`toml::value::Datetime`] values.
+///
+/// # Example
+///
+/// ```rust
+/// use toml_env::{initialize_value, map_env, Args};
+///
+/// std::env::set_var("INITIALIZE_VALUE_EXAMPLE", "Hello World");
+/// let value = initialize_value(Args {
+///     map_env: map_env! { "INITIALIZE_VALUE_EXAMPLE" => "plugin.greeting" },
+///     ..Args::default()
+/// })
+/// .unwrap()
+/// .unwrap();
+/// assert_eq!(value["plugin"]["greeting"].as_str(), Some("Hello World"));
+/// ```
+pub fn initialize_value(args: Args<'_>) -> Result<Option<Value>> {
+    let loaded = load(&args)?;
+    #[cfg(feature = "log-init")]
+    init_logger_loaded(&args, &loaded)?;
+    let value = loaded.config.map(|(value, _)| value);
+    if let Some(value) = &value {
+        log_config(value, &args);
+    }
+    Ok(value)
+}
+
+/// The merged configuration loaded by [`initialize_multi()`], which configuration types owned by
+/// different parts of an application can be deserialized from, without loading the configuration
+/// sources (and setting environment variables from the `.env.toml` file) again for each type.
+#[derive(Debug, Clone)]
+pub struct MultiConfig {
+    /// The merged configuration, `None` if no configuration was found.
+    value: Option<Value>,
+}
+
+impl MultiConfig {
+    /// Deserialize the value at `key` (see [`TomlKeyPath`], e.g. `db` for the `[db]` table) into
+    /// `C`, or the whole configuration if the `key` is empty. Returns `None` if no configuration
+    /// was found, or there is no value at the `key`.
+    pub fn get<C: DeserializeOwned>(&self, key: &str) -> Result<Option<C>> {
+        let key: TomlKeyPath = key
+            .parse()
+            .map_err(|error| InnerError::InvalidKeyPath { error })?;
+        let value = match self.value.as_ref().and_then(|value| key.resolve(value)) {
+            Some(value) => value.clone(),
+            None => return Ok(None),
+        };
+        deserialize_config(value).map(Some).map_err(|error| {
+            InnerError::ErrorDeserializingValue {
+                key: Box::new(key),
+                error: Box::new(error),
+            }
+            .into()
+        })
+    }
+
+    /// The merged configuration, `None` if no configuration was found.
+    pub fn value(&self) -> Option<&Value> {
+        self.value.as_ref()
+    }
+}
+
+/// Load and merge the configuration sources once (see [`initialize()`]), returning a
+/// [`MultiConfig`] which several configuration types can be deserialized from, using different
+/// top level tables (or key paths) of the merged configuration.
+///
+/// # Example
+///
+/// ```rust
+/// use serde::Deserialize;
+/// use toml_env::{initialize_multi, map_env, Args};
+///
+/// #[derive(Deserialize)]
+/// struct DbConfig {
+///     url: String,
+/// }
+///
+/// #[derive(Deserialize)]
+/// struct TelemetryConfig {
+///     endpoint: String,
+/// }
+///
+/// std::env::set_var("MULTI_EXAMPLE_DB_URL", "postgres://localhost");
+/// std::env::set_var("MULTI_EXAMPLE_TELEMETRY_ENDPOINT", "http://localhost:4317");
+/// let config = initialize_multi(Args {
+///     map_env: map_env! {
+///         "MULTI_EXAMPLE_DB_URL" => "db.url",
+///         "MULTI_EXAMPLE_TELEMETRY_ENDPOINT" => "telemetry.endpoint",
+///     },
+///     ..Args::default()
+/// })
+/// .unwrap();
+/// let db: DbConfig = config.get("db").unwrap().unwrap();
+/// let telemetry: TelemetryConfig = config.get("telemetry").unwrap().unwrap();
+/// assert_eq!(db.url, "postgres://localhost");
+/// assert_eq!(telemetry.endpoint, "http://localhost:4317");
+/// ```
+pub fn initialize_multi(args: Args<'_>) -> Result<MultiConfig> {
+    Ok(MultiConfig {
+        value: initialize_value(args)?,
+    })
+}
+
+/// Initialize configuration only from the `mappings` of environment variables to config keys (see
+/// [`Args::map_env`]), reading their values from an explicit `environment` snapshot (e.g. collected
+/// from [`std::env::vars()`] at startup, or constructed in a unit test). This does not touch the
+/// filesystem or the process environment at all, so it can be used in sandboxed processes (e.g.
+/// with seccomp or Landlock restrictions). Values are parsed using the same type inference as
+/// [`initialize()`] with the default [`Args`]. Returns `None` if there are no `mappings`.
+///
+/// # Example
+///
+/// ```rust
+/// use std::collections::HashMap;
+///
+/// let environment: HashMap<String, String> =
+///     [("HOST".to_owned(), "localhost".to_owned())].into_iter().collect();
+/// let config: toml::Value =
+///     toml_env::initialize_env_only(toml_env::map_env! { "HOST" => "server.host" }, &environment)
+///         .unwrap()
+///         .unwrap();
+/// assert_eq!(config["server"]["host"].as_str(), Some("localhost"));
+/// ```
+pub fn initialize_env_only<C>(
+    mappings: HashMap<&str, TomlKeyPath>,
+    environment: &HashMap<String, String>,
+) -> Result<Option<C>>
+where
+    C: DeserializeOwned + Serialize,
+{
+    let args = Args {
+        map_env: mappings,
+        ..Args::default()
+    };
+    let (string_variables, list_delimiters, number_inferences) = Default::default();
+    let options = EnvValueOptions {
+        environment: Some(environment),
+        ..EnvValueOptions::new(
+            &args,
+            &string_variables,
+            &list_delimiters,
+            &number_inferences,
+        )
+    };
+    let map_env = args
+        .map_env
+        .iter()
+        .map(|(key, value)| (Cow::Borrowed(*key), value.clone()))
+        .collect();
+    let layer = initialize_env(Logging::None, map_env, &options, &mut Vec::new())?;
+    Ok(deserialize_loaded(
+        layer.map(|layer| (layer.value, layer.source)),
+        &args,
+    )?)
+}
+
+/// A synthetic test case for the mapping of an environment variable to a key in the
+/// configuration, see [`mapping_contract()`].
+#[derive(Debug, Clone)]
+pub struct MappingCase {
+    /// Name of the environment variable.
+    pub variable_name: String,
+    /// A sample value for the environment variable, based on the type of the key in the default
+    /// configuration.
+    pub sample_value: String,
+    /// The key in the configuration that the environment variable is mapped to.
+    pub key: TomlKeyPath,
+    /// The type (see [`toml::Value::type_str()`]) that the sample value is parsed as.
+    pub expected_type: &'static str,
+}
+
+impl std::fmt::Display for MappingCase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}={:?} -> {} ({})",
+            self.variable_name, self.sample_value, self.key, self.expected_type
+        )
+    }
+}
+
+/// Generate a [`MappingCase`] for each environment variable mapped by `args` (see
+/// [`Args::map_env`] and [`Args::auto_map_env`]), sorted by variable name. The sample values are
+/// chosen using the types of the keys in `C::default()`, and are parsed the same way as by
+/// [`initialize()`] with these `args`.
+///
+/// The cases can be stored alongside the tests of an application, and compared against in a test
+/// to detect accidental changes to how environment variables are mapped (e.g. after upgrading
+/// this library, or changing [`Args`]).
+///
+/// # Example
+///
+/// ```rust
+/// use serde::{Deserialize, Serialize};
+/// use toml_env::{map_env, mapping_contract, Args};
+///
+/// #[derive(Default, Serialize, Deserialize)]
+/// struct Config {
+///     port: u16,
+///     debug: bool,
+/// }
+///
+/// let args = Args {
+///     map_env: map_env! { "PORT" => "port", "DEBUG" => "debug" },
+///     schema: Some(toml::Value::try_from(Config::default()).unwrap()),
+///     ..Args::default()
+/// };
+/// let contract: Vec<String> = mapping_contract::<Config>(&args)
+///     .unwrap()
+///     .iter()
+///     .map(ToString::to_string)
+///     .collect();
+/// assert_eq!(
+///     contract,
+///     [
+///         "DEBUG=\"true\" -> debug (boolean)",
+///         "PORT=\"42\" -> port (integer)",
+///     ]
+/// );
+/// ```
+pub fn mapping_contract<C>(args: &Args<'_>) -> Result<Vec<MappingCase>>
+where
+    C: Default + Serialize,
+{
+    let defaults =
+        Value::try_from(C::default()).map_err(|error| InnerError::ErrorSerializingDefaults {
+            error: Box::new(error),
+        })?;
+    let variables = MappedVariables::new(args);
+    let options = EnvValueOptions::new(
+        args,
+        &variables.string_variables,
+        &variables.list_delimiters,
+        &variables.number_inferences,
+    );
+
+    let mut cases = Vec::with_capacity(variables.map_env.len());
+    for (variable_name, key) in &variables.map_env {
+        let sample_value = match key.resolve(&defaults) {
+            Some(Value::Integer(_)) => "42".to_owned(),
+            Some(Value::Float(_)) => "1.5".to_owned(),
+            Some(Value::Boolean(_)) => "true".to_owned(),
+            Some(Value::Datetime(_)) => "1979-05-27T07:32:00Z".to_owned(),
+            Some(value @ (Value::Array(_) | Value::Table(_))) => value.to_string(),
+            Some(Value::String(_)) | None => "sample".to_owned(),
+        };
+        let value = parse_env_value(variable_name, sample_value.clone(), key, &options)?;
+        cases.push(MappingCase {
+            variable_name: variable_name.to_string(),
+            sample_value,
+            key: key.clone(),
+            expected_type: value.type_str(),
+        });
+    }
+    Ok(cases)
+}
+
+/// The freshness of each of the `layers`, see [`InitializationReport::sources`].
+fn source_freshness(layers: &[Layer]) -> Vec<SourceFreshness> {
+    layers
+        .iter()
+        .map(|layer| {
+            let path = match &layer.source {
+                ConfigSource::DotEnv(path)
+                | ConfigSource::File(path)
+                | ConfigSource::PreloadedDotEnv { path, .. } => Some(path),
+                _ => None,
+            };
+            SourceFreshness {
+                source: layer.source.clone(),
+                read_at: layer.read_at,
+                modified: path
+                    .and_then(|path| std::fs::metadata(path).and_then(|m| m.modified()).ok()),
+            }
+        })
+        .collect()
+}
+
+/// The names of the environment variables which were read by the `layers`, sorted by name.
+fn variable_names(layers: &[Layer]) -> Vec<String> {
+    let variable_names: BTreeSet<&String> = layers
+        .iter()
+        .flat_map(|layer| match &layer.source {
+            ConfigSource::Environment { variable_names }
+            | ConfigSource::PreloadedDotEnv { variable_names, .. } => variable_names.as_slice(),
+            _ => &[],
+        })
+        .collect();
+    variable_names.into_iter().cloned().collect()
+}
+
+/// The same as [`initialize()`], but returns the merged configuration as JSON (see
+/// [`to_json()`]), for handing to libraries which only accept JSON.
+#[cfg(feature = "json")]
+pub fn initialize_json(args: Args<'_>) -> Result<Option<serde_json::Value>> {
+    let loaded = load(&args)?;
+    #[cfg(feature = "log-init")]
+    init_logger_loaded(&args, &loaded)?;
+    let config: Option<Value> = deserialize_loaded(loaded.config, &args)?;
+    Ok(config.as_ref().map(to_json))
+}
+
+/// Convert a TOML `value` into the equivalent JSON value. Datetimes are converted into strings,
+/// and floats which cannot be represented in JSON (`nan` and `inf`) are converted into `null`.
+///
+/// # Example
+///
+/// ```rust
+/// let value: toml::Value = toml::from_str("[server]\nport = 8080").unwrap();
+/// let json = toml_env::to_json(&value);
+/// assert_eq!(json["server"]["port"], 8080);
+/// ```
+#[cfg(feature = "json")]
+pub fn to_json(value: &Value) -> serde_json::Value {
+    match value {
+        Value::String(value) => value.clone().into(),
+        Value::Integer(value) => (*value).into(),
+        Value::Float(value) => serde_json::Number::from_f64(*value)
+            .map(serde_json::Value::Number)
+            .unwrap_or(serde_json::Value::Null),
+        Value::Boolean(value) => (*value).into(),
+        Value::Datetime(value) => value.to_string().into(),
+        Value::Array(array) => array.iter().map(to_json).collect(),
+        Value::Table(table) => serde_json::Value::Object(
+            table
+                .iter()
+                .map(|(key, value)| (key.clone(), to_json(value)))
+                .collect(),
+        ),
+    }
+}
+
+/// When a source of configuration was read, see [`InitializationReport::sources`].
+#[derive(Debug, Clone)]
+pub struct SourceFreshness {
+    /// The source which was read.
+    pub source: ConfigSource,
+    /// The time at which the source was read. For environment variables, this is the time at
+    /// which their values were captured.
+    pub read_at: SystemTime,
+    /// The time at which the file was last modified, for sources which are read from a file.
+    /// `None` for other sources, or if the modification time is not available on this platform.
+    pub modified: Option<SystemTime>,
+}
+
+impl SourceFreshness {
+    /// The time elapsed since the source was read, e.g. to alert if a long-running service
+    /// has not reloaded its configuration recently enough.
+    pub fn age(&self) -> Duration {
+        self.read_at.elapsed().unwrap_or_default()
+    }
+}
+
+/// A report describing the configuration loaded by [`initialize_with_report()`].
+#[derive(Debug, Clone)]
+pub struct InitializationReport {
+    /// The source(s) of the configuration, `None` if no configuration was found.
+    pub source: Option<ConfigSource>,
+    /// The merged configuration before it was deserialized, `None` if no configuration was found.
+    pub value: Option<Value>,
+    /// The source of each value in the merged configuration, keyed by the path to the value (in
+    /// the format of [`TomlKeyPath`]). Arrays are considered to be a single value.
+    pub provenance: BTreeMap<String, ConfigSource>,
+    /// The time at which the configuration was loaded.
+    pub loaded_at: SystemTime,
+    /// Errors from sources which were skipped (see [`Args::optional_sources`] and
+    /// [`Args::missing_key_path`]).
+    pub warnings: Vec<SourceWarning>,
+    /// The names of the environment variables which were read to produce the configuration
+    /// (including the [`Args::config_variable_name`] variable if it was set), sorted by name.
+    pub variable_names: Vec<String>,
+    /// When each of the sources which were merged to produce the configuration was read (and
+    /// last modified, for files), ordered from lowest to highest precedence.
+    pub sources: Vec<SourceFreshness>,
+    /// The top level entries in the `.env.toml` file other than the configuration table (which
+    /// are set as environment variables), with their values as they were parsed from the file.
+    /// Empty if there is no `.env.toml` file.
+    #[cfg(feature = "dotenv")]
+    pub dotenv_variables: toml::Table,
+    /// See [`Args::redact`].
+    redact: Vec<TomlKeyPath>,
+}
+
+impl InitializationReport {
+    /// Create a description of the effective configuration with the values of keys in
+    /// [`Args::redact`] hidden, intended to be served as JSON (using a [`serde`] serializer of
+    /// your choice) from an endpoint like `/debug/config`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use serde::{Deserialize, Serialize};
+    /// use toml_env::{initialize_with_report, map_env, Args};
+    ///
+    /// #[derive(Serialize, Deserialize)]
+    /// struct Config {
+    ///     user: String,
+    ///     password: String,
+    /// }
+    ///
+    /// std::env::set_var("DESCRIBE_USER", "admin");
+    /// std::env::set_var("DESCRIBE_PASSWORD", "hunter2");
+    ///
+    /// let (_config, report) = initialize_with_report::<Config>(Args {
+    ///     map_env: map_env! {
+    ///         "DESCRIBE_USER" => "user",
+    ///         "DESCRIBE_PASSWORD" => "password",
+    ///     },
+    ///     redact: vec!["password".parse().unwrap()],
+    ///     ..Args::default()
+    /// })
+    /// .unwrap();
+    ///
+    /// let description = report.describe();
+    /// assert_eq!(description.config["user"].as_str(), Some("admin"));
+    /// assert_eq!(description.config["password"].as_str(), Some(toml_env::REDACTED));
+    /// assert_eq!(
+    ///     description.sources["password"],
+    ///     "environment variables DESCRIBE_PASSWORD"
+    /// );
+    /// ```
+    pub fn describe(&self) -> ConfigDescription {
+        let mut config = self
+            .value
+            .clone()
+            .unwrap_or_else(|| Value::Table(toml::Table::new()));
+        // The fingerprint is of the redacted configuration, because a fast unkeyed hash of the
+        // secrets would allow them to be guessed offline.
+        redact(&mut config, &self.redact);
+        let config = datetimes_to_strings(sort_keys(config));
+
+        ConfigDescription {
+            fingerprint: format!("{:016x}", fnv1a(config.to_string().as_bytes())),
+            sources: self
+                .provenance
+                .iter()
+                .map(|(key, source)| (key.clone(), source.to_string()))
+                .collect(),
+            loaded_at: unix_seconds(self.loaded_at),
+            config,
+        }
+    }
+
+    /// Deserialize the merged configuration into `C` (see [`initialize()`]), filling in the key
+    /// and source of any [`WithSource`] fields using the [`InitializationReport::provenance`].
+    /// Returns `None` if no configuration was found.
+    pub fn deserialize_with_sources<C: DeserializeOwned>(&self) -> Result<Option<C>> {
+        let (value, source) = match (&self.value, &self.source) {
+            (Some(value), Some(source)) => (value.clone(), source),
+            _ => return Ok(None),
+        };
+        let annotated = std::cell::Cell::new(false);
+        let deserializer = SourceDeserializer {
+            value,
+            key: TomlKeyPath::default(),
+            provenance: &self.provenance,
+            annotated: &annotated,
+        };
+        C::deserialize(deserializer).map(Some).map_err(|error| {
+            InnerError::ErrorParsingMergedToml {
+                source: source.clone(),
+                error: error.into(),
+            }
+            .into()
+        })
+    }
+
+    /// Opt in to logging each access to a secret in the merged configuration made using
+    /// [`TomlValueExt::get_as`] to `logging`, with a timestamp, see [`AuditedConfig`].
+    pub fn audited(&self, logging: Logging) -> AuditedConfig {
+        AuditedConfig {
+            value: self
+                .value
+                .clone()
+                .unwrap_or_else(|| Value::Table(toml::Table::new())),
+            redact: self.redact.clone(),
+            logging,
+        }
+    }
+
+    /// Render the merged configuration as TOML with the values of keys in [`Args::redact`]
+    /// hidden, where each value is annotated with a comment naming its source (see
+    /// [`InitializationReport::provenance`]), e.g. `url = "..." # from environment variables
+    /// MY_APP__DB__URL`. This is intended to be attached to support tickets when debugging where
+    /// a value came from. Keys are sorted, and arrays are rendered inline.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use toml_env::{initialize_with_report, map_env, Args};
+    ///
+    /// std::env::set_var("RENDER_ANNOTATED_DB_URL", "postgres://db");
+    ///
+    /// let (_config, report) = initialize_with_report::<toml::Value>(Args {
+    ///     defaults: Some("[db]\npool_size = 4"),
+    ///     map_env: map_env! { "RENDER_ANNOTATED_DB_URL" => "db.url" },
+    ///     ..Args::default()
+    /// })
+    /// .unwrap();
+    ///
+    /// assert_eq!(
+    ///     report.render_annotated(),
+    ///     "[db]\n\
+    ///     pool_size = 4 # from embedded defaults\n\
+    ///     url = \"postgres://db\" # from environment variables RENDER_ANNOTATED_DB_URL\n"
+    /// );
+    /// ```
+    pub fn render_annotated(&self) -> String {
+        fn render_table(
+            path: &mut PathElements,
+            table: &toml::Table,
+            provenance: &BTreeMap<String, ConfigSource>,
+            output: &mut String,
+        ) {
+            for (key, value) in table.iter().filter(|(_, value)| !value.is_table()) {
+                path.push(PathElement::TableProperty(key.as_str().into()));
+                output.push_str(&format!("{} = {value}", toml_key(key)));
+                if let Some(source) = provenance.get(&TomlKeyPath(path.clone()).to_string()) {
+                    output.push_str(&format!(" # from {source}"));
+                }
+                output.push('\n');
+                path.pop();
+            }
+            for (key, value) in table.iter() {
+                if let Value::Table(table) = value {
+                    path.push(PathElement::TableProperty(key.as_str().into()));
+                    // Tables which only contain other tables are implied by their headers.
+                    if table.is_empty() || table.values().any(|value| !value.is_table()) {
+                        if !output.is_empty() {
+                            output.push('\n');
+                        }
+                        let header = path
+                            .iter()
+                            .map(|element| toml_key(&element.to_string()))
+                            .collect::<Vec<_>>()
+                            .join(".");
+                        output.push_str(&format!("[{header}]\n"));
+                    }
+                    render_table(path, table, provenance, output);
+                    path.pop();
+                }
+            }
+        }
+
+        let mut config = match &self.value {
+            Some(value) => value.clone(),
+            None => return String::new(),
+        };
+        redact(&mut config, &self.redact);
+        let mut output = String::new();
+        if let Value::Table(table) = sort_keys(config) {
+            render_table(
+                &mut PathElements::new(),
+                &table,
+                &self.provenance,
+                &mut output,
+            );
+        }
+        output
+    }
+}
+
+/// Format `key` as a TOML key, quoting it if it is not a bare key.
+fn toml_key(key: &str) -> String {
+    let bare = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if bare {
+        key.to_owned()
+    } else {
+        Value::String(key.to_owned()).to_string()
+    }
+}
+
+/// The version of the format that [`InitializationReport`] is serialized in, which is included in
+/// the output as the `version` field. It is incremented when a field is removed or its format is
+/// changed, but not when new fields are added.
+pub const REPORT_FORMAT_VERSION: u32 = 1;
+
+/// Serialized with the following fields, for consumption by external tools (see
+/// [`REPORT_FORMAT_VERSION`]):
+///
+/// + `version`: [`REPORT_FORMAT_VERSION`].
+/// + `source`: the [`ConfigSource`] of the configuration, omitted if no configuration was found.
+/// + `config`: the merged configuration with the values of keys in [`Args::redact`] hidden and
+///   datetimes converted to strings, omitted if no configuration was found.
+/// + `provenance`: [`InitializationReport::provenance`].
+/// + `loaded_at`: [`InitializationReport::loaded_at`] in seconds since the UNIX epoch.
+/// + `warnings`: [`InitializationReport::warnings`], each with a `kind` ([`SourceKind`]) and an
+///   `error` message.
+/// + `variable_names`: [`InitializationReport::variable_names`].
+/// + `sources`: [`InitializationReport::sources`], each with a `source`, a `read_at` time and an
+///   optional `modified` time in seconds since the UNIX epoch.
+///
+/// A [`ConfigSource`] is serialized as a table with a `kind` (`merged`, `dotenv`, `file`,
+/// `defaults`, `environment`, `inline` or `preloaded_dotenv`) and the fields of the variant
+/// (`from` and `into` for `merged`, `path`, `variable_names` and `label`). The values of the
+/// `.env.toml` file ([`InitializationReport::dotenv_variables`]) are not included, because they
+/// may contain secrets.
+impl Serialize for InitializationReport {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::SerializeStruct;
+        let config = self.value.clone().map(|mut config| {
+            redact(&mut config, &self.redact);
+            datetimes_to_strings(sort_keys(config))
+        });
+
+        let mut state = serializer.serialize_struct("InitializationReport", 8)?;
+        state.serialize_field("version", &REPORT_FORMAT_VERSION)?;
+        match &self.source {
+            Some(source) => state.serialize_field("source", source)?,
+            None => state.skip_field("source")?,
+        }
+        match &config {
+            Some(config) => state.serialize_field("config", config)?,
+            None => state.skip_field("config")?,
+        }
+        state.serialize_field("provenance", &self.provenance)?;
+        state.serialize_field("loaded_at", &unix_seconds(self.loaded_at))?;
+        state.serialize_field("warnings", &self.warnings)?;
+        state.serialize_field("variable_names", &self.variable_names)?;
+        state.serialize_field("sources", &self.sources)?;
+        state.end()
+    }
+}
+
+/// See [`InitializationReport`].
+impl Serialize for ConfigSource {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::SerializeMap;
+        let mut map = serializer.serialize_map(None)?;
+        match self {
+            ConfigSource::Merged { from, into } => {
+                map.serialize_entry("kind", "merged")?;
+                map.serialize_entry("from", from)?;
+                map.serialize_entry("into", into)?;
+            }
+            ConfigSource::DotEnv(path) => {
+                map.serialize_entry("kind", &SourceKind::DotEnv)?;
+                map.serialize_entry("path", &path.to_string_lossy())?;
+            }
+            ConfigSource::File(path) => {
+                map.serialize_entry("kind", &SourceKind::File)?;
+                map.serialize_entry("path", &path.to_string_lossy())?;
+            }
+            ConfigSource::Defaults => {
+                map.serialize_entry("kind", &SourceKind::Defaults)?;
+            }
+            ConfigSource::Environment { variable_names } => {
+                map.serialize_entry("kind", &SourceKind::Environment)?;
+                map.serialize_entry("variable_names", variable_names)?;
+            }
+            ConfigSource::Inline { label } => {
+                map.serialize_entry("kind", &SourceKind::Inline)?;
+                map.serialize_entry("label", label)?;
+            }
+            ConfigSource::PreloadedDotEnv {
+                path,
+                variable_names,
+            } => {
+                map.serialize_entry("kind", &SourceKind::PreloadedDotEnv)?;
+                map.serialize_entry("path", &path.to_string_lossy())?;
+                map.serialize_entry("variable_names", variable_names)?;
+            }
+            ConfigSource::Custom { description, kind } => {
+                map.serialize_entry("kind", kind)?;
+                map.serialize_entry("description", description)?;
+            }
+            ConfigSource::Remote { url } => {
+                map.serialize_entry("kind", &SourceKind::Remote)?;
+                map.serialize_entry("url", url)?;
+            }
+        }
+        map.end()
+    }
+}
+
+/// Serialized as a snake case string (e.g. `preloaded_dotenv`), see [`InitializationReport`].
+impl Serialize for SourceKind {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(match self {
+            SourceKind::DotEnv => "dotenv",
+            SourceKind::File => "file",
+            SourceKind::Defaults => "defaults",
+            SourceKind::Environment => "environment",
+            SourceKind::Inline => "inline",
+            SourceKind::PreloadedDotEnv => "preloaded_dotenv",
+            SourceKind::Custom => "custom",
+            SourceKind::Secrets => "secrets",
+            SourceKind::Remote => "remote",
+        })
+    }
+}
+
+/// See [`InitializationReport`].
+impl Serialize for SourceWarning {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::SerializeStruct;
+        let mut state = serializer.serialize_struct("SourceWarning", 2)?;
+        state.serialize_field("kind", &self.kind)?;
+        state.serialize_field("error", &self.error.to_string())?;
+        state.end()
+    }
+}
+
+/// See [`InitializationReport`].
+impl Serialize for SourceFreshness {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::SerializeStruct;
+        let mut state = serializer.serialize_struct("SourceFreshness", 3)?;
+        state.serialize_field("source", &self.source)?;
+        state.serialize_field("read_at", &unix_seconds(self.read_at))?;
+        match self.modified {
+            Some(modified) => state.serialize_field("modified", &unix_seconds(modified))?,
+            None => state.skip_field("modified")?,
+        }
+        state.end()
+    }
+}
+
+/// The number of seconds between the UNIX epoch and `time` (`0` if `time` is before the epoch).
+fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+/// The value which replaces redacted values, see [`Args::redact`].
+pub const REDACTED: &str = "***";
+
+/// Replace the values at the `keys` in `value` (if they are present) with [`REDACTED`].
+fn redact(value: &mut Value, keys: &[TomlKeyPath]) {
+    fn redact_path(value: &mut Value, pattern: &[PathElement]) {
+        let (element, rest) = match pattern.split_first() {
+            Some(split) => split,
+            None => {
+                *value = Value::String(REDACTED.to_owned());
+                return;
+            }
+        };
+        match (element, value) {
+            (PathElement::Wildcard, Value::Table(table)) => {
+                table
+                    .iter_mut()
+                    .for_each(|(_, value)| redact_path(value, rest));
+            }
+            (PathElement::Wildcard, Value::Array(array)) => {
+                array.iter_mut().for_each(|value| redact_path(value, rest));
+            }
+            (PathElement::TableProperty(key), Value::Table(table)) => {
+                if let Some(value) = table.get_mut(&**key) {
+                    redact_path(value, rest);
+                }
+            }
+            (PathElement::ArrayIndex(index), Value::Array(array)) => {
+                if let Some(value) = array.get_mut(*index) {
+                    redact_path(value, rest);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    for key in keys {
+        redact_path(value, &key.0);
+    }
+}
+
+/// Whether the value at `key` is redacted by any of the `keys` (see [`Args::redact`]).
+fn is_redacted(key: &TomlKeyPath, keys: &[TomlKeyPath]) -> bool {
+    keys.iter().any(|pattern| {
+        pattern.0.len() <= key.0.len()
+            && pattern
+                .0
+                .iter()
+                .zip(&key.0)
+                .all(|(pattern, element)| *pattern == PathElement::Wildcard || pattern == element)
+    })
+}
+
+/// Whether the value at `key` is redacted by, or is a table containing a value redacted by, any
+/// of the `keys` (see [`Args::redact`]).
+fn contains_redacted(key: &TomlKeyPath, keys: &[TomlKeyPath]) -> bool {
+    keys.iter().any(|pattern| {
+        pattern
+            .0
+            .iter()
+            .zip(&key.0)
+            .all(|(pattern, element)| *pattern == PathElement::Wildcard || pattern == element)
+    })
+}
+
+/// Convert all datetimes in `value` to strings, because they are otherwise serialized as a
+/// special table by [`toml`] when using other serializers.
+fn datetimes_to_strings(value: Value) -> Value {
+    match value {
+        Value::Datetime(datetime) => Value::String(datetime.to_string()),
+        Value::Table(table) => Value::Table(
+            table
+                .into_iter()
+                .map(|(key, value)| (key, datetimes_to_strings(value)))
+                .collect(),
+        ),
+        Value::Array(array) => Value::Array(array.into_iter().map(datetimes_to_strings).collect()),
+        value => value,
+    }
+}
+
+/// 64 bit FNV-1a hash, which (unlike [`std::hash::DefaultHasher`]) is stable across Rust versions
+/// and platforms.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// A description of the effective configuration, created using
+/// [`InitializationReport::describe()`]. Implements [`serde::Serialize`] with the fields below.
+#[derive(Debug, Clone)]
+pub struct ConfigDescription {
+    /// The merged configuration, with sensitive values redacted and datetimes converted to
+    /// strings.
+    pub config: Value,
+    /// A description of the source of each value in the configuration, keyed by the path to the
+    /// value.
+    pub sources: BTreeMap<String, String>,
+    /// A stable hash of the redacted configuration, as hex. Can be used to check whether multiple
+    /// instances of an application are using the same configuration. Redacted values don't affect
+    /// the fingerprint, so that it reveals nothing about them.
+    pub fingerprint: String,
+    /// The time at which the configuration was loaded, in seconds since the UNIX epoch.
+    pub loaded_at: u64,
+}
+
+impl Serialize for ConfigDescription {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::SerializeStruct;
+        let mut state = serializer.serialize_struct("ConfigDescription", 4)?;
+        state.serialize_field("config", &self.config)?;
+        state.serialize_field("sources", &self.sources)?;
+        state.serialize_field("fingerprint", &self.fingerprint)?;
+        state.serialize_field("loaded_at", &self.loaded_at)?;
+        state.end()
+    }
+}
+
+/// A key which has a different type in the old and new configuration, see [`KeyDiff`].
+#[derive(Debug, Clone)]
+pub struct TypeChange {
+    /// Path to the key.
+    pub key: TomlKeyPath,
+    /// Type of the value in the old configuration (see [`toml::Value::type_str()`]).
+    pub old: &'static str,
+    /// Type of the value in the new configuration (see [`toml::Value::type_str()`]).
+    pub new: &'static str,
+}
+
+/// The difference between the keys of two configurations, produced by [`diff_keys()`]. Keys are
+/// sorted by their path.
+#[derive(Debug, Clone, Default)]
+pub struct KeyDiff {
+    /// Keys which are present in the new configuration, but not in the old.
+    pub added: Vec<TomlKeyPath>,
+    /// Keys which are present in the old configuration, but not in the new.
+    pub removed: Vec<TomlKeyPath>,
+    /// Keys which are present in both configurations, but with a different type.
+    pub type_changed: Vec<TypeChange>,
+}
+
+impl KeyDiff {
+    /// Returns `true` if there are no differences between the keys of the two configurations.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.type_changed.is_empty()
+    }
+}
+
+impl std::fmt::Display for KeyDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for key in &self.added {
+            writeln!(f, "+ {key}")?;
+        }
+        for key in &self.removed {
+            writeln!(f, "- {key}")?;
+        }
+        for change in &self.type_changed {
+            writeln!(f, "~ {}: {} -> {}", change.key, change.old, change.new)?;
+        }
+        Ok(())
+    }
+}
+
+/// Compare the keys of two configurations (e.g. example configs serialized from the `Default`
+/// implementation of the config struct in two different builds of an application), and report
+/// which keys have been added, removed, or have changed type. Values themselves are not
+/// compared.
+///
+/// # Example
+///
+/// ```rust
+/// let old: toml::Value = toml::from_str(r#"
+/// port = 1883
+/// host = "localhost"
+/// [tls]
+/// enabled = false
+/// "#).unwrap();
+/// let new: toml::Value = toml::from_str(r#"
+/// port = "1883"
+/// [tls]
+/// enabled = false
+/// cert = "cert.pem"
+/// "#).unwrap();
+///
+/// let diff = toml_env::diff_keys(&old, &new);
+/// assert_eq!(diff.to_string(), "+ tls.cert\n- host\n~ port: integer -> string\n");
+/// ```
+pub fn diff_keys(old: &Value, new: &Value) -> KeyDiff {
+    fn diff_impl(path: &mut PathElements, old: &Value, new: &Value, diff: &mut KeyDiff) {
+        match (old, new) {
+            (Value::Table(old), Value::Table(new)) => {
+                for (key, old_value) in old {
+                    path.push(PathElement::TableProperty(key.as_str().into()));
+                    match new.get(key) {
+                        Some(new_value) => diff_impl(path, old_value, new_value, diff),
+                        None => diff.removed.push(TomlKeyPath(path.clone())),
+                    }
+                    path.pop();
+                }
+                for key in new.keys().filter(|key| !old.contains_key(*key)) {
+                    path.push(PathElement::TableProperty(key.as_str().into()));
+                    diff.added.push(TomlKeyPath(path.clone()));
+                    path.pop();
+                }
+            }
+            (Value::Array(old), Value::Array(new)) => {
+                for i in 0..old.len().max(new.len()) {
+                    path.push(PathElement::ArrayIndex(i));
+                    match (old.get(i), new.get(i)) {
+                        (Some(old_value), Some(new_value)) => {
+                            diff_impl(path, old_value, new_value, diff)
+                        }
+                        (Some(_), None) => diff.removed.push(TomlKeyPath(path.clone())),
+                        (None, Some(_)) => diff.added.push(TomlKeyPath(path.clone())),
+                        (None, None) => {}
+                    }
+                    path.pop();
+                }
+            }
+            (old, new) => {
+                if old.type_str() != new.type_str() {
+                    diff.type_changed.push(TypeChange {
+                        key: TomlKeyPath(path.clone()),
+                        old: old.type_str(),
+                        new: new.type_str(),
+                    })
+                }
+            }
+        }
+    }
+
+    let mut diff = KeyDiff::default();
+    diff_impl(&mut PathElements::new(), old, new, &mut diff);
+    diff.added.sort_by(|a, b| a.0.cmp(&b.0));
+    diff.removed.sort_by(|a, b| a.0.cmp(&b.0));
+    diff.type_changed.sort_by(|a, b| a.key.0.cmp(&b.key.0));
+    diff
+}
+
+/// Serialize and deserialize integers which may be out of range for TOML's `i64` integers (such
+/// as large `u64` or `u128` values) as strings, for use with
+/// `#[serde(with = "toml_env::big_int")]`.
+///
+/// Integers which are in range for an `i64` are serialized as integers, otherwise they are
+/// serialized as strings. Both integers and strings can be deserialized. Environment variables
+/// containing large integers are kept as strings when parsed using
+/// [`NumberInference::PreferInteger`] or [`ValueHint::Integer`] (including when specified by
+/// [`Args::schema`]), or when using [`LargeIntegers::String`].
+///
+/// # Example
+///
+/// ```rust
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Config {
+///     #[serde(with = "toml_env::big_int")]
+///     id: u64,
+/// }
+///
+/// let config: Config = toml::from_str("id = \"18446744073709551615\"").unwrap();
+/// assert_eq!(config.id, u64::MAX);
+/// let config: Config = toml::from_str("id = 1").unwrap();
+/// assert_eq!(config.id, 1);
+/// assert_eq!(
+///     toml::to_string(&Config { id: u64::MAX }).unwrap(),
+///     "id = \"18446744073709551615\"\n"
+/// );
+/// ```
+pub mod big_int {
+    use std::{fmt::Display, marker::PhantomData, str::FromStr};
+
+    use serde::{de::Error, Deserializer, Serializer};
+
+    /// Serialize the `value` as an integer if it is in range for an `i64`, otherwise as a string.
+    pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Display,
+        S: Serializer,
+    {
+        let value = value.to_string();
+        match i64::from_str(&value) {
+            Ok(value) => serializer.serialize_i64(value),
+            Err(_) => serializer.serialize_str(&value),
+        }
+    }
+
+    /// Deserialize an integer or a string containing an integer.
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: FromStr,
+        T::Err: Display,
+        D: Deserializer<'de>,
+    {
+        struct Visitor<T>(PhantomData<T>);
+
+        impl<T> serde::de::Visitor<'_> for Visitor<T>
+        where
+            T: FromStr,
+            T::Err: Display,
+        {
+            type Value = T;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                formatter.write_str("an integer or a string containing an integer")
+            }
+
+            fn visit_i64<E: Error>(self, value: i64) -> Result<T, E> {
+                self.visit_str(&value.to_string())
+            }
+
+            fn visit_u64<E: Error>(self, value: u64) -> Result<T, E> {
+                self.visit_str(&value.to_string())
+            }
+
+            fn visit_str<E: Error>(self, value: &str) -> Result<T, E> {
+                T::from_str(value).map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(Visitor(PhantomData))
+    }
+}
+
+/// Use with `#[serde(with = "toml_env::secret")]` to configure [`secrecy::Secret`] values (e.g.
+/// [`secrecy::SecretString`] or [`secrecy::SecretVec`]). The secret is deserialized as usual, but
+/// serialized as [`REDACTED`], so that it is never included in the logged configuration.
+///
+/// # Example
+///
+/// ```rust
+/// use secrecy::{ExposeSecret, SecretString};
+///
+/// #[derive(serde::Serialize, serde::Deserialize)]
+/// struct Config {
+///     #[serde(with = "toml_env::secret")]
+///     password: SecretString,
+/// }
+///
+/// let config: Config = toml::from_str("password = \"hunter2\"").unwrap();
+/// assert_eq!(config.password.expose_secret(), "hunter2");
+/// assert_eq!(toml::to_string(&config).unwrap(), "password = \"***\"\n");
+/// ```
+#[cfg(feature = "secrecy")]
+pub mod secret {
+    use secrecy::{zeroize::Zeroize, Secret};
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    /// Serialize [`REDACTED`](crate::REDACTED) in place of the secret.
+    pub fn serialize<T, S>(_secret: &Secret<T>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Zeroize,
+        S: Serializer,
+    {
+        serializer.serialize_str(crate::REDACTED)
+    }
+
+    /// Deserialize the secret.
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Secret<T>, D::Error>
+    where
+        T: Zeroize,
+        Secret<T>: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        Secret::deserialize(deserializer)
+    }
+}
+
+/// An environment variable which can be used to set a key in the configuration, see
+/// [`env_variables()`].
+#[derive(Debug, Clone)]
+pub struct EnvVariable {
+    /// Name of the environment variable.
+    pub name: String,
+    /// The key that the environment variable is mapped to.
+    pub key: TomlKeyPath,
+    /// The default value of the key.
+    pub default: Value,
+}
+
+impl EnvVariable {
+    /// The default value formatted as the value of the environment variable. Floats are formatted
+    /// so that they parse back identically, and arrays and tables are formatted as inline TOML
+    /// (see [`Args::inline_toml`]).
+    pub fn default_string(&self) -> String {
+        match &self.default {
+            Value::String(value) => value.clone(),
+            Value::Float(value) => format_float(*value),
+            value => value.to_string(),
+        }
+    }
+}
+
+/// List the automatically mapped environment variables (see [`Conventions::variable_name()`])
+/// for each value in a `schema` (e.g. serialized from the `Default` implementation of the config
+/// struct), sorted by key. Arrays are considered to be a single value.
+pub fn env_variables(schema: &Value, conventions: &Conventions<'_>) -> Vec<EnvVariable> {
+    fn collect(
+        path: &mut PathElements,
+        value: &Value,
+        conventions: &Conventions<'_>,
+        variables: &mut Vec<EnvVariable>,
+    ) {
+        match value {
+            Value::Table(table) => {
+                let mut entries: Vec<_> = table.iter().collect();
+                entries.sort_by_key(|(key, _)| *key);
+                for (key, value) in entries {
+                    path.push(PathElement::TableProperty(key.as_str().into()));
+                    collect(path, value, conventions, variables);
+                    path.pop();
+                }
+            }
+            value => {
+                let key = TomlKeyPath(path.clone());
+                variables.push(EnvVariable {
+                    name: conventions.variable_name(&key),
+                    key,
+                    default: value.clone(),
+                })
+            }
+        }
+    }
+
+    let mut variables = Vec::new();
+    collect(
+        &mut PathElements::new(),
+        schema,
+        conventions,
+        &mut variables,
+    );
+    variables
+}
+
+/// A format for [`render_template()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TemplateFormat {
+    /// A Helm `values.yaml` skeleton with an `env` map of environment variables.
+    HelmValues,
+    /// Terraform variable definitions, one for each environment variable.
+    Terraform,
+}
+
+/// Render a template defining the environment `variables` (see [`env_variables()`]) with their
+/// default values, for use in infrastructure-as-code definitions of deployments.
+///
+/// # Example
+///
+/// ```rust
+/// use toml_env::{env_variables, render_template, Conventions, TemplateFormat};
+///
+/// let schema: toml::Value = toml::from_str("[db]\nhost = \"localhost\"\nport = 5432").unwrap();
+/// let conventions = Conventions {
+///     config_variable_name: "MY_APP",
+///     ..Conventions::default()
+/// };
+/// let variables = env_variables(&schema, &conventions);
+/// assert_eq!(
+///     render_template(&variables, TemplateFormat::HelmValues),
+///     "env:\n  MY_APP__DB__HOST: \"localhost\"\n  MY_APP__DB__PORT: \"5432\"\n"
+/// );
+/// ```
+pub fn render_template(variables: &[EnvVariable], format: TemplateFormat) -> String {
+    /// Quote a string using the escapes common to YAML and HCL double quoted strings.
+    fn quote(value: &str) -> String {
+        let mut quoted = String::with_capacity(value.len() + 2);
+        quoted.push('"');
+        for c in value.chars() {
+            match c {
+                '"' => quoted.push_str("\\\""),
+                '\\' => quoted.push_str("\\\\"),
+                '\n' => quoted.push_str("\\n"),
+                '\r' => quoted.push_str("\\r"),
+                '\t' => quoted.push_str("\\t"),
+                c => quoted.push(c),
+            }
+        }
+        quoted.push('"');
+        quoted
+    }
+
+    let mut output = String::new();
+    match format {
+        TemplateFormat::HelmValues => {
+            output.push_str("env:");
+            if variables.is_empty() {
+                output.push_str(" {}");
+            }
+            output.push('\n');
+            for variable in variables {
+                let value = quote(&variable.default_string());
+                output.push_str(&format!("  {}: {value}\n", variable.name));
+            }
+        }
+        TemplateFormat::Terraform => {
+            for (i, variable) in variables.iter().enumerate() {
+                if i > 0 {
+                    output.push('\n');
+                }
+                let description = quote(&format!("Sets `{}` in the configuration.", variable.key));
+                let value = quote(&variable.default_string());
+                output.push_str(&format!(
+                    "variable \"{}\" {{\n  description = {description}\n  type        = string\n  default     = {value}\n}}\n",
+                    variable.name.to_lowercase()
+                ));
+            }
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod test {
+    use toml::Value;
+
+    use crate::InsertTomlValueError;
+
+    use super::{insert_toml_value, parse_toml_value, NumberInference};
+    #[test]
+    fn insert_toml_value_empty_path() {
+        let mut value = toml::Value::String("Hello".to_owned());
+        insert_toml_value(
+            &mut value,
+            "".parse().unwrap(),
+            toml::Value::String("World".to_owned()),
+        )
+        .unwrap();
+        assert_eq!(value.as_str().unwrap(), "World");
+    }
+
+    #[test]
+    fn insert_toml_value_table_property() {
+        let mut value = toml::Value::Table(toml::Table::new());
+        insert_toml_value(
+            &mut value,
+            "child".parse().unwrap(),
+            toml::Value::String("Hello Child".to_owned()),
+        )
+        .unwrap();
+        assert_eq!(value.get("child").unwrap().as_str().unwrap(), "Hello Child");
+    }
+
+    #[test]
+    fn insert_toml_value_table_property_property() {
+        let mut value = toml::Value::Table(toml::Table::new());
+        insert_toml_value(
+            &mut value,
+            "child.value".parse().unwrap(),
+            toml::Value::String("Hello Child Value".to_owned()),
+        )
+        .unwrap();
+        assert_eq!(
+            value
+                .get("child")
+                .unwrap()
+                .get("value")
+                .unwrap()
+                .as_str()
+                .unwrap(),
+            "Hello Child Value"
+        );
+    }
+
+    #[test]
+    fn insert_toml_value_create_array() {
+        let mut value = toml::Value::Array(Vec::new());
+        insert_toml_value(
+            &mut value,
+            "0".parse().unwrap(),
+            toml::Value::String("Hello Element".to_owned()),
+        )
+        .unwrap();
+        assert_eq!(value.get(0).unwrap().as_str().unwrap(), "Hello Element");
+    }
+
+    #[test]
+    fn insert_toml_value_array_out_of_bounds_error() {
+        let mut value = toml::Value::Array(Vec::new());
+        let error = insert_toml_value(
+            &mut value,
+            "1".parse().unwrap(),
+            toml::Value::String("Hello Element".to_owned()),
+        )
+        .unwrap_err();
+
+        assert!(matches!(
+            error,
+            InsertTomlValueError::ArrayOutOfBounds { .. }
+        ))
+    }
+
+    #[test]
+    fn insert_toml_value_table_property_index_error() {
+        let mut value = toml::Value::Array(Vec::new());
+        let error = insert_toml_value(
+            &mut value,
+            "key".parse().unwrap(),
+            toml::Value::String("Hello Element".to_owned()),
+        )
+        .unwrap_err();
+
+        assert!(matches!(
+            error,
+            InsertTomlValueError::TablePropertyCannotIndex { .. }
+        ))
+    }
+
+    #[test]
+    fn insert_toml_value_array_index_cannot_index_error() {
+        let mut value = toml::Value::Table(toml::Table::new());
+        let error = insert_toml_value(
+            &mut value,
+            "0".parse().unwrap(),
+            toml::Value::String("Hello Element".to_owned()),
+        )
+        .unwrap_err();
+
+        assert!(matches!(
+            error,
+            InsertTomlValueError::ArrayIndexCannotIndex { .. }
+        ))
+    }
+
+    #[test]
+    fn insert_toml_value_table_child_create_array() {
+        let mut value = toml::Value::Table(toml::Table::new());
+        insert_toml_value(
+            &mut value,
+            "child.0".parse().unwrap(),
+            toml::Value::String("Hello Element".to_owned()),
+        )
+        .unwrap();
+        assert_eq!(
+            value
+                .get("child")
+                .unwrap()
+                .get(0)
+                .unwrap()
+                .as_str()
+                .unwrap(),
+            "Hello Element"
+        );
+    }
+
+    #[test]
+    fn insert_toml_value_append() {
+        let mut value = toml::Value::Table(toml::Table::new());
+        for (path, element) in [("tags.+", "a"), ("tags.+", "b"), ("nested.+.+", "c")] {
+            insert_toml_value(&mut value, path.parse().unwrap(), element.into()).unwrap();
+        }
+        assert_eq!(value["tags"], Value::Array(vec!["a".into(), "b".into()]));
+        assert_eq!(value["nested"][0][0].as_str(), Some("c"));
+
+        let error =
+            insert_toml_value(&mut value, "nested.0.0.+".parse().unwrap(), "d".into()).unwrap_err();
+        assert!(matches!(
+            error,
+            crate::InsertTomlValueError::AppendCannotIndex { .. }
+        ));
+        let mut table: Value = toml::from_str("[tags]\nkey = 1").unwrap();
+        let error =
+            insert_toml_value(&mut table, "tags.+".parse().unwrap(), "a".into()).unwrap_err();
+        assert!(matches!(
+            error,
+            crate::InsertTomlValueError::AppendCannotIndex { .. }
+        ));
+        assert!(table["tags"].get("+").is_none());
+
+        // A quoted `+` is a literal key.
+        let path: crate::TomlKeyPath = "tags.\"+\"".parse().unwrap();
+        assert_eq!(path.to_string(), "tags.\"+\"");
+        insert_toml_value(&mut table, path, "a".into()).unwrap();
+        assert_eq!(table["tags"]["+"].as_str(), Some("a"));
+    }
+
+    #[test]
+    fn initialize_append() {
+        std::env::set_var("TEST_APPEND_TAG_1", "a");
+        std::env::set_var("TEST_APPEND_TAG_2", "b");
+        let config: Value = crate::initialize(crate::Args {
+            map_env: crate::map_env! {
+                "TEST_APPEND_TAG_2" => "tags.+",
+                "TEST_APPEND_TAG_1" => "tags.+",
+                "TEST_APPEND_TAG_3" => "tags.+",
+            },
+            ..crate::Args::default()
+        })
+        .unwrap()
+        .unwrap();
+        // Appended in the order of the variable names.
+        assert_eq!(config["tags"], Value::Array(vec!["a".into(), "b".into()]));
+    }
+
+    #[test]
+    fn diff_keys_array() {
+        let old: toml::Value = toml::from_str("array = [1, 2]").unwrap();
+        let new: toml::Value = toml::from_str("array = [1, 2.0, 3]").unwrap();
+        let diff = crate::diff_keys(&old, &new);
+        assert_eq!(diff.to_string(), "+ array.2\n~ array.1: integer -> float\n");
+        assert!(crate::diff_keys(&old, &old).is_empty());
+    }
+
+    #[test]
+    fn initialize_config_variable_key_path() {
+        #[derive(serde::Serialize, serde::Deserialize)]
+        struct Config {
+            value: String,
+        }
+
+        std::env::set_var(
+            "TEST_CONFIG_VARIABLE_KEY_PATH",
+            "[other_app]\nvalue=\"other\"\n[my_app]\nvalue=\"mine\"",
+        );
+        let config: Config = crate::initialize(crate::Args {
+            config_variable_name: "TEST_CONFIG_VARIABLE_KEY_PATH",
+            config_variable_key_path: "my_app".parse().unwrap(),
+            ..crate::Args::default()
+        })
+        .unwrap()
+        .unwrap();
+        assert_eq!(config.value, "mine");
+
+        let args = |missing_key_path| crate::Args {
+            config_variable_name: "TEST_CONFIG_VARIABLE_KEY_PATH",
+            config_variable_key_path: "my_ap".parse().unwrap(),
+            missing_key_path,
+            ..crate::Args::default()
+        };
+        let (config, report) =
+            crate::initialize_with_report::<Value>(args(crate::MissingKeyPath::Silent)).unwrap();
+        assert!(config.is_none());
+        assert!(report.warnings.is_empty());
+        let (config, report) =
+            crate::initialize_with_report::<Value>(args(crate::MissingKeyPath::Warn)).unwrap();
+        assert!(config.is_none());
+        assert_eq!(
+            report.warnings[0].to_string(),
+            "Skipped environment variables: \
+            No config found at `my_ap` in `TEST_CONFIG_VARIABLE_KEY_PATH` environment variable"
+        );
+        crate::initialize::<Value>(args(crate::MissingKeyPath::Error)).unwrap_err();
+    }
+
+    #[test]
+    fn initialize_merge_conflict() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = dir.path().join("config.toml");
+        std::fs::write(&config_path, "[child]\nvalue=1\nother=true").unwrap();
+        std::env::set_var("TEST_MERGE_CONFLICT_VALUE", "1.5");
+
+        let error = crate::initialize::<toml::Value>(crate::Args {
+            config_path: Some(&config_path),
+            map_env: [("TEST_MERGE_CONFLICT_VALUE", "child.value".parse().unwrap())]
+                .into_iter()
+                .collect(),
+            ..crate::Args::default()
+        })
+        .unwrap_err();
+
+        let conflict = error.merge_conflict().expect("Expected a merge conflict");
+        assert_eq!(conflict.key.to_string(), "child.value");
+        assert_eq!(conflict.from_type, "float");
+        assert_eq!(conflict.into_type, "integer");
+        assert!(matches!(
+            &conflict.from,
+            crate::ConfigSource::Environment { variable_names }
+                if variable_names == &["TEST_MERGE_CONFLICT_VALUE"]
+        ));
+        assert!(matches!(&conflict.into, crate::ConfigSource::File(path) if path == &config_path));
+    }
+
+    #[test]
+    fn initialize_key_policies() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = dir.path().join("config.toml");
+        std::fs::write(&config_path, "[auth]\nadmin_token=\"secret\"").unwrap();
+        std::env::set_var("TEST_KEY_POLICIES_TOKEN", "secret");
+        let key_policies = || {
+            vec![crate::KeyPolicy::Allow {
+                key: "auth.admin_token".parse().unwrap(),
+                sources: vec![crate::SourceKind::Environment],
+            }]
+        };
+
+        let error = crate::initialize::<toml::Value>(crate::Args {
+            config_path: Some(&config_path),
+            key_policies: key_policies(),
+            ..crate::Args::default()
+        })
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "Key `auth.admin_token` is not allowed to be set by config TOML file \
+                (set by config TOML file {config_path:?})"
+            )
+        );
+
+        let config = crate::initialize::<toml::Value>(crate::Args {
+            map_env: crate::map_env! { "TEST_KEY_POLICIES_TOKEN" => "auth.admin_token" },
+            key_policies: key_policies(),
+            ..crate::Args::default()
+        })
+        .unwrap()
+        .unwrap();
+        assert_eq!(config["auth"]["admin_token"].as_str(), Some("secret"));
+
+        // Secret backends are distinguished from other custom sources, and a key-per-file
+        // directory is only a secret backend when it is declared as one.
+        let secrets_dir = dir.path().join("secrets");
+        std::fs::create_dir(&secrets_dir).unwrap();
+        std::fs::write(secrets_dir.join("auth__admin_token"), "secret").unwrap();
+        let secrets_args = |kind| crate::Args {
+            sources: vec![Box::new(
+                crate::KeyPerFileSource::new(&secrets_dir)
+                    .divider("__")
+                    .kind(kind),
+            )],
+            key_policies: vec![crate::KeyPolicy::Allow {
+                key: "auth.admin_token".parse().unwrap(),
+                sources: vec![crate::SourceKind::Secrets],
+            }],
+            ..crate::Args::default()
+        };
+        let error =
+            crate::initialize::<toml::Value>(secrets_args(crate::SourceKind::Custom)).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "Key `auth.admin_token` is not allowed to be set by custom source \
+                (set by custom source files in {secrets_dir:?})"
+            )
+        );
+        let (config, report) =
+            crate::initialize_with_report::<toml::Value>(secrets_args(crate::SourceKind::Secrets))
+                .unwrap();
+        assert_eq!(
+            config.unwrap()["auth"]["admin_token"].as_str(),
+            Some("secret")
+        );
+        assert_eq!(
+            report.provenance["auth.admin_token"].kind(),
+            Some(crate::SourceKind::Secrets)
+        );
+    }
+
+    #[test]
+    fn parse_toml_value_number_inference() {
+        assert_eq!(
+            parse_toml_value("1883".to_owned(), NumberInference::PreferInteger, false),
+            Value::Integer(1883)
+        );
+        assert_eq!(
+            parse_toml_value("-5".to_owned(), NumberInference::PreferInteger, false),
+            Value::Integer(-5)
+        );
+        assert_eq!(
+            parse_toml_value("1883".to_owned(), NumberInference::PreferFloat, false),
+            Value::Float(1883.0)
+        );
+        assert_eq!(
+            parse_toml_value("1.5".to_owned(), NumberInference::PreferInteger, false),
+            Value::Float(1.5)
+        );
+        assert_eq!(
+            parse_toml_value(
+                "99999999999999999999".to_owned(),
+                NumberInference::PreferInteger,
+                false
+            ),
+            Value::String("99999999999999999999".to_owned())
+        );
+        assert_eq!(
+            parse_toml_value("1.10".to_owned(), NumberInference::StringsOnly, false),
+            Value::String("1.10".to_owned())
+        );
+        assert_eq!(
+            parse_toml_value("true".to_owned(), NumberInference::StringsOnly, false),
+            Value::Boolean(true)
+        );
+    }
+
+    #[test]
+    fn parse_toml_value_inline_toml() {
+        assert_eq!(
+            parse_toml_value("[1, 2, 3]".to_owned(), NumberInference::PreferInteger, true),
+            Value::Array(vec![1.into(), 2.into(), 3.into()])
+        );
+        let table = parse_toml_value(
+            r#"{ host = "x", port = 1 }"#.to_owned(),
+            NumberInference::PreferFloat,
+            true,
+        );
+        assert_eq!(table["host"].as_str(), Some("x"));
+        assert_eq!(table["port"].as_integer(), Some(1));
+        assert_eq!(
+            parse_toml_value(
+                "[1, 2, 3]".to_owned(),
+                NumberInference::PreferInteger,
+                false
+            ),
+            Value::String("[1, 2, 3]".to_owned())
+        );
+        assert_eq!(
+            parse_toml_value("[not toml".to_owned(), NumberInference::PreferInteger, true),
+            Value::String("[not toml".to_owned())
+        );
+    }
+
+    #[cfg(feature = "dotenv")]
+    #[test]
+    fn parse_env_file() {
+        let variables = super::parse_env_file(
+            r#"
+# A comment.
+PLAIN=value # trailing comment
+export EXPORTED = exported
+SINGLE='literal \n # not a comment'
+DOUBLE="line 1\nline \"2\"" # comment
+EMPTY=
+HASH=a#b
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            variables,
+            [
+                ("PLAIN", "value"),
+                ("EXPORTED", "exported"),
+                ("SINGLE", "literal \\n # not a comment"),
+                ("DOUBLE", "line 1\nline \"2\""),
+                ("EMPTY", ""),
+                ("HASH", "a#b"),
+            ]
+            .map(|(name, value)| (name.to_owned(), value.to_owned()))
+        );
+        assert_eq!(super::parse_env_file("A=1\nnot a variable"), Err(2));
+        assert_eq!(super::parse_env_file("A=\"unterminated"), Err(1));
+        assert_eq!(super::parse_env_file("A='a' b"), Err(1));
+    }
+
+    #[test]
+    fn decode_base64() {
+        assert_eq!(super::decode_base64("aGVsbG8="), Some(b"hello".to_vec()));
+        assert_eq!(super::decode_base64("aGVsbG8"), Some(b"hello".to_vec()));
+        assert_eq!(
+            super::decode_base64("LS0t\nLS0=\n"),
+            Some(b"-----".to_vec())
+        );
+        assert_eq!(super::decode_base64(""), Some(Vec::new()));
+        assert_eq!(super::decode_base64("a"), None);
+        assert_eq!(super::decode_base64("a$=="), None);
+    }
+
+    #[test]
+    fn is_valid_key_path() {
+        assert!(crate::__is_valid_key_path(""));
+        assert!(crate::__is_valid_key_path("key"));
+        assert!(crate::__is_valid_key_path("key.0.key"));
+        assert!(!crate::__is_valid_key_path("."));
+        assert!(!crate::__is_valid_key_path(".key"));
+        assert!(!crate::__is_valid_key_path("key."));
+        assert!(!crate::__is_valid_key_path("child..value"));
+    }
+
+    #[cfg(feature = "dotenv")]
+    #[test]
+    fn initialize_dotenv_line_endings() {
+        let dir = tempfile::tempdir().unwrap();
+        let dotenv_path = dir.path().join(".env.toml");
+        std::fs::write(
+            &dotenv_path,
+            "TEST_DOTENV_LINE_ENDINGS_LF = \"line 1\\r\\nline 2\"\r\n\
+            TEST_DOTENV_LINE_ENDINGS_PRESERVE = \"line 1\\r\\nline 2\"\r\n",
+        )
+        .unwrap();
+
+        crate::initialize::<toml::Value>(crate::Args {
+            dotenv_path: &dotenv_path,
+            ..crate::Args::default()
+        })
+        .unwrap();
+        assert_eq!(
+            std::env::var("TEST_DOTENV_LINE_ENDINGS_LF").unwrap(),
+            "line 1\nline 2"
+        );
+
+        crate::initialize::<toml::Value>(crate::Args {
+            dotenv_path: &dotenv_path,
+            dotenv_line_endings: crate::LineEndings::Preserve,
+            ..crate::Args::default()
+        })
+        .unwrap();
+        assert_eq!(
+            std::env::var("TEST_DOTENV_LINE_ENDINGS_PRESERVE").unwrap(),
+            "line 1\r\nline 2"
+        );
+    }
+
+    #[cfg(feature = "dotenv")]
+    #[test]
+    fn initialize_dotenv_floats() {
+        let dir = tempfile::tempdir().unwrap();
+        let dotenv_path = dir.path().join(".env.toml");
+        std::fs::write(
+            &dotenv_path,
+            "TEST_DOTENV_FLOATS_WHOLE = 1.0\n\
+            TEST_DOTENV_FLOATS_LARGE = 1e300\n\
+            TEST_DOTENV_FLOATS_NEGATIVE_ZERO = -0.0\n\
+            TEST_DOTENV_FLOATS_INF = -inf\n",
+        )
+        .unwrap();
+
+        crate::initialize::<toml::Value>(crate::Args {
+            dotenv_path: &dotenv_path,
+            ..crate::Args::default()
+        })
+        .unwrap();
+        for (name, expected) in [
+            ("TEST_DOTENV_FLOATS_WHOLE", 1.0),
+            ("TEST_DOTENV_FLOATS_LARGE", 1e300),
+            ("TEST_DOTENV_FLOATS_NEGATIVE_ZERO", -0.0),
+            ("TEST_DOTENV_FLOATS_INF", f64::NEG_INFINITY),
+        ] {
+            let value = std::env::var(name).unwrap();
+            let parsed = parse_toml_value(value, NumberInference::PreferInteger, false);
+            let parsed = parsed.as_float().unwrap();
+            assert_eq!(parsed.to_bits(), f64::to_bits(expected), "{name}");
+        }
+
+        let error = crate::initialize::<toml::Value>(crate::Args {
+            dotenv_path: &dotenv_path,
+            dotenv_non_finite_floats: crate::NonFiniteFloats::Error,
+            ..crate::Args::default()
+        })
+        .unwrap_err();
+        assert!(error.to_string().contains("TEST_DOTENV_FLOATS_INF"));
+    }
+
+    #[cfg(feature = "dotenv")]
+    #[test]
+    fn initialize_unit() {
+        #[derive(serde::Serialize, serde::Deserialize)]
+        struct Unit;
+
+        let dir = tempfile::tempdir().unwrap();
+        let dotenv_path = dir.path().join(".env.toml");
+        std::fs::write(
+            &dotenv_path,
+            "TEST_INITIALIZE_UNIT=\"exported\"\n[CONFIG]\nvalue=1",
+        )
+        .unwrap();
+
+        let config: Option<()> = crate::initialize(crate::Args {
+            dotenv_path: &dotenv_path,
+            logging: crate::Logging::StdOut,
+            ..crate::Args::default()
+        })
+        .unwrap();
+        assert_eq!(config, Some(()));
+        assert_eq!(std::env::var("TEST_INITIALIZE_UNIT").unwrap(), "exported");
+
+        let config: Option<Unit> = crate::initialize(crate::Args {
+            dotenv_path: &dotenv_path,
+            ..crate::Args::default()
+        })
+        .unwrap();
+        assert!(config.is_some());
+    }
+
+    #[test]
+    fn initialize_schema() {
+        #[derive(serde::Serialize, serde::Deserialize, Default, Debug)]
+        struct Config {
+            port: u16,
+            name: String,
+        }
+
+        std::env::set_var("TEST_SCHEMA_PORT", "1883");
+        std::env::set_var("TEST_SCHEMA_NAME", "1883");
+        let config: Config = crate::initialize(crate::Args {
+            map_env: crate::map_env! {
+                "TEST_SCHEMA_PORT" => "port",
+                "TEST_SCHEMA_NAME" => "name",
+            },
+            schema: Some(Value::try_from(Config::default()).unwrap()),
+            ..crate::Args::default()
+        })
+        .unwrap()
+        .unwrap();
+        assert_eq!(config.port, 1883);
+        assert_eq!(config.name, "1883");
+
+        std::env::set_var("TEST_SCHEMA_INVALID_PORT", "not a port");
+        let error = crate::initialize::<Config>(crate::Args {
+            map_env: crate::map_env! { "TEST_SCHEMA_INVALID_PORT" => "port" },
+            schema: Some(Value::try_from(Config::default()).unwrap()),
+            ..crate::Args::default()
+        })
+        .unwrap_err();
+        assert!(error.to_string().contains("as integer"), "{error}");
+    }
+
+    #[test]
+    fn initialize_schema_table_array() {
+        #[derive(serde::Serialize, serde::Deserialize, Debug)]
+        struct Endpoint {
+            url: String,
+            weight: f64,
+        }
+
+        #[derive(serde::Serialize, serde::Deserialize, Debug)]
+        struct Config {
+            endpoints: Vec<Endpoint>,
+        }
+
+        let schema: Value = toml::from_str(
+            r#"
+            [[endpoints]]
+            url = ""
+            weight = 1.0
+            "#,
+        )
+        .unwrap();
+
+        std::env::set_var(
+            "TEST_SCHEMA_TABLE_ARRAY",
+            r#"[{ url = "a", weight = 2 }, { url = "b", weight = 0.5 }]"#,
+        );
+        let config: Config = crate::initialize(crate::Args {
+            map_env: crate::map_env! { "TEST_SCHEMA_TABLE_ARRAY" => "endpoints" },
+            schema: Some(schema.clone()),
+            ..crate::Args::default()
+        })
+        .unwrap()
+        .unwrap();
+        assert_eq!(config.endpoints.len(), 2);
+        assert_eq!(config.endpoints[0].url, "a");
+        assert_eq!(config.endpoints[0].weight, 2.0);
+        assert_eq!(config.endpoints[1].url, "b");
+
+        #[cfg(feature = "json")]
+        {
+            std::env::set_var(
+                "TEST_SCHEMA_TABLE_ARRAY_JSON",
+                r#"[{"url": "a", "weight": 2}]"#,
+            );
+            let config: Config = crate::initialize(crate::Args {
+                map_env: crate::map_env! { "TEST_SCHEMA_TABLE_ARRAY_JSON" => "endpoints" },
+                schema: Some(schema.clone()),
+                ..crate::Args::default()
+            })
+            .unwrap()
+            .unwrap();
+            assert_eq!(config.endpoints[0].url, "a");
+        }
+
+        std::env::set_var(
+            "TEST_SCHEMA_TABLE_ARRAY_INVALID",
+            r#"[{ url = "a" }, { url = 1 }]"#,
+        );
+        let error = crate::initialize::<Config>(crate::Args {
+            map_env: crate::map_env! { "TEST_SCHEMA_TABLE_ARRAY_INVALID" => "endpoints" },
+            schema: Some(schema.clone()),
+            ..crate::Args::default()
+        })
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Error parsing environment variable TEST_SCHEMA_TABLE_ARRAY_INVALID (mapped to \
+            endpoints): expected string at `endpoints.1.url`"
+        );
+
+        std::env::set_var("TEST_SCHEMA_TABLE_ARRAY_NOT_ARRAY", "a,b");
+        let error = crate::initialize::<Config>(crate::Args {
+            map_env: crate::map_env! { "TEST_SCHEMA_TABLE_ARRAY_NOT_ARRAY" => "endpoints" },
+            schema: Some(schema),
+            ..crate::Args::default()
+        })
+        .unwrap_err();
+        assert!(error.to_string().contains("as array of tables"), "{error}");
+    }
+
+    #[test]
+    fn initialize_value_hints() {
+        #[derive(serde::Serialize, serde::Deserialize, Default)]
+        struct Config {
+            port: u16,
+            version: String,
+            zip: String,
+        }
+
+        std::env::set_var("TEST_VALUE_HINTS_PORT", "8080");
+        std::env::set_var("TEST_VALUE_HINTS_VERSION", "1.10");
+        std::env::set_var("TEST_VALUE_HINTS_ZIP", "01234");
+        let config: Config = crate::initialize(crate::Args {
+            map_env: crate::map_env! {
+                "TEST_VALUE_HINTS_PORT" => "port",
+                "TEST_VALUE_HINTS_VERSION" => "version",
+                "TEST_VALUE_HINTS_ZIP" => "zip",
+            },
+            value_hints: [
+                ("TEST_VALUE_HINTS_PORT", crate::ValueHint::Integer),
+                ("TEST_VALUE_HINTS_VERSION", crate::ValueHint::String),
+                ("TEST_VALUE_HINTS_ZIP", crate::ValueHint::String),
+            ]
+            .into_iter()
+            .collect(),
+            ..crate::Args::default()
+        })
+        .unwrap()
+        .unwrap();
+        assert_eq!(config.port, 8080);
+        assert_eq!(config.version, "1.10");
+        assert_eq!(config.zip, "01234");
+    }
+
+    #[cfg(feature = "auto-map-env")]
+    #[test]
+    fn initialize_auto_map_env_no_infer_types() {
+        #[derive(serde::Serialize, serde::Deserialize)]
+        struct Config {
+            version: String,
+            port: u16,
+        }
+
+        std::env::set_var("TEST_NO_INFER__VERSION", "1.10");
+        std::env::set_var("TEST_NO_INFER__PORT", "8080");
+        let config: Config = crate::initialize(crate::Args {
+            auto_map_env: Some(crate::AutoMapEnvArgs {
+                prefix: Some("TEST_NO_INFER"),
+                infer_types: false,
+                ..crate::AutoMapEnvArgs::default()
+            }),
+            value_hints: [("TEST_NO_INFER__PORT", crate::ValueHint::Integer)]
+                .into_iter()
+                .collect(),
+            ..crate::Args::default()
+        })
+        .unwrap()
+        .unwrap();
+        assert_eq!(config.version, "1.10");
+        assert_eq!(config.port, 8080);
+    }
+
+    #[cfg(feature = "auto-map-env")]
+    #[test]
+    fn initialize_list_delimiter() {
+        #[derive(serde::Serialize, serde::Deserialize, Default)]
+        struct Config {
+            allowed_hosts: Vec<String>,
+            ports: Vec<u16>,
+            weights: Vec<f64>,
+            name: String,
+        }
+
+        std::env::set_var("TEST_LIST__ALLOWED_HOSTS", "a.com, b.com,c.com");
+        std::env::set_var("TEST_LIST__PORTS", "80");
+        std::env::set_var("TEST_LIST__NAME", "name");
+        std::env::set_var("TEST_LIST_WEIGHTS", "1;2.5");
+        let config: Config = crate::initialize(crate::Args {
+            auto_map_env: Some(crate::AutoMapEnvArgs {
+                prefix: Some("TEST_LIST"),
+                list_delimiter: Some(','),
+                ..crate::AutoMapEnvArgs::default()
+            }),
+            map_env: crate::map_env! { "TEST_LIST_WEIGHTS" => "weights" },
+            value_hints: [("TEST_LIST_WEIGHTS", crate::ValueHint::List(';'))]
+                .into_iter()
+                .collect(),
+            schema: Some(toml::Value::try_from(Config::default()).unwrap()),
+            number_inference: crate::NumberInference::PreferInteger,
+            ..crate::Args::default()
+        })
+        .unwrap()
+        .unwrap();
+        assert_eq!(config.allowed_hosts, vec!["a.com", "b.com", "c.com"]);
+        assert_eq!(config.ports, vec![80]);
+        assert_eq!(config.weights, vec![1.0, 2.5]);
+        assert_eq!(config.name, "name");
+
+        // Without a schema, the variables mapped to arrays are unknown.
+        let error = crate::initialize::<Value>(crate::Args {
+            auto_map_env: Some(crate::AutoMapEnvArgs {
+                prefix: Some("TEST_LIST"),
+                list_delimiter: Some(','),
+                ..crate::AutoMapEnvArgs::default()
+            }),
+            ..crate::Args::default()
+        })
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "AutoMapEnvArgs::list_delimiter requires Args::schema to be set"
+        );
+    }
+
+    #[test]
+    fn initialize_optional_sources() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = dir.path().join("config.toml");
+        std::fs::write(&config_path, "not valid toml").unwrap();
+        std::env::set_var("TEST_OPTIONAL_SOURCES_VALUE", "value");
+        let args = || crate::Args {
+            config_path: Some(&config_path),
+            map_env: crate::map_env! { "TEST_OPTIONAL_SOURCES_VALUE" => "value" },
+            ..crate::Args::default()
+        };
+
+        crate::initialize::<toml::Value>(args()).unwrap_err();
+
+        let (config, report) = crate::initialize_with_report::<toml::Value>(crate::Args {
+            optional_sources: vec![crate::SourceKind::File],
+            ..args()
+        })
+        .unwrap();
+        assert_eq!(config.unwrap()["value"].as_str(), Some("value"));
+        assert_eq!(report.warnings.len(), 1);
+        assert_eq!(report.warnings[0].kind, crate::SourceKind::File);
+    }
+
+    #[test]
+    fn initialize_optional_paths() {
+        struct Failing {
+            optional: bool,
+        }
+
+        impl crate::Source for Failing {
+            fn load(
+                &self,
+            ) -> std::result::Result<Option<Value>, Box<dyn std::error::Error + Send + Sync>>
+            {
+                Err("unavailable".into())
+            }
+
+            fn description(&self) -> String {
+                "failing".to_owned()
+            }
+
+            fn optional(&self) -> bool {
+                self.optional
+            }
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("base.toml"), "value = \"base\"").unwrap();
+        std::fs::write(dir.path().join("overlay.toml"), "not valid toml").unwrap();
+        let args = || crate::Args {
+            base_dir: Some(dir.path()),
+            config_paths: vec![
+                std::path::Path::new("base.toml"),
+                std::path::Path::new("overlay.toml"),
+            ],
+            ..crate::Args::default()
+        };
+
+        crate::initialize::<Value>(args()).unwrap_err();
+
+        let (config, report) = crate::initialize_with_report::<Value>(crate::Args {
+            optional_paths: vec![std::path::Path::new("overlay.toml")],
+            sources: vec![Box::new(Failing { optional: true })],
+            ..args()
+        })
+        .unwrap();
+        assert_eq!(config.unwrap()["value"].as_str(), Some("base"));
+        assert_eq!(report.warnings.len(), 2);
+        assert_eq!(report.warnings[0].kind, crate::SourceKind::File);
+        assert_eq!(report.warnings[1].kind, crate::SourceKind::Custom);
+
+        // Only the overlay is optional.
+        std::fs::write(dir.path().join("base.toml"), "not valid toml").unwrap();
+        crate::initialize::<Value>(crate::Args {
+            optional_paths: vec![std::path::Path::new("overlay.toml")],
+            ..args()
+        })
+        .unwrap_err();
+        crate::initialize::<Value>(crate::Args {
+            sources: vec![Box::new(Failing { optional: false })],
+            ..crate::Args::default()
+        })
+        .unwrap_err();
+    }
+
+    #[cfg(feature = "dotenv")]
+    #[test]
+    fn initialize_optional_dotenv_sets_no_variables() {
+        let dir = tempfile::tempdir().unwrap();
+        let dotenv_path = dir.path().join(".env.toml");
+        std::fs::write(
+            &dotenv_path,
+            "TEST_OPTIONAL_DOTENV_A = \"a\"\nTEST_OPTIONAL_DOTENV_B = [1, 2]",
+        )
+        .unwrap();
+
+        let (_, report) = crate::initialize_with_report::<Value>(crate::Args {
+            dotenv_path: &dotenv_path,
+            optional_paths: vec![&dotenv_path],
+            ..crate::Args::default()
+        })
+        .unwrap();
+        assert_eq!(report.warnings.len(), 1);
+        assert_eq!(report.warnings[0].kind, crate::SourceKind::DotEnv);
+        assert!(std::env::var_os("TEST_OPTIONAL_DOTENV_A").is_none());
+        assert!(std::env::var_os("TEST_OPTIONAL_DOTENV_B").is_none());
+    }
+
+    #[cfg(feature = "log-init")]
+    #[test]
+    fn init_logger_invalid() {
+        let config: Value = toml::from_str("[log]\nformat = \"fancy\"").unwrap();
+        assert_eq!(
+            crate::init_logger(&config).unwrap_err().to_string(),
+            "Invalid value \"fancy\" for `log.format` (expected \"full\" or \"compact\")"
+        );
+        let config: Value = toml::from_str("[log]\nlevel = 1").unwrap();
+        assert!(crate::init_logger(&config).is_err());
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn initialize_json() {
+        std::env::set_var(
+            "TEST_JSON_DATABASE",
+            r#"{"host": "localhost", "ports": [5432, 5433]}"#,
+        );
+        std::env::set_var("TEST_JSON_INVALID", "{not json");
+        let config: Value = crate::initialize(crate::Args {
+            map_env: crate::map_env! {
+                "TEST_JSON_DATABASE" => "database",
+                "TEST_JSON_INVALID" => "invalid",
+            },
+            json: true,
+            ..crate::Args::default()
+        })
+        .unwrap()
+        .unwrap();
+        assert_eq!(config["database"]["host"].as_str(), Some("localhost"));
+        assert_eq!(
+            config["database"]["ports"],
+            Value::Array(vec![5432.into(), 5433.into()])
+        );
+        assert_eq!(config["invalid"].as_str(), Some("{not json"));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn initialize_json_value() {
+        std::env::set_var("TEST_JSON_VALUE_PORT", "8080");
+        let config = crate::initialize_json(crate::Args {
+            config_inline: Some(crate::InlineSource {
+                label: "json",
+                content: "released = 1979-05-27T07:32:00Z\nratio = nan\n[server]\nhosts = [\"a\", \"b\"]",
+            }),
+            map_env: crate::map_env! { "TEST_JSON_VALUE_PORT" => "server.port" },
+            number_inference: crate::NumberInference::PreferInteger,
+            ..crate::Args::default()
+        })
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            config,
+            serde_json::json!({
+                "released": "1979-05-27T07:32:00Z",
+                "ratio": null,
+                "server": {
+                    "hosts": ["a", "b"],
+                    "port": 8080,
+                },
+            })
+        );
+        assert!(config["server"]["port"].is_i64());
+    }
+
+    #[test]
+    fn initialize_value_parser() {
+        std::env::set_var("TEST_VALUE_PARSER_LABELS", "a=1;b=2");
+        std::env::set_var("TEST_VALUE_PARSER_PORT", "8080");
+        let config: Value = crate::initialize(crate::Args {
+            map_env: crate::map_env! {
+                "TEST_VALUE_PARSER_LABELS" => "labels",
+                "TEST_VALUE_PARSER_PORT" => "port",
+            },
+            value_parser: Some(Box::new(|value, key| {
+                if key.to_string() != "labels" {
+                    return None;
+                }
+                let table = value
+                    .split(';')
+                    .filter_map(|pair| pair.split_once('='))
+                    .map(|(k, v)| (k.to_owned(), Value::String(v.to_owned())))
+                    .collect();
+                Some(Value::Table(table))
+            })),
+            ..crate::Args::default()
+        })
+        .unwrap()
+        .unwrap();
+        assert_eq!(config["labels"]["a"].as_str(), Some("1"));
+        assert_eq!(config["labels"]["b"].as_str(), Some("2"));
+        assert_eq!(config["port"].as_float(), Some(8080.0));
+    }
+
+    #[test]
+    fn initialize_defaults() {
+        std::env::set_var("TEST_DEFAULTS_PORT", "8080");
+        let (config, report) = crate::initialize_with_report::<Value>(crate::Args {
+            defaults: Some("host = \"localhost\"\nport = 80"),
+            map_env: crate::map_env! { "TEST_DEFAULTS_PORT" => "port" },
+            number_inference: crate::NumberInference::PreferInteger,
+            ..crate::Args::default()
+        })
+        .unwrap();
+        let config = config.unwrap();
+        assert_eq!(config["host"].as_str(), Some("localhost"));
+        assert_eq!(config["port"].as_integer(), Some(8080));
+        assert!(matches!(
+            report.provenance.get("host"),
+            Some(crate::ConfigSource::Defaults)
+        ));
+
+        crate::initialize::<Value>(crate::Args {
+            defaults: Some("not valid toml"),
+            ..crate::Args::default()
+        })
+        .unwrap_err();
+    }
+
+    #[cfg(feature = "humantime")]
+    #[test]
+    fn initialize_duration() {
+        use crate::{DurationFormat, ValueHint};
+
+        #[derive(serde::Serialize, serde::Deserialize)]
+        struct Config {
+            timeout_secs: u64,
+            timeout_millis: u64,
+            timeout: std::time::Duration,
+        }
+
+        std::env::set_var("TEST_DURATION_SECS", "5m");
+        std::env::set_var("TEST_DURATION_MILLIS", "30s");
+        std::env::set_var("TEST_DURATION_STRUCTURED", "2h");
+        let config: Config = crate::initialize(crate::Args {
+            map_env: crate::map_env! {
+                "TEST_DURATION_SECS" => "timeout_secs",
+                "TEST_DURATION_MILLIS" => "timeout_millis",
+                "TEST_DURATION_STRUCTURED" => "timeout",
+            },
+            value_hints: [
+                (
+                    "TEST_DURATION_SECS",
+                    ValueHint::Duration(DurationFormat::Seconds),
+                ),
+                (
+                    "TEST_DURATION_MILLIS",
+                    ValueHint::Duration(DurationFormat::Milliseconds),
+                ),
+                (
+                    "TEST_DURATION_STRUCTURED",
+                    ValueHint::Duration(DurationFormat::Structured),
+                ),
+            ]
+            .into_iter()
+            .collect(),
+            ..crate::Args::default()
+        })
+        .unwrap()
+        .unwrap();
+        assert_eq!(config.timeout_secs, 300);
+        assert_eq!(config.timeout_millis, 30_000);
+        assert_eq!(config.timeout, std::time::Duration::from_secs(7200));
+    }
+
+    #[cfg(feature = "bytesize")]
+    #[test]
+    fn initialize_byte_size() {
+        std::env::set_var("TEST_BYTE_SIZE_CACHE", "10MB");
+        std::env::set_var("TEST_BYTE_SIZE_BUFFER", "512KiB");
+        std::env::set_var("TEST_BYTE_SIZE_INVALID", "lots");
+        let args = |names: &[&'static str]| crate::Args {
+            map_env: crate::map_env! {
+                "TEST_BYTE_SIZE_CACHE" => "cache",
+                "TEST_BYTE_SIZE_BUFFER" => "buffer",
+                "TEST_BYTE_SIZE_INVALID" => "invalid",
+            },
+            value_hints: names
+                .iter()
+                .map(|name| (*name, crate::ValueHint::ByteSize))
+                .collect(),
+            ..crate::Args::default()
+        };
+
+        let config: Value =
+            crate::initialize(args(&["TEST_BYTE_SIZE_CACHE", "TEST_BYTE_SIZE_BUFFER"]))
+                .unwrap()
+                .unwrap();
+        assert_eq!(config["cache"].as_integer(), Some(10_000_000));
+        assert_eq!(config["buffer"].as_integer(), Some(524_288));
+        assert_eq!(config["invalid"].as_str(), Some("lots"));
+
+        crate::initialize::<Value>(args(&["TEST_BYTE_SIZE_INVALID"])).unwrap_err();
+    }
+
+    #[cfg(feature = "archive")]
+    #[test]
+    fn split_archive_path() {
+        use std::path::Path;
+
+        use super::ArchiveFormat;
+
+        assert_eq!(
+            super::split_archive_path(Path::new("bundle.zip!config/config.toml")),
+            Some((
+                Path::new("bundle.zip"),
+                ArchiveFormat::Zip,
+                "config/config.toml"
+            ))
+        );
+        assert_eq!(
+            super::split_archive_path(Path::new("assets/bundle.tar!config.toml")),
+            Some((
+                Path::new("assets/bundle.tar"),
+                ArchiveFormat::Tar,
+                "config.toml"
+            ))
+        );
+        assert_eq!(
+            super::split_archive_path(Path::new("bundle.tar.gz!config.toml")),
+            Some((
+                Path::new("bundle.tar.gz"),
+                ArchiveFormat::TarGz,
+                "config.toml"
+            ))
+        );
+        assert_eq!(
+            super::split_archive_path(Path::new("bundle.gz!config.toml")),
+            None
+        );
+        assert_eq!(
+            super::split_archive_path(Path::new("bundle.tgz!config.toml")),
+            None
+        );
+        assert_eq!(super::split_archive_path(Path::new("config!.toml")), None);
+        assert_eq!(super::split_archive_path(Path::new("config.toml")), None);
+    }
+
+    #[cfg(feature = "archive")]
+    #[test]
+    fn initialize_archive() {
+        use std::io::Write;
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = b"value = \"archived\"";
+
+        let mut zip =
+            zip::ZipWriter::new(std::fs::File::create(dir.path().join("bundle.zip")).unwrap());
+        zip.start_file("config/config.toml", zip::write::FileOptions::default())
+            .unwrap();
+        zip.write_all(config).unwrap();
+        zip.finish().unwrap();
+
+        fn append<W: std::io::Write>(builder: &mut tar::Builder<W>, config: &[u8]) {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(config.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, "./config/config.toml", config)
+                .unwrap();
+        }
+        let mut tar =
+            tar::Builder::new(std::fs::File::create(dir.path().join("bundle.tar")).unwrap());
+        append(&mut tar, config);
+        tar.finish().unwrap();
+        let gz = flate2::write::GzEncoder::new(
+            std::fs::File::create(dir.path().join("bundle.tar.gz")).unwrap(),
+            flate2::Compression::default(),
+        );
+        let mut tar_gz = tar::Builder::new(gz);
+        append(&mut tar_gz, config);
+        tar_gz.into_inner().unwrap().finish().unwrap();
+
+        let load = |path: &str| {
+            let path = dir.path().join(path);
+            crate::initialize::<Value>(crate::Args {
+                config_path: Some(&path),
+                ..crate::Args::default()
+            })
+            .unwrap()
+        };
+        for path in [
+            "bundle.zip!config/config.toml",
+            "bundle.tar!config/config.toml",
+            "bundle.tar!./config/config.toml",
+            "bundle.tar.gz!config/config.toml",
+        ] {
+            assert_eq!(
+                load(path).unwrap()["value"].as_str(),
+                Some("archived"),
+                "{path}"
+            );
+        }
+        assert!(load("bundle.zip!missing.toml").is_none());
+        assert!(load("bundle.tar.gz!missing.toml").is_none());
+        assert!(load("missing.tar!config/config.toml").is_none());
+    }
+
+    #[test]
+    fn container_config_detect() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = vec![
+            dir.path().join("missing.toml"),
+            dir.path().join("config.toml"),
+            dir.path().join("other.toml"),
+        ];
+        std::fs::write(&paths[1], "value = 1").unwrap();
+        std::fs::write(&paths[2], "value = 2").unwrap();
+        std::env::set_var("TEST_CONTAINER_APP__VALUE", "3");
+        // Neither the config variable itself nor variables which only share the prefix (without
+        // the divider) are automatically mapped.
+        std::env::set_var("TEST_CONTAINER_APP", "value = 4");
+        std::env::set_var("TEST_CONTAINER_APPLE", "5");
+
+        let container = crate::ContainerConfig::detect_paths("test-container-app", paths.clone());
+        assert_eq!(container.config_path.as_ref(), Some(&paths[1]));
+        assert_eq!(container.config_variable_name, "TEST_CONTAINER_APP");
+        assert_eq!(container.variable_names, vec!["TEST_CONTAINER_APP__VALUE"]);
+        assert_eq!(
+            container.to_string(),
+            format!(
+                "Config files:\n  {:?}: not found\n  {:?}: using\n  {:?}: found (ignored)\n\
+                Environment variables with prefix TEST_CONTAINER_APP: TEST_CONTAINER_APP__VALUE",
+                paths[0], paths[1], paths[2]
+            )
+        );
+    }
+
+    #[test]
+    fn initialize_file_suffix() {
+        let dir = tempfile::tempdir().unwrap();
+        let password_path = dir.path().join("db_password");
+        std::fs::write(&password_path, "secret\n").unwrap();
+        std::env::set_var("TEST_FILE_SUFFIX_PASSWORD_FILE", &password_path);
+        std::env::set_var("TEST_FILE_SUFFIX_USER", "user");
+        std::env::set_var("TEST_FILE_SUFFIX_USER_FILE", &password_path);
+
+        let (config, report) = crate::initialize_with_report::<Value>(crate::Args {
+            map_env: crate::map_env! {
+                "TEST_FILE_SUFFIX_PASSWORD" => "db.password",
+                "TEST_FILE_SUFFIX_USER" => "db.user",
+            },
+            file_suffix: Some(crate::DEFAULT_FILE_SUFFIX),
+            ..crate::Args::default()
+        })
+        .unwrap();
+        let config = config.unwrap();
+        assert_eq!(config["db"]["password"].as_str(), Some("secret"));
+        assert_eq!(config["db"]["user"].as_str(), Some("user"));
+        assert!(matches!(
+            report.provenance.get("db.password"),
+            Some(crate::ConfigSource::Environment { variable_names })
+                if variable_names == &["TEST_FILE_SUFFIX_PASSWORD_FILE"]
+        ));
+    }
+
+    #[cfg(feature = "auto-map-env")]
+    #[test]
+    fn initialize_auto_map_env_file_suffix() {
+        let dir = tempfile::tempdir().unwrap();
+        let password_path = dir.path().join("db_password");
+        std::fs::write(&password_path, "secret").unwrap();
+        std::env::set_var("TEST_AUTO_FILE_SUFFIX__DB__PASSWORD_FILE", &password_path);
+
+        let config: Value = crate::initialize(crate::Args {
+            auto_map_env: Some(crate::AutoMapEnvArgs {
+                prefix: Some("TEST_AUTO_FILE_SUFFIX"),
+                ..crate::AutoMapEnvArgs::default()
+            }),
+            file_suffix: Some(crate::DEFAULT_FILE_SUFFIX),
+            schema: Some(toml::from_str("[db]\npassword = \"\"").unwrap()),
+            ..crate::Args::default()
+        })
+        .unwrap()
+        .unwrap();
+        assert_eq!(config["db"]["password"].as_str(), Some("secret"));
+    }
+
+    #[cfg(feature = "auto-map-env")]
+    #[test]
+    fn initialize_auto_map_env_file_suffix_keys() {
+        // Variables for keys which end with the suffix, including a top level `file` key whose
+        // variable is only the prefix followed by the suffix.
+        let dir = tempfile::tempdir().unwrap();
+        let log_path = dir.path().join("app.log");
+        std::fs::write(&log_path, "contents").unwrap();
+        std::env::set_var("TEST_AUTO_SUFFIX_KEYS__FILE", "/tmp/x");
+        std::env::set_var("TEST_AUTO_SUFFIX_KEYS__LOG_FILE", &log_path);
+
+        let args = |schema: Option<&str>| crate::Args {
+            auto_map_env: Some(crate::AutoMapEnvArgs {
+                prefix: Some("TEST_AUTO_SUFFIX_KEYS"),
+                ..crate::AutoMapEnvArgs::default()
+            }),
+            file_suffix: Some(crate::DEFAULT_FILE_SUFFIX),
+            schema: schema.map(|schema| toml::from_str(schema).unwrap()),
+            ..crate::Args::default()
+        };
+
+        // Without `log` in the schema, the variable only sets `log_file`, and the file isn't read.
+        assert!(!crate::accessed_paths(&args(None)).contains(&log_path));
+        let config: Value = crate::initialize(args(None)).unwrap().unwrap();
+        assert_eq!(config["file"].as_str(), Some("/tmp/x"));
+        assert_eq!(config["log_file"].as_str(), log_path.to_str());
+        assert!(config.get("log").is_none());
+
+        // The file doesn't need to exist, e.g. for a log file which is created later.
+        let missing_path = dir.path().join("missing.log");
+        std::env::set_var("TEST_AUTO_SUFFIX_KEYS__LOG_FILE", &missing_path);
+        let config: Value = crate::initialize(args(None)).unwrap().unwrap();
+        assert_eq!(config["log_file"].as_str(), missing_path.to_str());
+        let config: Value = crate::initialize(args(Some("[log]\nlevel = \"info\"")))
+            .unwrap()
+            .unwrap();
+        assert_eq!(config["log_file"].as_str(), missing_path.to_str());
+        assert!(config.get("log").is_none());
+
+        // With `log` in the schema, the variable also sets `log` from the file.
+        std::env::set_var("TEST_AUTO_SUFFIX_KEYS__LOG_FILE", &log_path);
+        let schema = Some("log = \"\"\nlog_file = \"\"");
+        assert!(crate::accessed_paths(&args(schema)).contains(&log_path));
+        let config: Value = crate::initialize(args(schema)).unwrap().unwrap();
+        assert_eq!(config["log_file"].as_str(), log_path.to_str());
+        assert_eq!(config["log"].as_str(), Some("contents"));
+        std::env::remove_var("TEST_AUTO_SUFFIX_KEYS__FILE");
+        std::env::remove_var("TEST_AUTO_SUFFIX_KEYS__LOG_FILE");
+    }
+
+    #[test]
+    fn render_template_terraform() {
+        let schema: Value = toml::from_str(
+            "name = \"a \\\"quoted\\\" name\"\nports = [80, 443]\n[tls]\nenabled = true",
+        )
+        .unwrap();
+        let variables = crate::env_variables(&schema, &crate::Conventions::default());
+        assert_eq!(
+            crate::render_template(&variables, crate::TemplateFormat::Terraform),
+            r#"variable "config__name" {
+  description = "Sets `name` in the configuration."
+  type        = string
+  default     = "a \"quoted\" name"
+}
+
+variable "config__ports" {
+  description = "Sets `ports` in the configuration."
+  type        = string
+  default     = "[80, 443]"
+}
+
+variable "config__tls__enabled" {
+  description = "Sets `tls.enabled` in the configuration."
+  type        = string
+  default     = "true"
+}
+"#
+        );
+    }
+
+    #[test]
+    fn initialize_big_int() {
+        #[derive(serde::Serialize, serde::Deserialize, Default)]
+        struct Config {
+            #[serde(with = "crate::big_int")]
+            id: u64,
+            #[serde(with = "crate::big_int")]
+            token: u128,
+        }
+
+        std::env::set_var("TEST_BIG_INT_ID", "18446744073709551615");
+        std::env::set_var(
+            "TEST_BIG_INT_TOKEN",
+            "340282366920938463463374607431768211455",
+        );
+        let config: Config = crate::initialize(crate::Args {
+            map_env: crate::map_env! {
+                "TEST_BIG_INT_ID" => "id",
+                "TEST_BIG_INT_TOKEN" => "token",
+            },
+            schema: Some(Value::try_from(Config::default()).unwrap()),
+            ..crate::Args::default()
+        })
+        .unwrap()
+        .unwrap();
+        assert_eq!(config.id, u64::MAX);
+        assert_eq!(config.token, u128::MAX);
+    }
+
+    #[test]
+    fn redact_wildcard() {
+        let mut value: Value = toml::from_str(
+            r#"
+            [db.primary]
+            password = "secret"
+            user = "user"
+            [db.replica]
+            password = "secret"
+            [tls]
+            key = "key"
+            "#,
+        )
+        .unwrap();
+        super::redact(
+            &mut value,
+            &["db.*.password".parse().unwrap(), "tls".parse().unwrap()],
+        );
+        assert_eq!(
+            value["db"]["primary"]["password"].as_str(),
+            Some(crate::REDACTED)
+        );
+        assert_eq!(value["db"]["primary"]["user"].as_str(), Some("user"));
+        assert_eq!(
+            value["db"]["replica"]["password"].as_str(),
+            Some(crate::REDACTED)
+        );
+        assert_eq!(value["tls"].as_str(), Some(crate::REDACTED));
+
+        std::env::set_var("TEST_REDACT_PORT", "secret");
+        let error = crate::initialize::<Value>(crate::Args {
+            map_env: crate::map_env! { "TEST_REDACT_PORT" => "db.port" },
+            value_hints: [("TEST_REDACT_PORT", crate::ValueHint::Integer)]
+                .into_iter()
+                .collect(),
+            redact: vec!["db.*".parse().unwrap()],
+            ..crate::Args::default()
+        })
+        .unwrap_err();
+        assert!(!error.to_string().contains("secret"));
+    }
+
+    #[test]
+    fn describe_fingerprint() {
+        let describe = |password: &str| {
+            std::env::set_var("TEST_FINGERPRINT_PASSWORD", password);
+            let (_config, report) = crate::initialize_with_report::<Value>(crate::Args {
+                map_env: crate::map_env! { "TEST_FINGERPRINT_PASSWORD" => "db.password" },
+                redact: vec!["db.password".parse().unwrap()],
+                ..crate::Args::default()
+            })
+            .unwrap();
+            report.describe()
+        };
+        let old = describe("hunter2");
+        let new = describe("hunter3");
+        assert_eq!(old.config, new.config);
+        assert_eq!(old.config["db"]["password"].as_str(), Some(crate::REDACTED));
+        assert_eq!(old.fingerprint, new.fingerprint);
+
+        std::env::set_var("TEST_FINGERPRINT_USER", "admin");
+        let (_config, report) = crate::initialize_with_report::<Value>(crate::Args {
+            map_env: crate::map_env! {
+                "TEST_FINGERPRINT_PASSWORD" => "db.password",
+                "TEST_FINGERPRINT_USER" => "db.user",
+            },
+            redact: vec!["db.password".parse().unwrap()],
+            ..crate::Args::default()
+        })
+        .unwrap();
+        assert_ne!(report.describe().fingerprint, new.fingerprint);
+    }
+
+    #[cfg(feature = "log")]
+    #[test]
+    fn audited_config() {
+        use crate::TomlValueExt;
+
+        static RECORDS: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+        struct CapturingLogger;
+        impl log::Log for CapturingLogger {
+            fn enabled(&self, _metadata: &log::Metadata<'_>) -> bool {
+                true
+            }
+
+            fn log(&self, record: &log::Record<'_>) {
+                RECORDS.lock().unwrap().push(record.args().to_string());
+            }
+
+            fn flush(&self) {}
+        }
+        log::set_logger(&CapturingLogger).unwrap();
+        log::set_max_level(log::LevelFilter::Info);
+        let audit_records = || -> Vec<String> {
+            std::mem::take(&mut *RECORDS.lock().unwrap())
+                .into_iter()
+                .filter(|record| record.starts_with("Accessed secret"))
+                .collect()
+        };
+
+        std::env::set_var("TEST_AUDITED_PASSWORD", "hunter2");
+        std::env::set_var("TEST_AUDITED_USER", "admin");
+        let (_config, report) = crate::initialize_with_report::<Value>(crate::Args {
+            map_env: crate::map_env! {
+                "TEST_AUDITED_PASSWORD" => "db.password",
+                "TEST_AUDITED_USER" => "db.user",
+            },
+            redact: vec!["db.password".parse().unwrap()],
+            ..crate::Args::default()
+        })
+        .unwrap();
+        let config = report.audited(crate::Logging::Log);
+
+        let user: String = config.get_as("db.user").unwrap();
+        assert_eq!(user, "admin");
+        assert!(audit_records().is_empty());
+
+        let password: String = config.get_as("db.password").unwrap();
+        assert_eq!(password, "hunter2");
+        let records = audit_records();
+        assert_eq!(records.len(), 1);
+        assert!(records[0].starts_with("Accessed secret `db.password` at "));
+
+        assert!(config.get_as::<u16>("db.password").is_err());
+        assert_eq!(audit_records().len(), 1);
+        assert!(config.get_as::<Value>("db").is_ok());
+        assert_eq!(audit_records().len(), 1);
+    }
+
+    #[test]
+    fn contains_redacted() {
+        let keys: Vec<crate::TomlKeyPath> = vec!["db.*.password".parse().unwrap()];
+        let contains = |key: &str| super::contains_redacted(&key.parse().unwrap(), &keys);
+        assert!(contains("db.primary.password"));
+        assert!(contains("db.primary"));
+        assert!(contains("db"));
+        assert!(contains(""));
+        assert!(!contains("db.primary.user"));
+        assert!(!contains("tls"));
+    }
+
+    #[test]
+    fn deserialize_with_sources() {
+        use crate::WithSource;
+
+        #[derive(Debug, serde::Deserialize)]
+        struct Db {
+            url: WithSource<String>,
+            replicas: Vec<WithSource<String>>,
+        }
+
+        #[derive(Debug, serde::Deserialize)]
+        struct Config {
+            port: WithSource<u16>,
+            db: WithSource<Db>,
+        }
+
+        std::env::set_var("TEST_WITH_SOURCES_PORT", "8080");
+        let (_config, report) = crate::initialize_with_report::<Value>(crate::Args {
+            defaults: Some("[db]\nurl = \"postgres://localhost\"\nreplicas = [\"a\", \"b\"]"),
+            map_env: crate::map_env! { "TEST_WITH_SOURCES_PORT" => "port" },
+            number_inference: crate::NumberInference::PreferInteger,
+            ..crate::Args::default()
+        })
+        .unwrap();
+
+        let config: Config = report.deserialize_with_sources().unwrap().unwrap();
+        assert_eq!(*config.port, 8080);
+        assert_eq!(config.port.key.to_string(), "port");
+        assert_eq!(
+            config.port.source.as_deref(),
+            Some("environment variables TEST_WITH_SOURCES_PORT")
+        );
+        assert_eq!(config.db.key.to_string(), "db");
+        assert_eq!(config.db.url.key.to_string(), "db.url");
+        assert_eq!(config.db.url.source.as_deref(), Some("embedded defaults"));
+        assert_eq!(config.db.replicas[1].key.to_string(), "db.replicas.1");
+        assert_eq!(*config.db.replicas[1], "b");
+        assert_eq!(
+            config.db.replicas[1].source.as_deref(),
+            Some("embedded defaults")
+        );
+
+        let config: Config = toml::from_str("port = 80\n[db]\nurl = \"x\"\nreplicas = []").unwrap();
+        assert_eq!(*config.port, 80);
+        assert!(config.port.key.is_empty());
+        assert_eq!(config.port.source, None);
+
+        std::env::set_var("TEST_WITH_SOURCES_PORT", "not a port");
+        let (_config, report) = crate::initialize_with_report::<Value>(crate::Args {
+            map_env: crate::map_env! { "TEST_WITH_SOURCES_PORT" => "port" },
+            ..crate::Args::default()
+        })
+        .unwrap();
+        let error = report.deserialize_with_sources::<Config>().unwrap_err();
+        let error = std::error::Error::source(&error).unwrap().to_string();
+        assert!(error.contains("for key `port`"), "{error}");
+    }
+
+    #[cfg(feature = "secrecy")]
+    #[test]
+    fn initialize_secret() {
+        use secrecy::ExposeSecret;
+
+        #[derive(serde::Serialize, serde::Deserialize)]
+        struct Config {
+            #[serde(with = "crate::secret")]
+            password: secrecy::SecretString,
+        }
+
+        std::env::set_var("TEST_SECRET_PASSWORD", "hunter2");
+        let config: Config = crate::initialize(crate::Args {
+            map_env: crate::map_env! { "TEST_SECRET_PASSWORD" => "password" },
+            logging: crate::Logging::StdOut,
+            ..crate::Args::default()
+        })
+        .unwrap()
+        .unwrap();
+        assert_eq!(config.password.expose_secret(), "hunter2");
+        assert_eq!(
+            Value::try_from(&config).unwrap()["password"].as_str(),
+            Some(crate::REDACTED)
+        );
+    }
+
+    #[test]
+    fn initialize_empty_values() {
+        #[derive(serde::Serialize, serde::Deserialize, Debug)]
+        struct Config {
+            host: Option<String>,
+            port: Option<u16>,
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = dir.path().join("config.toml");
+        std::fs::write(&config_path, "host = \"localhost\"\nport = 8080").unwrap();
+        std::env::set_var("TEST_EMPTY_VALUES_HOST", "");
+        std::env::set_var("TEST_EMPTY_VALUES_PORT", "");
+        let args = |empty_values| crate::Args {
+            config_path: Some(&config_path),
+            map_env: crate::map_env! {
+                "TEST_EMPTY_VALUES_HOST" => "host",
+                "TEST_EMPTY_VALUES_PORT" => "port",
+            },
+            empty_values,
+            ..crate::Args::default()
+        };
+
+        assert!(crate::initialize::<Config>(args(crate::EmptyValues::Keep)).is_err());
+
+        let config: Config = crate::initialize(args(crate::EmptyValues::Unset))
+            .unwrap()
+            .unwrap();
+        assert_eq!(config.host, None);
+        assert_eq!(config.port, None);
+    }
+
+    #[cfg(feature = "dotenv")]
+    #[test]
+    fn initialize_base_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join(".env.toml"), "[CONFIG]\ndotenv = true").unwrap();
+        std::fs::write(dir.path().join("config.toml"), "file = true").unwrap();
+
+        let (config, report) = crate::initialize_with_report::<Value>(crate::Args {
+            config_path: Some(std::path::Path::new("config.toml")),
+            base_dir: Some(dir.path()),
+            ..crate::Args::default()
+        })
+        .unwrap();
+        let config = config.unwrap();
+        assert_eq!(config["dotenv"].as_bool(), Some(true));
+        assert_eq!(config["file"].as_bool(), Some(true));
+        assert!(matches!(
+            report.provenance.get("file"),
+            Some(crate::ConfigSource::File(path)) if path == &dir.path().join("config.toml")
+        ));
+
+        // A relative path in the `CONFIG` variable is also resolved against the base directory.
+        std::fs::remove_file(dir.path().join(".env.toml")).unwrap();
+        std::env::set_var("TEST_BASE_DIR_CONFIG", "config.toml");
+        let args = crate::Args {
+            config_variable_name: "TEST_BASE_DIR_CONFIG",
+            base_dir: Some(dir.path()),
+            ..crate::Args::default()
+        };
+        assert!(crate::accessed_paths(&args).contains(&dir.path().join("config.toml")));
+        let (config, report) = crate::initialize_with_report::<Value>(args).unwrap();
+        assert_eq!(config.unwrap()["file"].as_bool(), Some(true));
+        assert!(matches!(
+            report.provenance.get("file"),
+            Some(crate::ConfigSource::File(path)) if path == &dir.path().join("config.toml")
+        ));
+        std::env::remove_var("TEST_BASE_DIR_CONFIG");
+
+        let error = crate::initialize::<Value>(crate::Args {
+            base_dir: Some(std::path::Path::new("relative")),
+            ..crate::Args::default()
+        })
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Args::base_dir \"relative\" must be an absolute path"
+        );
+    }
+
+    #[test]
+    fn initialize_env_only() {
+        std::env::set_var("TEST_ENV_ONLY_PROCESS", "process");
+        let environment: std::collections::HashMap<String, String> = [
+            ("TEST_ENV_ONLY_HOST".to_owned(), "localhost".to_owned()),
+            ("TEST_ENV_ONLY_DEBUG".to_owned(), "true".to_owned()),
+        ]
+        .into_iter()
+        .collect();
+        let config: Value = crate::initialize_env_only(
+            crate::map_env! {
+                "TEST_ENV_ONLY_HOST" => "host",
+                "TEST_ENV_ONLY_DEBUG" => "debug",
+                "TEST_ENV_ONLY_PROCESS" => "process",
+            },
+            &environment,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(config["host"].as_str(), Some("localhost"));
+        assert_eq!(config["debug"].as_bool(), Some(true));
+        assert!(config.get("process").is_none());
+    }
+
+    #[test]
+    fn initialize_unset_sentinel() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = dir.path().join("config.toml");
+        std::fs::write(
+            &config_path,
+            "[proxy]\nurl = \"http://proxy\"\n[server]\nhost = \"a\"",
+        )
+        .unwrap();
+        std::env::set_var("TEST_UNSET_SENTINEL_PROXY", crate::DEFAULT_UNSET_SENTINEL);
+
+        let config: Value = crate::initialize(crate::Args {
+            config_path: Some(&config_path),
+            map_env: crate::map_env! { "TEST_UNSET_SENTINEL_PROXY" => "proxy" },
+            unset_sentinel: Some(crate::DEFAULT_UNSET_SENTINEL),
+            ..crate::Args::default()
+        })
+        .unwrap()
+        .unwrap();
+        assert!(config.get("proxy").is_none());
+        assert_eq!(config["server"]["host"].as_str(), Some("a"));
+    }
+
+    #[test]
+    fn accessed_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        std::env::set_var("TEST_ACCESSED_PATHS_PASSWORD_FILE", "password");
+        let paths = crate::accessed_paths(&crate::Args {
+            config_path: Some(std::path::Path::new("config.toml")),
+            map_env: crate::map_env! { "TEST_ACCESSED_PATHS_PASSWORD" => "password" },
+            file_suffix: Some(crate::DEFAULT_FILE_SUFFIX),
+            base_dir: Some(dir.path()),
+            ..crate::Args::default()
+        });
+        assert!(paths.contains(&dir.path().join("config.toml")));
+        assert!(paths.contains(&dir.path().join("password")));
+
+        let args = crate::Args {
+            sources: vec![
+                Box::new(crate::CommandSource::new("pass").arg("show")),
+                Box::new(crate::KeyPerFileSource::new(dir.path().join("secrets"))),
+                Box::new(crate::SystemdCredentialsSource::new().directory(dir.path())),
+            ],
+            ..crate::Args::default()
+        };
+        let accesses = crate::accesses(&args);
+        assert!(accesses.contains(&crate::Access::Command("pass".into())));
+        assert!(accesses.contains(&crate::Access::Path(dir.path().join("secrets"))));
+        let paths = crate::accessed_paths(&args);
+        assert!(paths.contains(&dir.path().join("secrets")));
+        assert!(paths.contains(&dir.path().to_owned()));
+    }
+
+    #[cfg(feature = "remote")]
+    #[test]
+    fn accesses_remote() {
+        let accesses = crate::accesses(&crate::Args {
+            config_location: Some(crate::ConfigLocation::Url(
+                "https://config.internal/app.toml",
+            )),
+            ..crate::Args::default()
+        });
+        assert!(accesses.contains(&crate::Access::Endpoint(
+            "https://config.internal/app.toml".to_owned()
+        )));
+    }
+
+    #[cfg(feature = "dotenv")]
+    #[test]
+    fn initialize_preloaded_dotenv() {
+        let dir = tempfile::tempdir().unwrap();
+        let preloaded_path = dir.path().join(".env");
+        std::fs::write(
+            &preloaded_path,
+            "# Loaded by dotenvy\nexport TEST_PRELOADED_HOST=localhost\n",
+        )
+        .unwrap();
+        let dotenv_path = dir.path().join(".env.toml");
+        std::fs::write(&dotenv_path, "TEST_PRELOADED_HOST = \"example.com\"").unwrap();
+        std::env::set_var("TEST_PRELOADED_HOST", "localhost");
+        std::env::set_var("TEST_PRELOADED_PORT", "8080");
+        let args = |dotenv_overlap| crate::Args {
+            dotenv_path: &dotenv_path,
+            dotenv_overlap,
+            preloaded_dotenv_path: Some(&preloaded_path),
+            map_env: crate::map_env! {
+                "TEST_PRELOADED_HOST" => "host",
+                "TEST_PRELOADED_PORT" => "port",
+            },
+            ..crate::Args::default()
+        };
+
+        assert!(crate::initialize::<Value>(args(crate::DotEnvOverlap::Error)).is_err());
+
+        let (config, report) =
+            crate::initialize_with_report::<Value>(args(crate::DotEnvOverlap::Keep)).unwrap();
+        assert_eq!(config.unwrap()["host"].as_str(), Some("localhost"));
+        assert!(matches!(
+            report.provenance.get("host"),
+            Some(crate::ConfigSource::PreloadedDotEnv { path, variable_names })
+                if path == &preloaded_path && variable_names == &["TEST_PRELOADED_HOST"]
+        ));
+        assert!(matches!(
+            report.provenance.get("port"),
+            Some(crate::ConfigSource::Environment { .. })
+        ));
+    }
+
+    #[test]
+    fn initialize_large_integers() {
+        std::env::set_var("TEST_LARGE_INTEGERS_ID", "18446744073709551615");
+        let args = |large_integers| crate::Args {
+            map_env: crate::map_env! { "TEST_LARGE_INTEGERS_ID" => "id" },
+            large_integers,
+            ..crate::Args::default()
+        };
+
+        // By default, large integers are parsed as floats with `NumberInference::PreferFloat`, and
+        // kept as strings with `NumberInference::PreferInteger`.
+        let config: Value = crate::initialize(args(crate::LargeIntegers::Infer))
+            .unwrap()
+            .unwrap();
+        assert_eq!(config["id"].as_float(), Some(18446744073709551615.0));
+        let config: Value = crate::initialize(crate::Args {
+            number_inference: crate::NumberInference::PreferInteger,
+            ..args(crate::LargeIntegers::Infer)
+        })
+        .unwrap()
+        .unwrap();
+        assert_eq!(config["id"].as_str(), Some("18446744073709551615"));
+
+        let config: Value = crate::initialize(args(crate::LargeIntegers::String))
+            .unwrap()
+            .unwrap();
+        assert_eq!(config["id"].as_str(), Some("18446744073709551615"));
+
+        let config: Value = crate::initialize(args(crate::LargeIntegers::Float))
+            .unwrap()
+            .unwrap();
+        assert_eq!(config["id"].as_float(), Some(18446744073709551615.0));
+
+        let error = crate::initialize::<Value>(args(crate::LargeIntegers::Error)).unwrap_err();
+        assert!(error.to_string().contains("TEST_LARGE_INTEGERS_ID"));
+    }
+
+    #[cfg(all(feature = "pretty-logging", feature = "preserve-order"))]
+    #[test]
+    fn render_config_preserve_order() {
+        #[derive(serde::Serialize)]
+        struct Server {
+            port: u16,
+            host: String,
+        }
+
+        #[derive(serde::Serialize)]
+        struct Config {
+            server: Server,
+            name: String,
+            debug: bool,
+        }
+
+        let config = Config {
+            server: Server {
+                port: 8080,
+                host: "localhost".to_owned(),
+            },
+            name: "app".to_owned(),
+            debug: false,
+        };
+        assert_eq!(
+            super::render_config_log(&config, &[], crate::ConfigLog::Full).unwrap(),
+            "name = \"app\"\ndebug = false\n\n[server]\nport = 8080\nhost = \"localhost\"\n"
+        );
+    }
+
+    #[cfg(feature = "pretty-logging")]
+    #[test]
+    fn render_config_log() {
+        use crate::ConfigLog;
+
+        let config = toml::toml! {
+            a = 1
+            b = 2
+            [c]
+            d = 3
+            e = 4
+        };
+        let render = |config_log| super::render_config_log(&config, &[], config_log).unwrap();
+        assert_eq!(
+            render(ConfigLog::Full),
+            "a = 1\nb = 2\n\n[c]\nd = 3\ne = 4\n"
+        );
+        assert_eq!(
+            render(ConfigLog::Truncated {
+                max_keys: Some(3),
+                max_bytes: None
+            }),
+            "a = 1\nb = 2\n\n[c]\nd = 3\n... 1 more keys omitted\n"
+        );
+        assert_eq!(
+            render(ConfigLog::Truncated {
+                max_keys: None,
+                max_bytes: Some(12)
+            }),
+            "a = 1\nb = 2\n... 2 more keys omitted\n"
+        );
+        assert_eq!(
+            render(ConfigLog::Truncated {
+                max_keys: Some(10),
+                max_bytes: Some(100)
+            }),
+            render(ConfigLog::Full)
+        );
+        assert_eq!(
+            render(ConfigLog::Summary),
+            "a: 1 key\nb: 1 key\nc: 2 keys\n"
+        );
+    }
+
+    #[test]
+    fn render_annotated() {
+        std::env::set_var("TEST_RENDER_ANNOTATED_PASSWORD", "hunter2");
+        let (_, report) = crate::initialize_with_report::<Value>(crate::Args {
+            defaults: Some("name = \"app\"\n[\"db host\".primary]\nports = [5432]"),
+            map_env: crate::map_env! {
+                "TEST_RENDER_ANNOTATED_PASSWORD" => "db.password",
+            },
+            redact: vec!["db.password".parse().unwrap()],
+            ..crate::Args::default()
+        })
+        .unwrap();
+        assert_eq!(
+            report.render_annotated(),
+            "name = \"app\" # from embedded defaults\n\
+            \n\
+            [db]\n\
+            password = \"***\" # from environment variables TEST_RENDER_ANNOTATED_PASSWORD\n\
+            \n\
+            [\"db host\".primary]\n\
+            ports = [5432] # from embedded defaults\n"
+        );
+    }
+
+    #[test]
+    fn initialize_infer_datetimes() {
+        std::env::set_var("TEST_INFER_DATETIMES_RELEASE", "2024-01-01");
+        std::env::set_var("TEST_INFER_DATETIMES_START", "2024-01-01");
+        let (_, report) = crate::initialize_with_report::<Value>(crate::Args {
+            map_env: crate::map_env! {
+                "TEST_INFER_DATETIMES_RELEASE" => "release",
+                "TEST_INFER_DATETIMES_START" => "start",
+            },
+            value_hints: [("TEST_INFER_DATETIMES_START", crate::ValueHint::Datetime)]
+                .into_iter()
+                .collect(),
+            infer_datetimes: false,
+            ..crate::Args::default()
+        })
+        .unwrap();
+        let config = report.value.unwrap();
+        assert_eq!(config["release"].as_str(), Some("2024-01-01"));
+        assert!(config["start"].as_datetime().is_some());
+    }
+
+    #[cfg(feature = "auto-map-env")]
+    #[test]
+    fn builder() {
+        std::env::set_var("TEST_BUILDER__VALUE", "auto");
+        std::env::set_var("TEST_BUILDER_MAPPED", "mapped");
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = dir.path().join("config.toml");
+        std::fs::write(&config_path, "file = true").unwrap();
+
+        let config: Value = crate::builder()
+            .auto_env("TEST_BUILDER")
+            .file(&config_path)
+            .env("TEST_BUILDER_MAPPED", "mapped".parse().unwrap())
+            .defaults("default = 1")
+            .configure(|args| args.number_inference = crate::NumberInference::PreferInteger)
+            .load()
+            .unwrap()
+            .unwrap();
+        assert_eq!(config["value"].as_str(), Some("auto"));
+        assert_eq!(config["mapped"].as_str(), Some("mapped"));
+        assert_eq!(config["file"].as_bool(), Some(true));
+        assert_eq!(config["default"].as_integer(), Some(1));
+    }
+
+    #[cfg(feature = "dotenv")]
+    #[test]
+    fn initialize_dotenv_variables() {
+        let dir = tempfile::tempdir().unwrap();
+        let dotenv_path = dir.path().join(".env.toml");
+        std::fs::write(
+            &dotenv_path,
+            "TEST_DOTENV_VARIABLES_PORT = 8080\n[CONFIG]\nvalue = 1",
+        )
+        .unwrap();
+
+        let (_, report) = crate::initialize_with_report::<Value>(crate::Args {
+            dotenv_path: &dotenv_path,
+            ..crate::Args::default()
+        })
+        .unwrap();
+        assert_eq!(report.dotenv_variables.len(), 1);
+        assert_eq!(
+            report.dotenv_variables["TEST_DOTENV_VARIABLES_PORT"].as_integer(),
+            Some(8080)
+        );
+    }
+
+    #[test]
+    fn initialize_value() {
+        std::env::set_var("TEST_INITIALIZE_VALUE_RELEASED", "2024-01-01");
+        let value = crate::initialize_value(crate::Args {
+            map_env: crate::map_env! { "TEST_INITIALIZE_VALUE_RELEASED" => "plugin.released" },
+            ..crate::Args::default()
+        })
+        .unwrap()
+        .unwrap();
+        assert!(value["plugin"]["released"].as_datetime().is_some());
+    }
+
+    #[cfg(feature = "dotenv")]
+    #[test]
+    fn initialize_env_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let env_file_path = dir.path().join(".env");
+        let dotenv_path = dir.path().join(crate::DEFAULT_DOTENV_PATH);
+        std::fs::write(
+            &env_file_path,
+            "TEST_ENV_FILE_PORT=8080\nTEST_ENV_FILE_HOST=\"from env file\"\n",
+        )
+        .unwrap();
+        std::fs::write(&dotenv_path, "TEST_ENV_FILE_HOST = \"from dotenv\"").unwrap();
+        let (config, report) = crate::initialize_with_report::<Value>(crate::Args {
+            env_file_path: Some(&env_file_path),
+            dotenv_path: &dotenv_path,
+            dotenv_overlap: crate::DotEnvOverlap::Error,
+            map_env: crate::map_env! {
+                "TEST_ENV_FILE_PORT" => "port",
+                "TEST_ENV_FILE_HOST" => "host",
+            },
+            ..crate::Args::default()
+        })
+        .unwrap();
+        let config = config.unwrap();
+        assert_eq!(config["port"].as_float(), Some(8080.0));
+        assert_eq!(config["host"].as_str(), Some("from dotenv"));
+        assert!(matches!(
+            report.provenance.get("port"),
+            Some(crate::ConfigSource::PreloadedDotEnv { path, .. }) if path == &env_file_path
+        ));
+        assert!(matches!(
+            report.provenance.get("host"),
+            Some(crate::ConfigSource::Environment { .. })
+        ));
+
+        // Variables which are kept from the environment are not reported as coming from the file.
+        std::fs::write(&env_file_path, "TEST_ENV_FILE_KEEP=file\n").unwrap();
+        std::env::set_var("TEST_ENV_FILE_KEEP", "environment");
+        let (config, report) = crate::initialize_with_report::<Value>(crate::Args {
+            env_file_path: Some(&env_file_path),
+            dotenv_overlap: crate::DotEnvOverlap::Keep,
+            map_env: crate::map_env! { "TEST_ENV_FILE_KEEP" => "keep" },
+            ..crate::Args::default()
+        })
+        .unwrap();
+        assert_eq!(config.unwrap()["keep"].as_str(), Some("environment"));
+        assert!(matches!(
+            report.provenance.get("keep"),
+            Some(crate::ConfigSource::Environment { .. })
+        ));
+
+        std::fs::write(&env_file_path, "TEST_ENV_FILE_MULTILINE=\"a\nb\"\n").unwrap();
+        let error = crate::initialize::<Value>(crate::Args {
+            env_file_path: Some(&env_file_path),
+            ..crate::Args::default()
+        })
+        .unwrap_err();
+        assert!(error.to_string().contains("line 1"));
+    }
+
+    #[cfg(feature = "dotenv")]
+    #[test]
+    fn initialize_dotenv_local() {
+        let dir = tempfile::tempdir().unwrap();
+        let dotenv_path = dir.path().join(crate::DEFAULT_DOTENV_PATH);
+        let dotenv_local_path = dir.path().join(crate::DEFAULT_DOTENV_LOCAL_PATH);
+        std::fs::write(
+            &dotenv_path,
+            "TEST_DOTENV_LOCAL_A = \"base\"\nTEST_DOTENV_LOCAL_B = \"base\"\n\
+            [CONFIG]\nname = \"base\"\nport = 1",
+        )
+        .unwrap();
+        std::fs::write(
+            &dotenv_local_path,
+            "TEST_DOTENV_LOCAL_A = \"local\"\n[CONFIG]\nport = 2",
+        )
+        .unwrap();
+        let (config, report) = crate::initialize_with_report::<Value>(crate::Args {
+            dotenv_path: &dotenv_path,
+            dotenv_local_path: Some(&dotenv_local_path),
+            dotenv_overlap: crate::DotEnvOverlap::Error,
+            ..crate::Args::default()
+        })
+        .unwrap();
+        let config = config.unwrap();
+        assert_eq!(config["name"].as_str(), Some("base"));
+        assert_eq!(config["port"].as_integer(), Some(2));
+        assert_eq!(std::env::var("TEST_DOTENV_LOCAL_A").unwrap(), "local");
+        assert_eq!(std::env::var("TEST_DOTENV_LOCAL_B").unwrap(), "base");
+        assert_eq!(
+            report.dotenv_variables["TEST_DOTENV_LOCAL_A"].as_str(),
+            Some("local")
+        );
+        assert!(matches!(
+            report.provenance.get("port"),
+            Some(crate::ConfigSource::DotEnv(path)) if path == &dotenv_local_path
+        ));
+        assert!(matches!(
+            report.provenance.get("name"),
+            Some(crate::ConfigSource::DotEnv(path)) if path == &dotenv_path
+        ));
+    }
+
+    #[test]
+    fn initialize_config_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let base_path = dir.path().join("base.toml");
+        let site_path = dir.path().join("site.toml");
+        let missing_path = dir.path().join("missing.toml");
+        std::fs::write(&base_path, "name = \"base\"\nport = 1\nhost = \"base\"").unwrap();
+        std::fs::write(&site_path, "port = 2\nhost = \"site\"").unwrap();
+        let (config, report) = crate::initialize_with_report::<Value>(crate::Args {
+            config_paths: vec![&base_path, &site_path, &missing_path],
+            config_inline: Some(crate::InlineSource {
+                label: "inline",
+                content: "host = \"inline\"",
+            }),
+            ..crate::Args::default()
+        })
+        .unwrap();
+        let config = config.unwrap();
+        assert_eq!(config["name"].as_str(), Some("base"));
+        assert_eq!(config["port"].as_integer(), Some(2));
+        assert_eq!(config["host"].as_str(), Some("inline"));
+        assert!(matches!(
+            report.provenance.get("port"),
+            Some(crate::ConfigSource::File(path)) if path == &site_path
+        ));
+    }
+
+    #[cfg(feature = "json5")]
+    #[test]
+    fn initialize_json5() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = dir.path().join("config.json5");
+        std::fs::write(
+            &config_path,
+            "{\n  // The server.\n  server: { host: 'localhost', port: 8080, },\n}",
+        )
+        .unwrap();
+        let config: Value = crate::initialize(crate::Args {
+            config_path: Some(&config_path),
+            ..crate::Args::default()
+        })
+        .unwrap()
+        .unwrap();
+        assert_eq!(config["server"]["host"].as_str(), Some("localhost"));
+        assert_eq!(config["server"]["port"].as_integer(), Some(8080));
+    }
+
+    #[cfg(feature = "ini")]
+    #[test]
+    fn initialize_ini() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = dir.path().join("legacy.ini");
+        std::fs::write(
+            &config_path,
+            "name = legacy\n\n[server]\nhost = localhost\nport = 8080\nratio = 0.5\n",
+        )
+        .unwrap();
+        let config: Value = crate::initialize(crate::Args {
+            config_path: Some(&config_path),
+            ..crate::Args::default()
+        })
+        .unwrap()
+        .unwrap();
+        assert_eq!(config["name"].as_str(), Some("legacy"));
+        assert_eq!(config["server"]["host"].as_str(), Some("localhost"));
+        assert_eq!(config["server"]["port"].as_integer(), Some(8080));
+        assert_eq!(config["server"]["ratio"].as_float(), Some(0.5));
+
+        std::fs::write(
+            &config_path,
+            "server = legacy\n\n[server]\nhost = localhost\n",
+        )
+        .unwrap();
+        let error = crate::initialize::<Value>(crate::Args {
+            config_path: Some(&config_path),
+            ..crate::Args::default()
+        })
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "Property \"server\" in INI file {config_path:?} has the same name as a section"
+            )
+        );
+    }
+
+    #[test]
+    fn initialize_config_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = dir.path().join("config.toml");
+        let config_dir = dir.path().join("conf.d");
+        std::fs::create_dir(&config_dir).unwrap();
+        std::fs::write(&config_path, "name = \"main\"\nport = 1\nhost = \"main\"").unwrap();
+        std::fs::write(config_dir.join("10-port.toml"), "port = 10\nhost = \"10\"").unwrap();
+        std::fs::write(config_dir.join("20-host.toml"), "host = \"20\"").unwrap();
+        std::fs::write(config_dir.join("README"), "not toml").unwrap();
+        let config: Value = crate::initialize(crate::Args {
+            config_path: Some(&config_path),
+            config_dir: Some(&config_dir),
+            ..crate::Args::default()
+        })
+        .unwrap()
+        .unwrap();
+        assert_eq!(config["name"].as_str(), Some("main"));
+        assert_eq!(config["port"].as_integer(), Some(10));
+        assert_eq!(config["host"].as_str(), Some("20"));
+
+        let missing_dir = dir.path().join("missing.d");
+        let config: Option<Value> = crate::initialize(crate::Args {
+            config_dir: Some(&missing_dir),
+            ..crate::Args::default()
+        })
+        .unwrap();
+        assert!(config.is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn initialize_command_source() {
+        let source = crate::CommandSource::new("sh")
+            .arg("-c")
+            .arg("printf 'name = \"command\"\\nport = 80\\n'")
+            .precedence(crate::SourcePrecedence::Highest);
+        assert_eq!(
+            crate::Source::description(&source),
+            "command `sh -c printf 'name = \"command\"\\nport = 80\\n'`"
+        );
+        let (config, report) = crate::initialize_with_report::<Value>(crate::Args {
+            config_inline: Some(crate::InlineSource {
+                label: "file",
+                content: "name = \"file\"\nlevel = \"info\"",
+            }),
+            sources: vec![
+                Box::new(source),
+                Box::new(crate::CommandSource::new("true")),
+            ],
+            ..crate::Args::default()
+        })
+        .unwrap();
+        let config = config.unwrap();
+        assert_eq!(config["name"].as_str(), Some("command"));
+        assert_eq!(config["port"].as_integer(), Some(80));
+        assert_eq!(config["level"].as_str(), Some("info"));
+        assert!(report.source.is_some());
+
+        let error = crate::initialize::<Value>(crate::Args {
+            sources: vec![Box::new(crate::CommandSource::new("false"))],
+            ..crate::Args::default()
+        })
+        .unwrap_err();
+        assert!(error.to_string().contains("command `false`"));
+    }
+
+    #[cfg(feature = "aws-secrets-manager")]
+    #[test]
+    fn aws_secrets_manager_source() {
+        let source = crate::AwsSecretsManagerSource::new()
+            .secret("prod/app", "".parse().unwrap())
+            .secret("prod/app/database", "database".parse().unwrap())
+            .region("eu-west-1");
+        assert_eq!(
+            crate::Source::description(&source),
+            "AWS Secrets Manager secrets prod/app, prod/app/database"
+        );
+        assert!(crate::Source::load(&crate::AwsSecretsManagerSource::new())
+            .unwrap()
+            .is_none());
+
+        // Loading from within an asynchronous context (e.g. `#[tokio::main]`) returns the error
+        // from the unreachable endpoint instead of panicking.
+        std::env::set_var("AWS_ACCESS_KEY_ID", "test");
+        std::env::set_var("AWS_SECRET_ACCESS_KEY", "test");
+        std::env::set_var("AWS_ENDPOINT_URL", "http://127.0.0.1:1");
+        std::env::set_var("AWS_EC2_METADATA_DISABLED", "true");
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        assert!(runtime
+            .block_on(async { crate::Source::load(&source) })
+            .is_err());
+
+        let secret = crate::parse_secret("name = \"app\"\nport = 80").unwrap();
+        assert_eq!(secret["port"].as_integer(), Some(80));
+        let secret = crate::parse_secret(r#"{"username": "app", "port": 5432}"#).unwrap();
+        assert_eq!(secret["username"].as_str(), Some("app"));
+        assert_eq!(secret["port"].as_integer(), Some(5432));
+    }
+
+    #[cfg(feature = "vault")]
+    #[test]
+    fn vault_source() {
+        let auth = crate::VaultAuth::AppRole {
+            role_id: "app".to_owned(),
+            secret_id: "secret-id".to_owned(),
+        };
+        assert!(!format!("{auth:?}").contains("secret-id"));
+
+        let source = crate::VaultSource::new(
+            "http://127.0.0.1:1/",
+            crate::VaultAuth::Token("token".to_owned()),
+        )
+        .secret("secret", "app/database", "database".parse().unwrap())
+        .remote(crate::RemoteArgs {
+            timeout: std::time::Duration::from_secs(1),
+            ..crate::RemoteArgs::default()
+        });
+        assert_eq!(
+            crate::Source::description(&source),
+            "Vault secrets secret/app/database from http://127.0.0.1:1/"
+        );
+        let error = crate::initialize::<Value>(crate::Args {
+            sources: vec![Box::new(source)],
+            ..crate::Args::default()
+        })
+        .unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("Vault secrets secret/app/database"));
+
+        let (server, requests) = serve_http(vec![
+            (
+                "200 OK",
+                r#"{"auth": {"client_token": "vault-token"}}"#.to_owned(),
+            ),
+            (
+                "200 OK",
+                r#"{"data": {"data": {"host": "db", "port": 5432}, "metadata": {"version": 1}}}"#
+                    .to_owned(),
+            ),
+            (
+                "200 OK",
+                r#"{"data": {"data": {"name": "app"}, "metadata": {"version": 3}}}"#.to_owned(),
+            ),
+        ]);
+        let source = crate::VaultSource::new(&server, auth)
+            .secret("secret", "app/database", "database".parse().unwrap())
+            .secret("kv", "app", crate::TomlKeyPath::default())
+            .remote(crate::RemoteArgs {
+                headers: vec![("X-Vault-Namespace".to_owned(), "team".to_owned())],
+                ..crate::RemoteArgs::default()
+            });
+        assert!(!format!("{source:?}").contains("team"));
+        let value = crate::Source::load(&source).unwrap().unwrap();
+        assert_eq!(value["database"]["host"].as_str(), Some("db"));
+        assert_eq!(value["database"]["port"].as_integer(), Some(5432));
+        assert_eq!(value["name"].as_str(), Some("app"));
+        assert!(value.get("metadata").is_none());
+
+        let requests = requests.join().unwrap();
+        assert!(requests[0].starts_with("POST /v1/auth/approle/login HTTP/1.1\r\n"));
+        assert!(requests[0].ends_with(r#"{"role_id":"app","secret_id":"secret-id"}"#));
+        assert!(requests[1].starts_with("GET /v1/secret/data/app/database HTTP/1.1\r\n"));
+        assert!(requests[2].starts_with("GET /v1/kv/data/app HTTP/1.1\r\n"));
+        for request in &requests[1..] {
+            assert!(request.contains("X-Vault-Token: vault-token\r\n"));
+            assert!(request.contains("X-Vault-Namespace: team\r\n"));
+        }
+    }
+
+    #[cfg(feature = "kv-tree")]
+    #[test]
+    fn kv_tree_source() {
+        assert_eq!(crate::encode_base64(b""), "");
+        assert_eq!(crate::encode_base64(b"h"), "aA==");
+        assert_eq!(crate::encode_base64(b"hello"), "aGVsbG8=");
+        assert_eq!(crate::encode_base64(b"config/app/"), "Y29uZmlnL2FwcC8=");
+
+        let value = crate::nest_key_values(
+            [
+                ("database/host".to_owned(), "db".to_owned()),
+                ("database/port".to_owned(), "5432".to_owned()),
+                ("/debug/".to_owned(), "true".to_owned()),
+                ("".to_owned(), "ignored".to_owned()),
+            ],
+            "/",
+            true,
+        )
+        .unwrap();
+        assert_eq!(value["database"]["host"].as_str(), Some("db"));
+        assert_eq!(value["database"]["port"].as_integer(), Some(5432));
+        assert_eq!(value["debug"].as_bool(), Some(true));
+
+        let source =
+            crate::KvTreeSource::new(crate::KvBackend::Etcd, "http://127.0.0.1:1", "config/app/");
+        assert_eq!(
+            crate::Source::description(&source),
+            "etcd keys \"config/app/\" from http://127.0.0.1:1"
+        );
+        assert!(crate::Source::load(&source).is_err());
+
+        let (server, requests) = serve_http(vec![
+            (
+                "200 OK",
+                r#"[
+                    {"Key": "config/app/", "Value": null},
+                    {"Key": "config/app/database/host", "Value": "ZGI="},
+                    {"Key": "config/app/database/port", "Value": "NTQzMg=="}
+                ]"#
+                .to_owned(),
+            ),
+            ("404 Not Found", String::new()),
+        ]);
+        let source = crate::KvTreeSource::new(crate::KvBackend::Consul, &server, "config/app/")
+            .infer_types(true);
+        let value = crate::Source::load(&source).unwrap().unwrap();
+        assert_eq!(value["database"]["host"].as_str(), Some("db"));
+        assert_eq!(value["database"]["port"].as_integer(), Some(5432));
+        assert_eq!(value.as_table().unwrap().len(), 1);
+        assert!(crate::Source::load(&source).unwrap().is_none());
+        let requests = requests.join().unwrap();
+        assert!(requests[0].starts_with("GET /v1/kv/config/app/?recurse=true HTTP/1.1\r\n"));
+
+        // The prefix is a directory, so sibling trees which start with the same characters are
+        // ignored, and it is percent-encoded in the URL.
+        let (server, requests) = serve_http(vec![(
+            "200 OK",
+            r#"[
+                {"Key": "config/my app/port", "Value": "MDEyMw=="},
+                {"Key": "config/my app-staging/password", "Value": "ZGI="}
+            ]"#
+            .to_owned(),
+        )]);
+        let source = crate::KvTreeSource::new(crate::KvBackend::Consul, &server, "config/my app");
+        let value = crate::Source::load(&source).unwrap().unwrap();
+        assert_eq!(value["port"].as_str(), Some("0123"));
+        assert_eq!(value.as_table().unwrap().len(), 1);
+        let requests = requests.join().unwrap();
+        assert!(requests[0].starts_with("GET /v1/kv/config/my%20app/?recurse=true HTTP/1.1\r\n"));
+
+        let (server, requests) = serve_http(vec![
+            (
+                "200 OK",
+                r#"{"header": {}, "kvs": [
+                    {"key": "Y29uZmlnL2FwcC9uYW1l", "value": "YXBw"},
+                    {"key": "Y29uZmlnL2FwcC9lbXB0eQ=="}
+                ], "count": "2"}"#
+                    .to_owned(),
+            ),
+            ("200 OK", r#"{"header": {}}"#.to_owned()),
+        ]);
+        let source = crate::KvTreeSource::new(crate::KvBackend::Etcd, &server, "config/app/");
+        let value = crate::Source::load(&source).unwrap().unwrap();
+        assert_eq!(value["name"].as_str(), Some("app"));
+        assert_eq!(value["empty"].as_str(), Some(""));
+        assert!(crate::Source::load(&source).unwrap().is_none());
+        let requests = requests.join().unwrap();
+        assert!(requests[0].starts_with("POST /v3/kv/range HTTP/1.1\r\n"));
+        assert!(
+            requests[0].ends_with(r#"{"key":"Y29uZmlnL2FwcC8=","range_end":"Y29uZmlnL2FwcDA="}"#)
+        );
+    }
+
+    #[test]
+    fn initialize_systemd_credentials() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("database__password"), "123456\n").unwrap();
+        std::fs::write(dir.path().join("database__port"), "5432").unwrap();
+        std::fs::write(dir.path().join("token"), "0123").unwrap();
+
+        let source = crate::SystemdCredentialsSource::new()
+            .directory(dir.path())
+            .credential("token", "api.token".parse().unwrap())
+            .credential("missing", "missing".parse().unwrap());
+        let config = crate::Source::load(&source).unwrap().unwrap();
+        assert_eq!(config["api"]["token"].as_str(), Some("0123"));
+        assert!(config.get("database").is_none());
+        assert!(config.get("missing").is_none());
+
+        let (config, report) = crate::initialize_with_report::<Value>(crate::Args {
+            config_inline: Some(crate::InlineSource {
+                label: "file",
+                content: "[database]\nhost = \"db\"\npassword = \"file\"",
+            }),
+            sources: vec![Box::new(source.auto_map("__"))],
+            ..crate::Args::default()
+        })
+        .unwrap();
+        let config = config.unwrap();
+        assert_eq!(config["database"]["host"].as_str(), Some("db"));
+        assert_eq!(config["database"]["password"].as_str(), Some("123456"));
+        assert_eq!(config["database"]["port"].as_str(), Some("5432"));
+        assert_eq!(config["api"]["token"].as_str(), Some("0123"));
+        assert!(config.get("token").is_none());
+        assert!(report.source.is_some());
+
+        // Numeric secrets can be deserialized into strings.
+        #[derive(serde::Deserialize)]
+        struct Database {
+            password: String,
+        }
+        let database: Database = config["database"].clone().try_into().unwrap();
+        assert_eq!(database.password, "123456");
+
+        // Not running under systemd.
+        std::env::remove_var(crate::CREDENTIALS_DIRECTORY_VARIABLE);
+        let source = crate::SystemdCredentialsSource::new().auto_map("__");
+        assert!(crate::Source::load(&source).unwrap().is_none());
+    }
+
+    #[test]
+    fn initialize_key_per_file() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("database__host"), "db\n").unwrap();
+        std::fs::write(dir.path().join("database__port"), "5432").unwrap();
+        std::fs::write(dir.path().join("database__password"), "123456").unwrap();
+        std::fs::write(dir.path().join("debug"), "true").unwrap();
+        std::fs::create_dir(dir.path().join("..data")).unwrap();
+        std::fs::write(dir.path().join("..timestamp"), "ignored").unwrap();
+
+        let config = crate::Source::load(&crate::KeyPerFileSource::new(dir.path()))
+            .unwrap()
+            .unwrap();
+        assert_eq!(config["database__host"].as_str(), Some("db"));
+        assert_eq!(config["database__password"].as_str(), Some("123456"));
+        assert_eq!(config["debug"].as_str(), Some("true"));
+        assert_eq!(config.as_table().unwrap().len(), 4);
+
+        let config = crate::initialize::<Value>(crate::Args {
+            config_inline: Some(crate::InlineSource {
+                label: "file",
+                content: "[database]\nhost = \"localhost\"\nuser = \"app\"",
+            }),
+            sources: vec![
+                Box::new(
+                    crate::KeyPerFileSource::new(dir.path())
+                        .divider("__")
+                        .infer_types(true),
+                ),
+                Box::new(crate::KeyPerFileSource::new(dir.path().join("missing"))),
+            ],
+            ..crate::Args::default()
+        })
+        .unwrap()
+        .unwrap();
+        assert_eq!(config["database"]["host"].as_str(), Some("db"));
+        assert_eq!(config["database"]["port"].as_integer(), Some(5432));
+        assert_eq!(config["database"]["user"].as_str(), Some("app"));
+        assert_eq!(config["debug"].as_bool(), Some(true));
+
+        // Numeric secrets can be deserialized into strings by default.
+        #[derive(serde::Deserialize)]
+        struct Database {
+            password: String,
+        }
+        let config = crate::Source::load(&crate::KeyPerFileSource::new(dir.path()).divider("__"))
+            .unwrap()
+            .unwrap();
+        let database: Database = config["database"].clone().try_into().unwrap();
+        assert_eq!(database.password, "123456");
+    }
+
+    #[cfg(feature = "keyring")]
+    #[test]
+    fn keyring_source() {
+        let source = crate::KeyringSource::new().secret("app", "api", "api.token".parse().unwrap());
+        assert_eq!(
+            crate::Source::description(&source),
+            "keyring credentials app/api"
+        );
+        let source = crate::KeyringSource::new();
+        assert!(crate::Source::load(&source).unwrap().is_none());
+
+        /// A read-only credential with the password `{service}/{user}`, which is missing for the
+        /// `missing` user and inaccessible for the `locked` user.
+        #[derive(Debug)]
+        struct MockCredential {
+            service: String,
+            user: String,
+        }
+
+        impl keyring::credential::CredentialApi for MockCredential {
+            fn set_password(&self, _password: &str) -> keyring::Result<()> {
+                Err(keyring::Error::PlatformFailure("read-only".into()))
+            }
+
+            fn get_password(&self) -> keyring::Result<String> {
+                match self.user.as_str() {
+                    "missing" => Err(keyring::Error::NoEntry),
+                    "locked" => Err(keyring::Error::NoStorageAccess("locked".into())),
+                    user => Ok(format!("{}/{user}", self.service)),
+                }
+            }
+
+            fn delete_password(&self) -> keyring::Result<()> {
+                Err(keyring::Error::PlatformFailure("read-only".into()))
+            }
+
+            fn as_any(&self) -> &dyn std::any::Any {
+                self
+            }
+        }
+
+        #[derive(Debug)]
+        struct MockCredentialBuilder;
+
+        impl keyring::credential::CredentialBuilderApi for MockCredentialBuilder {
+            fn build(
+                &self,
+                _target: Option<&str>,
+                service: &str,
+                user: &str,
+            ) -> keyring::Result<Box<keyring::credential::Credential>> {
+                Ok(Box::new(MockCredential {
+                    service: service.to_owned(),
+                    user: user.to_owned(),
+                }))
+            }
+
+            fn as_any(&self) -> &dyn std::any::Any {
+                self
+            }
+        }
+
+        keyring::set_default_credential_builder(Box::new(MockCredentialBuilder));
+        let source = crate::KeyringSource::new()
+            .secret("app", "api", "api.token".parse().unwrap())
+            .secret("app", "db", "database.password".parse().unwrap())
+            .secret("app", "missing", "missing".parse().unwrap());
+        let value = crate::Source::load(&source).unwrap().unwrap();
+        assert_eq!(value["api"]["token"].as_str(), Some("app/api"));
+        assert_eq!(value["database"]["password"].as_str(), Some("app/db"));
+        assert!(value.get("missing").is_none());
+
+        let source =
+            crate::KeyringSource::new().secret("app", "missing", "missing".parse().unwrap());
+        assert!(crate::Source::load(&source).unwrap().is_none());
+        let source = crate::KeyringSource::new().secret("app", "locked", "locked".parse().unwrap());
+        assert!(crate::Source::load(&source).is_err());
+    }
+
+    #[cfg(feature = "aws-ssm")]
+    #[test]
+    fn aws_ssm_source() {
+        let source = crate::AwsSsmSource::new("/app/prod")
+            .region("eu-west-1")
+            .precedence(crate::SourcePrecedence::Highest);
+        assert_eq!(
+            crate::Source::description(&source),
+            "AWS SSM parameters \"/app/prod\""
+        );
+        assert_eq!(
+            crate::Source::precedence(&source),
+            crate::SourcePrecedence::Highest
+        );
+
+        let parameters = || {
+            vec![
+                ("/app/prod/db/port".to_owned(), "5432".to_owned()),
+                ("/app/prod/db/host".to_owned(), "localhost".to_owned()),
+                ("/app/prod/db/pin".to_owned(), "0123".to_owned()),
+                ("/app/prod/name".to_owned(), "app".to_owned()),
+            ]
+        };
+        let value = crate::ssm_parameters_value("/app/prod", parameters(), false)
+            .unwrap()
+            .unwrap();
+        assert_eq!(value["db"]["port"].as_str(), Some("5432"));
+        assert_eq!(value["db"]["host"].as_str(), Some("localhost"));
+        assert_eq!(value["db"]["pin"].as_str(), Some("0123"));
+        assert_eq!(value["name"].as_str(), Some("app"));
+        assert!(value.get("app").is_none());
+        let value = crate::ssm_parameters_value("/app/prod", parameters(), true)
+            .unwrap()
+            .unwrap();
+        assert_eq!(value["db"]["port"].as_integer(), Some(5432));
+        assert_eq!(value["db"]["host"].as_str(), Some("localhost"));
+        assert!(crate::ssm_parameters_value("/app/prod", Vec::new(), false)
+            .unwrap()
+            .is_none());
+
+        // Loading from within an asynchronous context (e.g. `#[tokio::main]`) returns the error
+        // from the unreachable endpoint instead of panicking.
+        std::env::set_var("AWS_ACCESS_KEY_ID", "test");
+        std::env::set_var("AWS_SECRET_ACCESS_KEY", "test");
+        std::env::set_var("AWS_ENDPOINT_URL", "http://127.0.0.1:1");
+        std::env::set_var("AWS_EC2_METADATA_DISABLED", "true");
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        assert!(runtime
+            .block_on(async { crate::Source::load(&source) })
+            .is_err());
+    }
+
+    #[test]
+    fn initialize_custom_sources() {
+        struct Store {
+            content: &'static str,
+            precedence: crate::SourcePrecedence,
+        }
+
+        impl crate::Source for Store {
+            fn load(
+                &self,
+            ) -> std::result::Result<Option<Value>, Box<dyn std::error::Error + Send + Sync>>
+            {
+                Ok(Some(toml::from_str(self.content)?))
+            }
+
+            fn description(&self) -> String {
+                format!("store {:?}", self.precedence)
+            }
+
+            fn precedence(&self) -> crate::SourcePrecedence {
+                self.precedence
+            }
+        }
+
+        std::env::set_var("TEST_CUSTOM_SOURCES_ENV", "env");
+        let (config, report) = crate::initialize_with_report::<Value>(crate::Args {
+            config_inline: Some(crate::InlineSource {
+                label: "file",
+                content: "file = \"file\"\nafter_defaults = \"file\"",
+            }),
+            map_env: crate::map_env! { "TEST_CUSTOM_SOURCES_ENV" => "after_file" },
+            sources: vec![
+                Box::new(Store {
+                    content: "after_defaults = \"store\"\nafter_file = \"store\"",
+                    precedence: crate::SourcePrecedence::AfterDefaults,
+                }),
+                Box::new(Store {
+                    content: "file = \"store\"\nafter_file = \"store\"",
+                    precedence: crate::SourcePrecedence::AfterFile,
+                }),
+                Box::new(Store {
+                    content: "highest = \"store\"",
+                    precedence: crate::SourcePrecedence::Highest,
+                }),
+            ],
+            ..crate::Args::default()
+        })
+        .unwrap();
+        let config = config.unwrap();
+        assert_eq!(config["after_defaults"].as_str(), Some("file"));
+        assert_eq!(config["file"].as_str(), Some("store"));
+        assert_eq!(config["after_file"].as_str(), Some("env"));
+        assert_eq!(config["highest"].as_str(), Some("store"));
+        assert!(matches!(
+            report.provenance.get("highest"),
+            Some(crate::ConfigSource::Custom { description, .. }) if description == "store Highest"
+        ));
+        assert_eq!(
+            report.provenance["highest"].kind(),
+            Some(crate::SourceKind::Custom)
+        );
+
+        let error = crate::initialize::<Value>(crate::Args {
+            sources: vec![Box::new(Store {
+                content: "not toml",
+                precedence: crate::SourcePrecedence::AfterFile,
+            })],
+            ..crate::Args::default()
+        })
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Error loading config from custom source store AfterFile"
+        );
+    }
+
+    #[test]
+    fn initialize_inline() {
+        let (config, report) = crate::initialize_with_report::<Value>(crate::Args {
+            config_inline: Some(crate::InlineSource {
+                label: "socket",
+                content: "[server]\nport = 8080",
+            }),
+            ..crate::Args::default()
+        })
+        .unwrap();
+        assert_eq!(config.unwrap()["server"]["port"].as_integer(), Some(8080));
+        assert!(matches!(
+            report.provenance.get("server.port"),
+            Some(crate::ConfigSource::Inline { label }) if label == "socket"
+        ));
+    }
 
-    let config = match (config, env_config) {
-        (None, None) => None,
-        (None, Some(config)) => Some(config),
-        (Some(config), None) => Some(config),
-        (Some(from), Some(into)) => {
-            let config = serde_toml_merge::merge(into.0, from.0).map_err(|error| {
-                InnerError::ErrorMerging {
-                    from: from.1.clone(),
-                    into: into.1.clone(),
-                    error,
-                }
-            })?;
+    #[test]
+    fn initialize_args_owned() {
+        std::env::set_var("TEST_ARGS_OWNED_NAME", "owned");
+        let owned = crate::ArgsOwned {
+            map_env: [(
+                format!("TEST_ARGS_OWNED_{}", "NAME"),
+                "server.name".parse().unwrap(),
+            )]
+            .into(),
+            ..crate::ArgsOwned::default()
+        };
+        let config: Value = crate::initialize((&owned).into()).unwrap().unwrap();
+        assert_eq!(config["server"]["name"].as_str(), Some("owned"));
 
-            let source = ConfigSource::Merged {
-                from: from.1.into(),
-                into: into.1.into(),
-            };
-            Some((config, source))
-        }
-    };
+        let owned = crate::ArgsOwned {
+            config_inline: Some(crate::InlineSourceOwned {
+                label: "owned".to_owned(),
+                content: "[server]\nport = 8080".to_owned(),
+            }),
+            ..crate::ArgsOwned::default()
+        };
+        let config: Value = crate::initialize(owned.args()).unwrap().unwrap();
+        assert_eq!(config["server"]["port"].as_integer(), Some(8080));
 
-    let file_config: Option<(Value, ConfigSource)> =
-        Option::transpose(args.config_path.map(|path| {
-            if path.is_file() {
-                let file_string = std::fs::read_to_string(path).map_err(|error| {
-                    InnerError::ErrorReadingFile {
-                        path: path.to_owned(),
-                        error,
-                    }
-                })?;
-                return Result::Ok(Some((
-                    toml::from_str(&file_string).map_err(|error| {
-                        InnerError::ErrorParsingTomlFile {
-                            path: path.to_owned(),
-                            error: error.into(),
-                        }
-                    })?,
-                    ConfigSource::File(path.to_owned()),
-                )));
-            }
-            Ok(None)
-        }))?
-        .flatten();
-
-    let config = match (config, file_config) {
-        (None, None) => None,
-        (None, Some(config)) => Some(config),
-        (Some(config), None) => Some(config),
-        (Some(from), Some(into)) => {
-            let config = serde_toml_merge::merge(into.0, from.0).map_err(|error| {
-                InnerError::ErrorMerging {
-                    from: from.1.clone(),
-                    into: into.1.clone(),
-                    error,
-                }
-            })?;
+        std::env::set_var("TEST_ARGS_OWNED_PORT", "80");
+        let owned = crate::ArgsOwned {
+            map_env: [(
+                format!("TEST_ARGS_OWNED_{}", "PORT"),
+                "server.port".parse().unwrap(),
+            )]
+            .into(),
+            number_inference: crate::NumberInference::PreferInteger,
+            redact: vec!["server.port".parse().unwrap()],
+            ..crate::ArgsOwned::default()
+        };
+        let args = owned.args();
+        assert_eq!(args.redact, owned.redact);
+        let config: Value = crate::initialize(args).unwrap().unwrap();
+        assert_eq!(config["server"]["port"].as_integer(), Some(80));
+    }
 
-            let source = ConfigSource::Merged {
-                from: from.1.into(),
-                into: into.1.into(),
-            };
-            Some((config, source))
-        }
-    };
+    #[cfg(feature = "auto-map-env")]
+    #[test]
+    fn initialize_args_owned_auto_map_env() {
+        std::env::set_var("TEST_ARGS_OWNED_AUTO_SERVER_NAME", "auto");
+        let owned = crate::ArgsOwned {
+            auto_map_env: Some(crate::AutoMapEnvArgsOwned {
+                prefix: Some(format!("TEST_ARGS_OWNED_{}", "AUTO")),
+                divider: "_".to_owned(),
+                ..crate::AutoMapEnvArgsOwned::default()
+            }),
+            ..crate::ArgsOwned::default()
+        };
+        let config: Value = crate::initialize(owned.args()).unwrap().unwrap();
+        assert_eq!(config["server"]["name"].as_str(), Some("auto"));
+    }
 
-    let config = Option::transpose(config.map(|(config, source)| {
-        C::deserialize(config).map_err(|error| InnerError::ErrorParsingMergedToml {
-            source,
-            error: error.into(),
+    #[test]
+    fn initialize_multi() {
+        let config = crate::initialize_multi(crate::Args {
+            config_inline: Some(crate::InlineSource {
+                label: "multi",
+                content: "[app]\nname = \"app\"\n[db]\nport = 5432",
+            }),
+            ..crate::Args::default()
         })
-    }))?;
+        .unwrap();
+        assert_eq!(
+            config.get::<String>("app.name").unwrap().as_deref(),
+            Some("app")
+        );
+        assert_eq!(config.get::<u16>("db.port").unwrap(), Some(5432));
+        assert_eq!(config.get::<u16>("telemetry").unwrap(), None);
+        assert!(config.get::<Value>("").unwrap().is_some());
+        let error = config.get::<u16>("app.name").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Error deserializing the value at `app.name`"
+        );
+    }
 
-    match (logging, config.as_ref()) {
-        (_, Some(config)) => {
-            let config_string = toml::to_string_pretty(&config)
-                .expect("Expected to be able to re-serialize config toml");
-            log_info(
-                logging,
-                format_args!("Parsed configuration:\n\x1b[34m{config_string}\x1b[0m"),
-            );
+    #[test]
+    fn initialize_reader() {
+        struct FailingReader;
+
+        impl std::io::Read for FailingReader {
+            fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+                Err(std::io::Error::other("connection reset"))
+            }
         }
-        (Logging::None, _) | (_, None) => {}
-    }
 
-    Ok(config)
-}
+        let config: Value = crate::initialize_reader(
+            "[server]\nport = 8080".as_bytes(),
+            "socket",
+            crate::Args::default(),
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(config["server"]["port"].as_integer(), Some(8080));
 
-#[cfg(test)]
-mod test {
-    use crate::InsertTomlValueError;
+        let error =
+            crate::initialize_reader::<Value>(FailingReader, "socket", crate::Args::default())
+                .unwrap_err();
+        assert_eq!(error.to_string(), "Error reading config from \"socket\"");
+    }
 
-    use super::insert_toml_value;
+    #[cfg(feature = "dotenv")]
     #[test]
-    fn insert_toml_value_empty_path() {
-        let mut value = toml::Value::String("Hello".to_owned());
-        insert_toml_value(
-            &mut value,
-            "".parse().unwrap(),
-            toml::Value::String("World".to_owned()),
+    fn initialize_str() {
+        let config: Value = crate::initialize_str(
+            Some("TEST_INITIALIZE_STR_TOKEN = \"secret\"\n[CONFIG]\nname = \"dotenv\""),
+            Some("[server]\nport = 8080"),
+            crate::Args::default(),
         )
+        .unwrap()
         .unwrap();
-        assert_eq!(value.as_str().unwrap(), "World");
+        assert_eq!(config["name"].as_str(), Some("dotenv"));
+        assert_eq!(config["server"]["port"].as_integer(), Some(8080));
+        assert_eq!(
+            std::env::var("TEST_INITIALIZE_STR_TOKEN").unwrap(),
+            "secret"
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let dotenv_path = dir.path().join(".env.toml");
+        std::fs::write(&dotenv_path, "not toml").unwrap();
+        let config: Option<Value> = crate::initialize_str(
+            None,
+            None,
+            crate::Args {
+                dotenv_path: &dotenv_path,
+                ..crate::Args::default()
+            },
+        )
+        .unwrap();
+        assert!(config.is_none());
     }
 
     #[test]
-    fn insert_toml_value_table_property() {
-        let mut value = toml::Value::Table(toml::Table::new());
-        insert_toml_value(
-            &mut value,
-            "child".parse().unwrap(),
-            toml::Value::String("Hello Child".to_owned()),
-        )
+    fn initialize_with_report_variable_names() {
+        std::env::set_var("TEST_REPORT_VARIABLE_NAMES_B", "b");
+        std::env::set_var("TEST_REPORT_VARIABLE_NAMES_A", "a");
+        let (_, report) = crate::initialize_with_report::<Value>(crate::Args {
+            map_env: crate::map_env! {
+                "TEST_REPORT_VARIABLE_NAMES_A" => "a",
+                "TEST_REPORT_VARIABLE_NAMES_B" => "b",
+                "TEST_REPORT_VARIABLE_NAMES_UNSET" => "unset",
+            },
+            ..crate::Args::default()
+        })
         .unwrap();
-        assert_eq!(value.get("child").unwrap().as_str().unwrap(), "Hello Child");
+        assert_eq!(
+            report.variable_names,
+            [
+                "TEST_REPORT_VARIABLE_NAMES_A",
+                "TEST_REPORT_VARIABLE_NAMES_B"
+            ]
+        );
     }
 
     #[test]
-    fn insert_toml_value_table_property_property() {
-        let mut value = toml::Value::Table(toml::Table::new());
-        insert_toml_value(
-            &mut value,
-            "child.value".parse().unwrap(),
-            toml::Value::String("Hello Child Value".to_owned()),
-        )
+    fn initialize_with_report_sources() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = dir.path().join("config.toml");
+        std::fs::write(&config_path, "name = \"file\"").unwrap();
+        std::env::set_var("TEST_REPORT_SOURCES_PORT", "8080");
+        let (_, report) = crate::initialize_with_report::<Value>(crate::Args {
+            config_path: Some(&config_path),
+            map_env: crate::map_env! { "TEST_REPORT_SOURCES_PORT" => "port" },
+            ..crate::Args::default()
+        })
         .unwrap();
+
+        let (file, env) = match report.sources.as_slice() {
+            [file, env] => (file, env),
+            sources => panic!("Expected two sources, found {sources:?}"),
+        };
+        assert!(matches!(&file.source, crate::ConfigSource::File(path) if path == &config_path));
         assert_eq!(
-            value
-                .get("child")
-                .unwrap()
-                .get("value")
-                .unwrap()
-                .as_str()
-                .unwrap(),
-            "Hello Child Value"
+            file.modified,
+            Some(std::fs::metadata(&config_path).unwrap().modified().unwrap())
         );
+        assert!(matches!(
+            env.source,
+            crate::ConfigSource::Environment { .. }
+        ));
+        assert_eq!(env.modified, None);
+        assert!(file.read_at >= report.loaded_at && env.read_at >= report.loaded_at);
     }
 
     #[test]
-    fn insert_toml_value_create_array() {
-        let mut value = toml::Value::Array(Vec::new());
-        insert_toml_value(
-            &mut value,
-            "0".parse().unwrap(),
-            toml::Value::String("Hello Element".to_owned()),
-        )
+    fn mapping_contract() {
+        #[derive(Default, serde::Serialize)]
+        struct Config {
+            port: u16,
+            ratio: f64,
+            name: String,
+            tags: Vec<String>,
+        }
+
+        let args = crate::Args {
+            map_env: crate::map_env! {
+                "TEST_CONTRACT_PORT" => "port",
+                "TEST_CONTRACT_RATIO" => "ratio",
+                "TEST_CONTRACT_NAME" => "name",
+                "TEST_CONTRACT_TAGS" => "tags",
+                "TEST_CONTRACT_EXTRA" => "extra",
+            },
+            ..crate::Args::default()
+        };
+        let contract: Vec<String> = crate::mapping_contract::<Config>(&args)
+            .unwrap()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        // Without a schema, integers are parsed as floats.
+        assert_eq!(
+            contract,
+            [
+                "TEST_CONTRACT_EXTRA=\"sample\" -> extra (string)",
+                "TEST_CONTRACT_NAME=\"sample\" -> name (string)",
+                "TEST_CONTRACT_PORT=\"42\" -> port (float)",
+                "TEST_CONTRACT_RATIO=\"1.5\" -> ratio (float)",
+                "TEST_CONTRACT_TAGS=\"[]\" -> tags (string)",
+            ]
+        );
+    }
+
+    #[test]
+    fn toml_key_path_quoted() {
+        let path: crate::TomlKeyPath = r#"servers."my.host.com".port"#.parse().unwrap();
+        assert_eq!(path.to_string(), r#"servers."my.host.com".port"#);
+        let value: Value = toml::from_str("[servers.\"my.host.com\"]\nport = 8080").unwrap();
+        assert_eq!(path.resolve(&value).unwrap().as_integer(), Some(8080));
+
+        let mut value = Value::Table(toml::Table::new());
+        crate::insert_toml_value(&mut value, path, Value::Integer(80)).unwrap();
+        assert_eq!(
+            value["servers"]["my.host.com"]["port"].as_integer(),
+            Some(80)
+        );
+
+        // Quoted numbers are table properties rather than array indices.
+        let path: crate::TomlKeyPath = "key.'0'".parse().unwrap();
+        assert_eq!(path.to_string(), r#"key."0""#);
+        assert!(matches!(
+            path.iter().last(),
+            Some(crate::PathElement::TableProperty(property)) if &**property == "0"
+        ));
+
+        let path: crate::TomlKeyPath = r#""a \"b\" \\ c""#.parse().unwrap();
+        assert_eq!(path.to_string(), r#""a \"b\" \\ c""#);
+
+        assert!(r#"key."unterminated"#.parse::<crate::TomlKeyPath>().is_err());
+        assert!(r#""key"suffix"#.parse::<crate::TomlKeyPath>().is_err());
+        assert!(crate::__is_valid_key_path(r#"servers."my.host.com".port"#));
+        assert!(crate::__is_valid_key_path(r#"a."\"".'b'"#));
+        assert!(!crate::__is_valid_key_path(r#"key."unterminated"#));
+        assert!(!crate::__is_valid_key_path(r#""key"suffix"#));
+    }
+
+    #[test]
+    fn initialize_numeric_table_keys() {
+        std::env::set_var("TEST_NUMERIC_TABLE_KEYS_NAME", "http");
+        let config: Value = crate::initialize(crate::Args {
+            defaults: Some(
+                "[ports.\"8080\"]\nname = \"default\"\n[ports.\"443\"]\nname = \"https\"",
+            ),
+            map_env: crate::map_env! {
+                "TEST_NUMERIC_TABLE_KEYS_NAME" => r#"ports."8080".name"#,
+            },
+            ..crate::Args::default()
+        })
+        .unwrap()
         .unwrap();
-        assert_eq!(value.get(0).unwrap().as_str().unwrap(), "Hello Element");
+        assert_eq!(config["ports"]["8080"]["name"].as_str(), Some("http"));
+        assert_eq!(config["ports"]["443"]["name"].as_str(), Some("https"));
     }
 
     #[test]
-    fn insert_toml_value_array_out_of_bounds_error() {
-        let mut value = toml::Value::Array(Vec::new());
-        let error = insert_toml_value(
-            &mut value,
-            "1".parse().unwrap(),
-            toml::Value::String("Hello Element".to_owned()),
+    fn toml_key_path_resolve_all() {
+        let value: Value = toml::from_str(
+            "[[servers]]\nport = 1\n[[servers]]\nport = 2\n[[servers]]\nhost = \"c\"",
         )
-        .unwrap_err();
+        .unwrap();
+        let path: crate::TomlKeyPath = "servers.*.port".parse().unwrap();
+        let resolved: Vec<String> = path
+            .resolve_all(&value)
+            .map(|(path, value)| format!("{path}={value}"))
+            .collect();
+        assert_eq!(resolved, ["servers.0.port=1", "servers.1.port=2"]);
+
+        let path: crate::TomlKeyPath = "servers.1".parse().unwrap();
+        assert_eq!(path.resolve_all(&value).count(), 1);
+        let path: crate::TomlKeyPath = "missing.*".parse().unwrap();
+        assert_eq!(path.resolve_all(&value).count(), 0);
+
+        // A quoted `*` is a literal key rather than a wildcard.
+        let value: Value = toml::from_str("[keys]\n\"*\" = 1\nother = 2").unwrap();
+        let path: crate::TomlKeyPath = "keys.\"*\"".parse().unwrap();
+        assert_eq!(path.iter().last(), Some(&crate::PathElement::from("*")));
+        assert_eq!(path.to_string(), "keys.\"*\"");
+        let resolved: Vec<String> = path
+            .resolve_all(&value)
+            .map(|(path, value)| format!("{path}={value}"))
+            .collect();
+        assert_eq!(resolved, ["keys.\"*\"=1"]);
+        let path: crate::TomlKeyPath = "keys.*".parse().unwrap();
+        assert_eq!(path.iter().last(), Some(&crate::PathElement::Wildcard));
+        assert_eq!(path.to_string(), "keys.*");
+        assert_eq!(path.resolve_all(&value).count(), 2);
 
+        let mut value = value;
+        let error = crate::insert_toml_value(&mut value, path, 3.into()).unwrap_err();
         assert!(matches!(
             error,
-            InsertTomlValueError::ArrayOutOfBounds { .. }
-        ))
+            crate::InsertTomlValueError::WildcardCannotInsert { .. }
+        ));
     }
 
     #[test]
-    fn insert_toml_value_table_property_index_error() {
-        let mut value = toml::Value::Array(Vec::new());
-        let error = insert_toml_value(
-            &mut value,
-            "key".parse().unwrap(),
-            toml::Value::String("Hello Element".to_owned()),
-        )
-        .unwrap_err();
+    fn initialize_config_location() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = dir.path().join("config.toml");
+        std::fs::write(&config_path, "source = \"file\"").unwrap();
+        std::env::set_var("TEST_CONFIG_LOCATION", "source = \"env\"");
 
+        let location = format!("file://{}", config_path.display());
+        let (config, report) = crate::builder()
+            .location(crate::ConfigLocation::parse(&location).unwrap())
+            .load_with_report::<Value>()
+            .unwrap();
+        assert_eq!(config.unwrap()["source"].as_str(), Some("file"));
+        assert!(matches!(report.source, Some(crate::ConfigSource::File(_))));
+
+        // The location replaces the config path and inline config.
+        let (config, report) = crate::initialize_with_report::<Value>(crate::Args {
+            config_path: Some(&config_path),
+            config_inline: Some(crate::InlineSource {
+                label: "inline",
+                content: "source = \"inline\"",
+            }),
+            config_location: Some(
+                crate::ConfigLocation::parse("env://TEST_CONFIG_LOCATION").unwrap(),
+            ),
+            ..crate::Args::default()
+        })
+        .unwrap();
+        assert_eq!(config.unwrap()["source"].as_str(), Some("env"));
         assert!(matches!(
-            error,
-            InsertTomlValueError::TablePropertyCannotIndex { .. }
-        ))
+            report.source,
+            Some(crate::ConfigSource::Environment { variable_names })
+                if variable_names == ["TEST_CONFIG_LOCATION"]
+        ));
+
+        let error = crate::ConfigLocation::parse("s3://bucket/config.toml").unwrap_err();
+        assert!(error.to_string().contains("s3://bucket/config.toml"));
     }
 
+    #[cfg(not(feature = "remote"))]
     #[test]
-    fn insert_toml_value_array_index_cannot_index_error() {
-        let mut value = toml::Value::Table(toml::Table::new());
-        let error = insert_toml_value(
-            &mut value,
-            "0".parse().unwrap(),
-            toml::Value::String("Hello Element".to_owned()),
-        )
+    fn initialize_config_location_url_without_remote() {
+        let error = crate::initialize::<Value>(crate::Args {
+            config_location: Some(crate::ConfigLocation::Url(
+                "https://config.internal/app.toml",
+            )),
+            ..crate::Args::default()
+        })
         .unwrap_err();
+        assert!(error.to_string().contains("`remote` feature"));
+    }
+
+    /// Serve each of the HTTP `responses` (with a status such as `200 OK` and a body) to a
+    /// connection on a local port, returning the URL of the server and a handle to join for the
+    /// requests which were received.
+    #[cfg(feature = "remote")]
+    fn serve_http(
+        responses: Vec<(&'static str, String)>,
+    ) -> (String, std::thread::JoinHandle<Vec<String>>) {
+        use std::io::{BufRead, Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let handle = std::thread::spawn(move || {
+            responses
+                .into_iter()
+                .map(|(status, body)| {
+                    let (stream, _) = listener.accept().unwrap();
+                    let mut reader = std::io::BufReader::new(stream);
+                    let mut request = String::new();
+                    let mut content_length = 0;
+                    loop {
+                        let mut line = String::new();
+                        reader.read_line(&mut line).unwrap();
+                        if let Some((name, value)) = line.split_once(':') {
+                            if name.eq_ignore_ascii_case("content-length") {
+                                content_length = value.trim().parse().unwrap();
+                            }
+                        }
+                        request.push_str(&line);
+                        if line == "\r\n" {
+                            break;
+                        }
+                    }
+                    let mut content = vec![0; content_length];
+                    reader.read_exact(&mut content).unwrap();
+                    request.push_str(&String::from_utf8(content).unwrap());
+                    write!(
+                        reader.get_mut(),
+                        "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                        body.len()
+                    )
+                    .unwrap();
+                    request
+                })
+                .collect()
+        });
+        (url, handle)
+    }
 
+    #[cfg(feature = "remote")]
+    #[test]
+    fn initialize_config_location_url() {
+        let (server, requests) = serve_http(vec![
+            ("200 OK", "name = \"remote\"".to_owned()),
+            ("404 Not Found", String::new()),
+        ]);
+        let url = format!("{server}/app.toml");
+        let remote = || crate::RemoteArgs {
+            timeout: std::time::Duration::from_secs(5),
+            headers: vec![("Authorization".to_owned(), "Bearer token".to_owned())],
+            ..crate::RemoteArgs::default()
+        };
+        let (config, report) = crate::initialize_with_report::<Value>(crate::Args {
+            config_location: Some(crate::ConfigLocation::Url(&url)),
+            remote: remote(),
+            ..crate::Args::default()
+        })
+        .unwrap();
+        assert_eq!(config.unwrap()["name"].as_str(), Some("remote"));
         assert!(matches!(
-            error,
-            InsertTomlValueError::ArrayIndexCannotIndex { .. }
-        ))
+            report.source,
+            Some(crate::ConfigSource::Remote { url: source_url }) if source_url == url
+        ));
+
+        // Not found.
+        let config = crate::initialize::<Value>(crate::Args {
+            config_location: Some(crate::ConfigLocation::Url(&url)),
+            remote: remote(),
+            ..crate::Args::default()
+        })
+        .unwrap();
+        assert!(config.is_none());
+
+        let requests = requests.join().unwrap();
+        assert!(requests[0].starts_with("GET /app.toml HTTP/1.1\r\n"));
+        assert!(requests[0].contains("Authorization: Bearer token\r\n"));
+
+        // A server which accepts the connection but never responds.
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/app.toml", listener.local_addr().unwrap());
+        let error = crate::initialize::<Value>(crate::Args {
+            config_location: Some(crate::ConfigLocation::Url(&url)),
+            remote: crate::RemoteArgs {
+                timeout: std::time::Duration::from_millis(100),
+                ..crate::RemoteArgs::default()
+            },
+            ..crate::Args::default()
+        })
+        .unwrap_err();
+        assert!(error.to_string().contains(&url));
+        drop(listener);
+
+        let url = "http://127.0.0.1:1/config.toml";
+        let location = crate::ConfigLocation::parse(url).unwrap();
+        assert!(matches!(location, crate::ConfigLocation::Url(location) if location == url));
+
+        let error = crate::initialize::<Value>(crate::Args {
+            config_location: Some(location),
+            remote: crate::RemoteArgs {
+                timeout: std::time::Duration::from_secs(1),
+                headers: vec![("Authorization".to_owned(), "Bearer token".to_owned())],
+                ..crate::RemoteArgs::default()
+            },
+            ..crate::Args::default()
+        })
+        .unwrap_err();
+        assert!(error.to_string().contains(url));
     }
 
     #[test]
-    fn insert_toml_value_table_child_create_array() {
-        let mut value = toml::Value::Table(toml::Table::new());
-        insert_toml_value(
-            &mut value,
-            "child.0".parse().unwrap(),
-            toml::Value::String("Hello Element".to_owned()),
+    fn toml_key_path_ord() {
+        let paths: std::collections::BTreeSet<crate::TomlKeyPath> =
+            ["b", "a.1", "a.0", "a", "a.key", "a.0"]
+                .into_iter()
+                .map(|path| path.parse().unwrap())
+                .collect();
+        let paths: Vec<String> = paths.iter().map(ToString::to_string).collect();
+        assert_eq!(paths, ["a", "a.key", "a.0", "a.1", "b"]);
+
+        let a: crate::TomlKeyPath = "a.0".parse().unwrap();
+        assert_eq!(a, "a.0".parse().unwrap());
+        assert_ne!(a, "a.\"0\"".parse().unwrap());
+    }
+
+    #[test]
+    fn toml_key_path_serde() {
+        #[derive(Debug, serde::Serialize, serde::Deserialize)]
+        struct Mappings {
+            map_env: std::collections::BTreeMap<String, crate::TomlKeyPath>,
+        }
+
+        let mappings: Mappings = toml::from_str(
+            "[map_env]\nHOST = \"server.host\"\nDOMAIN_PORT = 'servers.\"my.host.com\".port'",
         )
         .unwrap();
         assert_eq!(
-            value
-                .get("child")
-                .unwrap()
-                .get(0)
-                .unwrap()
-                .as_str()
-                .unwrap(),
-            "Hello Element"
+            mappings.map_env["HOST"],
+            "server.host".parse::<crate::TomlKeyPath>().unwrap()
+        );
+        assert_eq!(mappings.map_env["DOMAIN_PORT"].len(), 3);
+
+        let round_trip: Mappings = toml::from_str(&toml::to_string(&mappings).unwrap()).unwrap();
+        assert_eq!(round_trip.map_env, mappings.map_env);
+
+        let error = toml::from_str::<Mappings>("[map_env]\nHOST = 'server.\"host'").unwrap_err();
+        assert!(error.to_string().contains("unterminated quote"));
+    }
+
+    #[test]
+    fn toml_key_path_parse_error() {
+        use crate::{TomlKeyPath, TomlKeyPathParseErrorKind};
+
+        let error = r#"a."b"#.parse::<TomlKeyPath>().unwrap_err();
+        assert_eq!(error.kind, TomlKeyPathParseErrorKind::UnterminatedQuote);
+        assert_eq!(error.position, 2);
+
+        let error = r#"a."b"c"#.parse::<TomlKeyPath>().unwrap_err();
+        assert_eq!(
+            error.kind,
+            TomlKeyPathParseErrorKind::UnexpectedCharacter('c')
+        );
+        assert_eq!(error.position, 5);
+        assert_eq!(
+            error.to_string(),
+            r#"Invalid key path "a.\"b\"c": unexpected character 'c' after quoted segment at position 5"#
+        );
+
+        for (path, position) in [(".a", 0), ("a.", 2), ("a..b", 2), (".", 0)] {
+            let error = TomlKeyPath::parse_strict(path).unwrap_err();
+            assert_eq!(
+                error.kind,
+                TomlKeyPathParseErrorKind::EmptySegment,
+                "{path}"
+            );
+            assert_eq!(error.position, position, "{path}");
+        }
+        assert!(TomlKeyPath::parse_strict("").unwrap().is_empty());
+        assert_eq!(TomlKeyPath::parse_strict(r#"a."".0"#).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn toml_value_get_as() {
+        use crate::TomlValueExt;
+
+        let value: Value = toml::from_str(
+            r#"
+            ports = [8080, 8081]
+
+            [server]
+            host = "localhost"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(value.get_as::<u16>("ports.1").unwrap(), 8081);
+        assert_eq!(value.get_as::<Vec<u16>>("ports").unwrap(), vec![8080, 8081]);
+        assert_eq!(value.get_as::<String>("server.host").unwrap(), "localhost");
+
+        let error = value.get_as::<u16>("server.host").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Error deserializing the value at `server.host`"
+        );
+        let error = value.get_as::<u16>(r#"server."host"#).unwrap_err();
+        assert!(error.to_string().starts_with("Invalid key path"), "{error}");
+    }
+
+    #[test]
+    fn serialize_report() {
+        std::env::set_var("TEST_SERIALIZE_REPORT_PASSWORD", "hunter2");
+        let (_, report) = crate::initialize_with_report::<Value>(crate::Args {
+            defaults: Some("user = \"admin\""),
+            map_env: crate::map_env! {
+                "TEST_SERIALIZE_REPORT_PASSWORD" => "password",
+            },
+            redact: vec!["password".parse().unwrap()],
+            ..crate::Args::default()
+        })
+        .unwrap();
+
+        let serialized = Value::try_from(&report).unwrap();
+        assert_eq!(
+            serialized["version"].as_integer(),
+            Some(crate::REPORT_FORMAT_VERSION.into())
+        );
+        assert_eq!(serialized["config"]["user"].as_str(), Some("admin"));
+        assert_eq!(
+            serialized["config"]["password"].as_str(),
+            Some(crate::REDACTED)
+        );
+        assert_eq!(
+            serialized["provenance"]["password"],
+            toml::toml! {
+                kind = "environment"
+                variable_names = ["TEST_SERIALIZE_REPORT_PASSWORD"]
+            }
+            .into()
+        );
+        assert_eq!(
+            serialized["provenance"]["user"]["kind"].as_str(),
+            Some("defaults")
+        );
+        assert_eq!(serialized["source"]["kind"].as_str(), Some("merged"));
+        assert_eq!(
+            serialized["sources"].as_array().map(Vec::len),
+            Some(report.sources.len())
         );
+        assert_eq!(serialized["warnings"].as_array().map(Vec::len), Some(0));
     }
 }